@@ -1,79 +1,237 @@
 use crate::error::Error;
-use crate::{db, model, AppData};
+use crate::event::Event;
+use crate::secret::{self, Key, Keys};
+use crate::{db, markdown, model, AppData};
+use rusqlite::{Connection, TransactionBehavior};
+use std::collections::HashSet;
+use std::sync::Arc;
 use std::time::SystemTime;
 use uuid::Uuid;
 
+/// Maximum number of tags suggested by `complete_tag_fn`.
+const COMPLETION_LIMIT: usize = 10;
+
 // -----------------------------------------------------------------------------------------------
 
+pub fn append_memo_fn(
+    data: &AppData,
+    topic: &str,
+    text: &str,
+    heading: Option<&str>,
+) -> Result<model::Memo, Error> {
+    let mut db = data.db.writer();
+    // Write lock is taken at start, reads in transaction are not stale by other process.
+    let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let topics = db::Topic::all(&tx)?;
+    let found = if topic.is_empty() {
+        None
+    } else {
+        resolve_topic(&topics, topic)
+    };
+
+    let (topic_id, base) = match found {
+        Some(t) => {
+            let base = match db::Memo::latest_by_topic(&tx, &t.id) {
+                Ok(m) => decrypt(topic_key(data, &tx, &t.id)?.as_deref(), m.content)?,
+                Err(_) => "".to_owned(),
+            };
+            (t.id.clone(), base)
+        }
+        None => {
+            // Create new topic titled by specified name.
+            let base = if topic.is_empty() {
+                "".to_owned()
+            } else {
+                format!("# {topic}\n")
+            };
+            (Uuid::new_v4().to_string(), base)
+        }
+    };
+
+    let content = append_content(&base, text, heading);
+    let key = topic_key(data, &tx, &topic_id)?;
+    let memo = create_memo(&tx, &topic_id, &content, key.as_deref())?;
+    if data.inline_tags && key.is_none() {
+        sync_inline_tags(&tx, &topic_id, &content)?;
+    }
+
+    tx.commit()?;
+    Ok(memo)
+}
+
 pub fn create_memo_fn(data: &AppData, topic_id: &str, content: &str) -> Result<model::Memo, Error> {
+    let mut db = data.db.writer();
+    let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let key = topic_key(data, &tx, topic_id)?;
+    let memo = create_memo(&tx, topic_id, content, key.as_deref())?;
+    // Content of locked topic is not scanned for inline tags.
+    if data.inline_tags && key.is_none() {
+        sync_inline_tags(&tx, topic_id, content)?;
+    }
+
+    tx.commit()?;
+    Ok(memo)
+}
+
+/// Add `#tag` in content to tags of topic, and remove inline tags no longer in content.
+pub(crate) fn sync_inline_tags(
+    db: &Connection,
+    topic_id: &str,
+    content: &str,
+) -> Result<(), Error> {
+    let mut names = vec![];
+    for tag in markdown::inline_tags(content) {
+        let name = db::Tag::resolve(db, &tag)?;
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    for name in db::TopicTag::inline_by_topic(db, topic_id)? {
+        if !names.contains(&name) {
+            let m = db::TopicTag {
+                name,
+                topic_id: topic_id.to_owned(),
+            };
+            m.delete(db)?;
+            Event::TagRemoved {
+                topic_id: m.topic_id,
+                tag: m.name,
+            }
+            .record(db)?;
+        }
+    }
+
+    // Tag added manually is kept manual.
+    let existing = db::TopicTag::all_by_topic(db, topic_id)?;
+    for name in names {
+        if existing.contains(&name) {
+            continue;
+        }
+        db::TopicTag::create_inline(db, &name, topic_id)?;
+        Event::TagAdded {
+            topic_id: topic_id.to_owned(),
+            tag: name,
+        }
+        .record(db)?;
+    }
+
+    Ok(())
+}
+
+/// Save memo, encrypted by key if topic is locked.
+fn create_memo(
+    db: &Connection,
+    topic_id: &str,
+    content: &str,
+    key: Option<&Key>,
+) -> Result<model::Memo, Error> {
     let id = Uuid::new_v4().to_string();
 
     let title = parse_title(content);
+    let (title, stored) = match key {
+        Some(k) => (k.encrypt(&title)?, k.encrypt(content)?),
+        None => (title, content.to_owned()),
+    };
 
     let duration = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap();
     let timestamp = duration.as_secs() as i64;
 
-    let db = data.db.lock()?;
-    if let Some(topic) = db::Topic::all(&db)?.iter().find(|t| t.id == topic_id) {
+    if let Some(topic) = db::Topic::all(db)?.iter().find(|t| t.id == topic_id) {
         // Update exist topic.
-        topic.update(&db, &title, timestamp)?;
+        topic.update(db, &title, timestamp)?;
     } else {
         // Create new topic.
-        db::Topic::create(&db, topic_id, &title, timestamp)?;
+        db::Topic::create(db, topic_id, &title, timestamp)?;
+        Event::TopicCreated {
+            topic_id: topic_id.to_owned(),
+            title,
+        }
+        .record(db)?;
+    }
+
+    let memo = db::Memo::create(db, &id, topic_id, timestamp, &stored)?;
+    log::debug!("created memo id={id} topic_id={topic_id}");
+    Event::MemoSaved {
+        topic_id: topic_id.to_owned(),
+        memo_id: id,
     }
+    .record(db)?;
 
-    let memo = db::Memo::create(&db, &id, topic_id, timestamp, content)?;
     Ok(model::Memo {
         id: memo.id,
         topic_id: memo.topic_id,
         timestamp: memo.timestamp,
         latest: true,
-        content: memo.content,
+        content: content.to_owned(),
     })
 }
 
 pub fn delete_memo_fn(data: &AppData, topic_id: &str, id: Option<&str>) -> Result<usize, Error> {
-    let db = data.db.lock()?;
+    let mut db = data.db.writer();
+    let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
 
-    let memos = db::Memo::all_by_topic(&db, topic_id)?;
+    // Locked topic is deleted only while unlocked.
+    let key = topic_key(data, &tx, topic_id)?;
+
+    let duration = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    let deleted_at = duration.as_secs() as i64;
+
+    // Deleted memos are kept in trash until purged.
+    let memos = db::Memo::all_by_topic(&tx, topic_id)?;
     let mut delete_count = 0;
     for memo in &memos {
         if id.is_none() || memo.id == id.unwrap() {
-            memo.delete(&db)?;
+            memo.trash(&tx, deleted_at)?;
             delete_count += 1;
         }
     }
+    if delete_count == 0 {
+        return Err(Error::NotFound(id.unwrap_or(topic_id).to_owned()));
+    }
 
     let remains = memos.len() - delete_count;
+    log::debug!("deleted memo topic_id={topic_id} count={delete_count} remains={remains}");
 
     if remains == 0 {
-        // Delete related topic.
-        let topics = db::Topic::all(&db)?;
-        for topic in topics {
-            if topic.id == topic_id {
-                topic.delete(&db)?;
-            }
-        }
+        // Move related topic, tags and attachments, while lock is kept for restore.
+        db::TrashTopic::create(&tx, topic_id, deleted_at)?;
 
-        // Delete related tags.
-        let tags = db::TopicTag::all_by_topic(&db, topic_id)?;
-        for tag in tags {
-            let t = db::TopicTag {
-                name: tag.to_owned(),
-                topic_id: topic_id.to_owned(),
-            };
-            t.delete(&db)?;
+        Event::TopicDeleted {
+            topic_id: topic_id.to_owned(),
+        }
+        .record(&tx)?;
+    } else if let Some(memo_id) = id {
+        // Inline tags follow the latest revision, which may be the deleted one.
+        if data.inline_tags && key.is_none() {
+            let latest = db::Memo::latest_by_topic(&tx, topic_id)?;
+            sync_inline_tags(&tx, topic_id, &latest.content)?;
+        }
+        Event::MemoDeleted {
+            topic_id: topic_id.to_owned(),
+            memo_id: memo_id.to_owned(),
         }
+        .record(&tx)?;
     }
 
+    tx.commit()?;
+
+    if remains == 0 {
+        data.keys.remove(topic_id);
+    }
     Ok(remains)
 }
 
 pub fn get_memo_fn(data: &AppData, topic_id: &str, id: Option<&str>) -> Result<model::Memo, Error> {
-    let db = data.db.lock()?;
+    let db = data.db.reader();
 
+    let key = topic_key(data, &db, topic_id)?;
     let memos = db::Memo::all_by_topic(&db, topic_id)?;
 
     if let Ok(latest) = db::Memo::latest_by_topic(&db, topic_id) {
@@ -89,7 +247,7 @@ pub fn get_memo_fn(data: &AppData, topic_id: &str, id: Option<&str>) -> Result<m
             topic_id: memo.topic_id.clone(),
             timestamp: memo.timestamp,
             latest: memo.id == latest.id,
-            content: memo.content.clone(),
+            content: decrypt(key.as_deref(), memo.content.clone())?,
         })
     } else {
         Ok(model::Memo {
@@ -103,7 +261,8 @@ pub fn get_memo_fn(data: &AppData, topic_id: &str, id: Option<&str>) -> Result<m
 }
 
 pub fn get_memo_all_fn(data: &AppData, topic_id: &str) -> Result<Vec<model::Memo>, Error> {
-    let db = data.db.lock()?;
+    let db = data.db.reader();
+    let key = topic_key(data, &db, topic_id)?;
     let memos = db::Memo::all_by_topic(&db, topic_id)?;
 
     let mut models = vec![];
@@ -113,7 +272,7 @@ pub fn get_memo_all_fn(data: &AppData, topic_id: &str) -> Result<Vec<model::Memo
             topic_id: memo.topic_id,
             timestamp: memo.timestamp,
             latest: false,
-            content: memo.content,
+            content: decrypt(key.as_deref(), memo.content)?,
         });
     }
 
@@ -127,7 +286,22 @@ pub fn get_memo_all_fn(data: &AppData, topic_id: &str) -> Result<Vec<model::Memo
     Ok(models)
 }
 
-fn parse_title(content: &str) -> String {
+fn append_content(base: &str, text: &str, heading: Option<&str>) -> String {
+    let mut content = base.trim_end().to_owned();
+    if !content.is_empty() {
+        content.push_str("\n\n");
+    }
+
+    if let Some(h) = heading {
+        content.push_str(&format!("## {h}\n\n"));
+    }
+
+    content.push_str(text.trim_end());
+    content.push('\n');
+    content
+}
+
+pub(crate) fn parse_title(content: &str) -> String {
     content
         .chars()
         .skip_while(|c| c == &'#')
@@ -138,27 +312,79 @@ fn parse_title(content: &str) -> String {
         .to_owned()
 }
 
+/// Resolve topic by id or title, also by title shortened by `parse_title` like topic created
+/// by `append_memo_fn` with long name.
+fn resolve_topic<'a>(topics: &'a [db::Topic], target: &str) -> Option<&'a db::Topic> {
+    db::Topic::resolve(topics, target).or_else(|| {
+        let title = parse_title(&format!("# {target}"));
+        (!title.is_empty())
+            .then(|| db::Topic::resolve(topics, &title))
+            .flatten()
+    })
+}
+
 // -----------------------------------------------------------------------------------------------
 
 pub fn delete_topic_fn(data: &AppData, topic_id: &str) -> Result<usize, Error> {
     delete_memo_fn(data, topic_id, None)
 }
 
+pub fn find_topic_fn(data: &AppData, target: &str) -> Result<model::Topic, Error> {
+    let db = data.db.reader();
+    let topics = db::Topic::all(&db)?;
+    let topic = resolve_topic(&topics, target).ok_or_else(|| Error::NotFound(target.to_owned()))?;
+
+    let locked = db::TopicLock::find(&db, &topic.id)?.is_some();
+    Ok(topic_model(topic, locked, &data.keys))
+}
+
 pub fn get_topics_fn(data: &AppData, keyword: &str) -> Result<Vec<model::Topic>, Error> {
-    let db = data.db.lock()?;
+    let db = data.db.reader();
+    query_topics(&db, keyword, &data.keys)
+}
+
+/// Search topics unless superseded by newer search, see `Searches::begin`.
+pub fn search_topics_fn(
+    data: &AppData,
+    keyword: &str,
+    ticket: u64,
+) -> Result<Vec<model::Topic>, Error> {
+    if data.searches.is_superseded(ticket) {
+        return Err(Error::Cancelled);
+    }
+
+    let db = data.db.reader();
+    data.searches
+        .run(ticket, &db, |conn| query_topics(conn, keyword, &data.keys))
+        .unwrap_or(Err(Error::Cancelled))
+}
+
+fn query_topics(db: &Connection, keyword: &str, keys: &Keys) -> Result<Vec<model::Topic>, Error> {
+    let locked: HashSet<String> = db::TopicLock::all(db)?
+        .into_iter()
+        .map(|l| l.topic_id)
+        .collect();
+
     let topics = if keyword.is_empty() {
-        db::Topic::all(&db)
+        db::Topic::all(db)?
     } else {
-        db::Topic::search(&db, keyword)
-    }?;
+        // Encrypted content is searched only while unlocked.
+        let mut topics = db::Topic::search(db, keyword)?;
+        topics.retain(|t| !locked.contains(&t.id));
+        for topic in db::Topic::all(db)? {
+            if let Some(key) = locked.get(&topic.id).and_then(|id| keys.get(id)) {
+                if match_locked(db, &topic.id, &key, keyword)? {
+                    topics.push(topic);
+                }
+            }
+        }
+        topics
+    };
 
     let mut models = vec![];
     for topic in topics {
-        models.push(model::Topic {
-            id: topic.id,
-            title: topic.title,
-            timestamp: topic.timestamp,
-        });
+        let is_locked = locked.contains(&topic.id);
+        models.push(topic_model(&topic, is_locked, keys));
     }
 
     models.sort_unstable_by_key(|t| t.timestamp);
@@ -167,38 +393,529 @@ pub fn get_topics_fn(data: &AppData, keyword: &str) -> Result<Vec<model::Topic>,
     Ok(models)
 }
 
+/// Topic whose title is hidden if locked, or decrypted if unlocked in this session.
+pub(crate) fn topic_model(topic: &db::Topic, locked: bool, keys: &Keys) -> model::Topic {
+    let title = if locked {
+        keys.get(&topic.id)
+            .and_then(|k| k.decrypt(&topic.title).ok())
+            .unwrap_or_default()
+    } else {
+        topic.title.clone()
+    };
+
+    model::Topic {
+        id: topic.id.clone(),
+        title,
+        timestamp: topic.timestamp,
+        locked,
+    }
+}
+
+/// Same condition as `db::Topic::search` for decrypted content of locked topic.
+fn match_locked(db: &Connection, topic_id: &str, key: &Key, keyword: &str) -> Result<bool, Error> {
+    let (words, tags) = db::Topic::split_keyword(keyword);
+
+    if !tags.is_empty() {
+        let tags = tags
+            .iter()
+            .map(|t| db::Tag::resolve(db, t))
+            .collect::<Result<Vec<_>, _>>()?;
+        let topic_tags = db::TopicTag::all_by_topic(db, topic_id)?;
+        if !tags
+            .iter()
+            .any(|t| topic_tags.iter().any(|n| db::TopicTag::is_under(n, t)))
+        {
+            return Ok(false);
+        }
+    }
+
+    if !words.is_empty() {
+        let words: Vec<String> = words.iter().map(|w| w.to_lowercase()).collect();
+        let mut found = false;
+        for memo in db::Memo::all_by_topic(db, topic_id)? {
+            let content = key.decrypt(&memo.content)?.to_lowercase();
+            if words.iter().any(|w| content.contains(w)) {
+                found = true;
+                break;
+            }
+        }
+        if !found {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Encrypt all revisions, title, tags and attachments of topic by passphrase, which is locked
+/// until unlocked.
+pub fn lock_topic_fn(data: &AppData, topic_id: &str, passphrase: &str) -> Result<(), Error> {
+    let salt = secret::random::<{ secret::SALT_LEN }>()?;
+    let key = Key::derive(passphrase, &salt)?;
+
+    let mut db = data.db.writer();
+    // Overwrite plain text in freed pages, only while locking not to slow down other writes.
+    let secure_delete: bool = db.pragma_query_value(None, "secure_delete", |r| r.get(0))?;
+    db.pragma_update(None, "secure_delete", true)?;
+    let locked = lock_topic(&mut db, topic_id, &salt, &key);
+    db.pragma_update(None, "secure_delete", secure_delete)?;
+    locked?;
+    data.keys.remove(topic_id);
+
+    // Plain text is left in write-ahead log until checkpoint.
+    db.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+    Ok(())
+}
+
+fn lock_topic(db: &mut Connection, topic_id: &str, salt: &[u8], key: &Key) -> Result<(), Error> {
+    let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    if db::TopicLock::find(&tx, topic_id)?.is_some() {
+        return Err(Error::Internal(format!("already locked: {topic_id}")));
+    }
+
+    let topics = db::Topic::all(&tx)?;
+    let topic = topics
+        .iter()
+        .find(|t| t.id == topic_id)
+        .ok_or_else(|| Error::NotFound(topic_id.to_owned()))?;
+
+    for memo in db::Memo::all_by_topic(&tx, topic_id)? {
+        memo.update_content(&tx, &key.encrypt(&memo.content)?)?;
+    }
+    // Memos restored from trash are also locked.
+    for memo in db::TrashMemo::all_by_topic(&tx, topic_id)? {
+        memo.update_content(&tx, &key.encrypt(&memo.content)?)?;
+    }
+    topic.update(&tx, &key.encrypt(&topic.title)?, topic.timestamp)?;
+    db::Event::clear_title(&tx, topic_id)?;
+    map_topic_tags(&tx, topic_id, |name| key.encrypt(name))?;
+    for a in db::Attachment::all_by_topic(&tx, topic_id)? {
+        a.update(&tx, &key.encrypt(&a.name)?, &key.encrypt_bytes(&a.content)?)?;
+    }
+    db::TopicLock::create(&tx, topic_id, salt, &key.encrypt(topic_id)?)?;
+
+    tx.commit()?;
+    Ok(())
+}
+
+/// Keep key of locked topic in this session, fails with `Error::Passphrase` if wrong.
+pub fn unlock_topic_fn(data: &AppData, topic_id: &str, passphrase: &str) -> Result<(), Error> {
+    let key = verified_key(data, topic_id, passphrase)?;
+    data.keys.insert(topic_id, key);
+    Ok(())
+}
+
+/// Key derived from passphrase, which fails with `Error::Passphrase` if wrong.
+fn verified_key(data: &AppData, topic_id: &str, passphrase: &str) -> Result<Key, Error> {
+    let lock = {
+        let db = data.db.reader();
+        db::TopicLock::find(&db, topic_id)?.ok_or_else(|| Error::NotFound(topic_id.to_owned()))?
+    };
+
+    let key = Key::derive(passphrase, &lock.salt)?;
+    key.decrypt(&lock.verifier)?;
+    Ok(key)
+}
+
+/// Forget key of unlocked topic.
+pub fn relock_topic_fn(data: &AppData, topic_id: &str) -> Result<(), Error> {
+    data.keys.remove(topic_id);
+    Ok(())
+}
+
+/// Decrypt all revisions, title, tags and attachments of topic, and remove its lock.
+pub fn remove_topic_lock_fn(data: &AppData, topic_id: &str, passphrase: &str) -> Result<(), Error> {
+    // Key is not kept in session until lock is removed.
+    let key = verified_key(data, topic_id, passphrase)?;
+
+    let mut db = data.db.writer();
+    let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    for memo in db::Memo::all_by_topic(&tx, topic_id)? {
+        memo.update_content(&tx, &key.decrypt(&memo.content)?)?;
+    }
+    for memo in db::TrashMemo::all_by_topic(&tx, topic_id)? {
+        memo.update_content(&tx, &key.decrypt(&memo.content)?)?;
+    }
+    // Title is taken from memo, since topic repaired while locked has empty title.
+    if let Some(topic) = db::Topic::all(&tx)?.iter().find(|t| t.id == topic_id) {
+        let title = match db::Memo::latest_by_topic(&tx, topic_id) {
+            Ok(memo) => parse_title(&memo.content),
+            Err(_) => key.decrypt(&topic.title).unwrap_or_default(),
+        };
+        topic.update(&tx, &title, topic.timestamp)?;
+    }
+    map_topic_tags(&tx, topic_id, |name| key.decrypt(name))?;
+    for a in db::Attachment::all_by_topic(&tx, topic_id)? {
+        a.update(&tx, &key.decrypt(&a.name)?, &key.decrypt_bytes(&a.content)?)?;
+    }
+    if let Some(lock) = db::TopicLock::find(&tx, topic_id)? {
+        lock.delete(&tx)?;
+    }
+
+    tx.commit()?;
+    data.keys.remove(topic_id);
+    Ok(())
+}
+
+/// Key of topic if locked, which fails with `Error::Passphrase` unless unlocked.
+fn topic_key(data: &AppData, db: &Connection, topic_id: &str) -> Result<Option<Arc<Key>>, Error> {
+    match db::TopicLock::find(db, topic_id)? {
+        Some(_) => data.keys.get(topic_id).map(Some).ok_or(Error::Passphrase),
+        None => Ok(None),
+    }
+}
+
+fn decrypt(key: Option<&Key>, content: String) -> Result<String, Error> {
+    match key {
+        Some(k) => k.decrypt(&content),
+        None => Ok(content),
+    }
+}
+
+/// Replace names of tags of topic by `f`, keeping inline ones inline.
+fn map_topic_tags<F>(db: &Connection, topic_id: &str, f: F) -> Result<(), Error>
+where
+    F: Fn(&str) -> Result<String, Error>,
+{
+    let inline = db::TopicTag::inline_by_topic(db, topic_id)?;
+    let names = db::TopicTag::all_by_topic(db, topic_id)?;
+    db::TopicTag::delete_by_topic(db, topic_id)?;
+
+    for name in names {
+        if inline.contains(&name) {
+            db::TopicTag::create_inline(db, &f(&name)?, topic_id)?;
+        } else {
+            db::TopicTag::create(db, &f(&name)?, topic_id)?;
+        }
+    }
+    Ok(())
+}
+
 // -----------------------------------------------------------------------------------------------
 
 pub fn add_memo_tag_fn(data: &AppData, topic_id: &str, name: &str) -> Result<(), Error> {
-    let db = data.db.lock()?;
-    db::TopicTag::create(&db, name, topic_id)?;
+    if !markdown::is_valid_tag(name) {
+        return Err(Error::Invalid(format!("tag: {name}")));
+    }
+
+    let mut db = data.db.writer();
+    let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let key = topic_key(data, &tx, topic_id)?;
+    let name = db::Tag::resolve(&tx, name)?;
+    // Inline tag added manually is kept after removed from content.
+    delete_topic_tag(&tx, topic_id, &name, key.as_deref())?;
+    match &key {
+        Some(k) => db::TopicTag::create(&tx, &k.encrypt(&name)?, topic_id)?,
+        None => db::TopicTag::create(&tx, &name, topic_id)?,
+    };
+    Event::TagAdded {
+        topic_id: topic_id.to_owned(),
+        tag: if key.is_some() { String::new() } else { name },
+    }
+    .record(&tx)?;
+
+    tx.commit()?;
     Ok(())
 }
 
 pub fn remove_memo_tag_fn(data: &AppData, topic_id: &str, name: &str) -> Result<(), Error> {
-    let db = data.db.lock()?;
-    let m = db::TopicTag {
-        name: name.to_owned(),
+    let mut db = data.db.writer();
+    let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let key = topic_key(data, &tx, topic_id)?;
+    // Tag is added by name resolved from alias.
+    let name = db::Tag::resolve(&tx, name)?;
+    delete_topic_tag(&tx, topic_id, &name, key.as_deref())?;
+    Event::TagRemoved {
         topic_id: topic_id.to_owned(),
-    };
-    m.delete(&db)?;
+        tag: if key.is_some() { String::new() } else { name },
+    }
+    .record(&tx)?;
+
+    tx.commit()?;
+    Ok(())
+}
+
+/// Delete tag of topic, found by decrypted name if locked since encrypted name differs each time.
+fn delete_topic_tag(
+    db: &Connection,
+    topic_id: &str,
+    name: &str,
+    key: Option<&Key>,
+) -> Result<(), Error> {
+    for stored in db::TopicTag::all_by_topic(db, topic_id)? {
+        if decrypt(key, stored.clone())? == name {
+            let m = db::TopicTag {
+                name: stored,
+                topic_id: topic_id.to_owned(),
+            };
+            m.delete(db)?;
+        }
+    }
+    Ok(())
+}
+
+/// Tags of topic with their metadata.
+pub fn get_memo_tag_fn(data: &AppData, topic_id: &str) -> Result<Vec<model::TopicTag>, Error> {
+    let db = data.db.reader();
+    let key = topic_key(data, &db, topic_id)?;
+    let metadata = db::Tag::all(&db)?;
+    let inline = db::TopicTag::inline_by_topic(&db, topic_id)?;
+
+    let mut tags = vec![];
+    for stored in db::TopicTag::all_by_topic(&db, topic_id)? {
+        let inline = inline.contains(&stored);
+        let name = decrypt(key.as_deref(), stored)?;
+        let tag = metadata.iter().find(|t| t.name == name);
+        tags.push(model::TopicTag {
+            inline,
+            color: tag.and_then(|t| t.color.clone()),
+            description: tag.and_then(|t| t.description.clone()),
+            aliases: tag.map(|t| t.aliases.clone()).unwrap_or_default(),
+            name,
+        });
+    }
+    Ok(tags)
+}
+
+/// Set metadata of tag, and merge tags of `aliases` into it so they are resolved into it.
+/// Color or description of None is kept and empty one is cleared, aliases of None are kept.
+pub fn set_tag_fn(
+    data: &AppData,
+    name: &str,
+    color: Option<&str>,
+    description: Option<&str>,
+    aliases: Option<&[String]>,
+) -> Result<(), Error> {
+    // Color is written into style of tag element.
+    if let Some(color) = color.filter(|c| !c.is_empty() && !is_color(c)) {
+        return Err(Error::Invalid(format!("color: {color}")));
+    }
+
+    let mut db = data.db.writer();
+    let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let tags = db::Tag::all(&tx)?;
+    if let Some(tag) = tags.iter().find(|t| t.aliases.iter().any(|a| a == name)) {
+        return Err(Error::Invalid(format!(
+            "tag is alias of {}: {name}",
+            tag.name
+        )));
+    }
+    for alias in aliases.unwrap_or_default() {
+        // Alias in hierarchy of tag would rename tag itself when merged.
+        if alias.is_empty()
+            || db::TopicTag::is_under(alias, name)
+            || db::TopicTag::is_under(name, alias)
+            || tags.iter().any(|t| &t.name == alias)
+        {
+            return Err(Error::Invalid(format!("alias: {alias}")));
+        }
+        if tags
+            .iter()
+            .any(|t| t.name != name && t.aliases.contains(alias))
+        {
+            return Err(Error::Invalid(format!("alias already used: {alias}")));
+        }
+    }
+
+    db::Tag::upsert(&tx, name, color, description)?;
+    if let Some(aliases) = aliases {
+        db::Tag::set_aliases(&tx, name, aliases)?;
+        for alias in aliases {
+            db::TopicTag::rename(&tx, alias, name)?;
+        }
+    }
+    Event::DatabaseChanged.record(&tx)?;
+
+    tx.commit()?;
     Ok(())
 }
 
-pub fn get_memo_tag_fn(data: &AppData, topic_id: &str) -> Result<Vec<String>, Error> {
-    let db = data.db.lock()?;
-    let tags = db::TopicTag::all_by_topic(&db, topic_id)?;
+/// Whether `color` is like `#ffcc00`.
+fn is_color(color: &str) -> bool {
+    color.len() == 7 && color.starts_with('#') && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// All tags with their usage.
+pub fn get_all_tags_fn(data: &AppData) -> Result<Vec<model::Tag>, Error> {
+    let db = data.db.reader();
+    let tags = db::TopicTag::usages(&db)?
+        .into_iter()
+        .map(|u| model::Tag {
+            name: u.name,
+            count: u.count,
+            last_used: u.last_used,
+        })
+        .collect();
     Ok(tags)
 }
 
+/// Existing tags completing `input`, which starts them, starts their child, contains it, or
+/// contains its characters in order, ranked in this order then by usage.
+pub fn complete_tag_fn(data: &AppData, input: &str) -> Result<Vec<String>, Error> {
+    let input = input.trim_start_matches('#').to_lowercase();
+    if input.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut matches: Vec<_> = get_all_tags_fn(data)?
+        .into_iter()
+        .filter_map(|t| completion_rank(&t.name.to_lowercase(), &input).map(|r| (r, t)))
+        .collect();
+    matches.sort_by(|(r1, t1), (r2, t2)| {
+        r1.cmp(r2)
+            .then(t2.count.cmp(&t1.count))
+            .then(t2.last_used.cmp(&t1.last_used))
+    });
+
+    Ok(matches
+        .into_iter()
+        .take(COMPLETION_LIMIT)
+        .map(|(_, t)| t.name)
+        .collect())
+}
+
+fn completion_rank(name: &str, input: &str) -> Option<usize> {
+    if name.starts_with(input) {
+        Some(0)
+    } else if name.split('/').any(|s| s.starts_with(input)) {
+        Some(1)
+    } else if name.contains(input) {
+        Some(2)
+    } else {
+        let mut chars = name.chars();
+        input.chars().all(|c| chars.any(|n| n == c)).then_some(3)
+    }
+}
+
+/// Rename tag with its descendants across all topics, failing if new name is used.
+pub fn rename_tag_fn(data: &AppData, from: &str, to: &str) -> Result<usize, Error> {
+    retag(data, from, to, false)
+}
+
+/// Merge tag with its descendants into other tag across all topics.
+pub fn merge_tag_fn(data: &AppData, from: &str, into: &str) -> Result<usize, Error> {
+    retag(data, from, into, true)
+}
+
+/// Delete tag with its descendants from all topics.
+pub fn delete_tag_fn(data: &AppData, name: &str) -> Result<usize, Error> {
+    let mut db = data.db.writer();
+    let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let count = db::TopicTag::delete_all(&tx, name)?;
+    if count == 0 {
+        return Err(Error::NotFound(name.to_owned()));
+    }
+    db::Tag::delete_all(&tx, name)?;
+    Event::DatabaseChanged.record(&tx)?;
+
+    tx.commit()?;
+    log::debug!("deleted tag name={name} count={count}");
+    Ok(count)
+}
+
+fn retag(data: &AppData, from: &str, to: &str, merge: bool) -> Result<usize, Error> {
+    if !markdown::is_valid_tag(to) {
+        return Err(Error::Invalid(format!("tag: {to}")));
+    }
+
+    let mut db = data.db.writer();
+    let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let names = db::TopicTag::all(&tx)?;
+    if !names.iter().any(|n| db::TopicTag::is_under(n, from)) {
+        return Err(Error::NotFound(from.to_owned()));
+    }
+    if !merge && names.iter().any(|n| db::TopicTag::is_under(n, to)) {
+        return Err(Error::Invalid(format!("tag already exists: {to}")));
+    }
+
+    let count = db::TopicTag::rename(&tx, from, to)?;
+    db::Tag::rename(&tx, from, to)?;
+    Event::DatabaseChanged.record(&tx)?;
+
+    tx.commit()?;
+    log::debug!("renamed tag from={from} to={to} count={count}");
+    Ok(count)
+}
+
 // -----------------------------------------------------------------------------------------------
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::db::create_table_if_not_exists;
-    use rusqlite::Connection;
-    use std::sync::Mutex;
+    use crate::event;
+    use crate::testing::setup_appdate;
+
+    #[test]
+    fn test_append_memo_fn_exist_topic() {
+        let data = setup_appdate();
+        {
+            let conn = data.db.writer();
+            db::Topic::create(&conn, "t1", "title1", 0).unwrap();
+            db::Memo::create(&conn, "m1", "t1", 0, "# title1\n").unwrap();
+        }
+
+        let memo = append_memo_fn(&data, "title1", "text\n", Some("heading")).unwrap();
+        assert_eq!("t1", memo.topic_id);
+        assert_eq!("# title1\n\n## heading\n\ntext\n", memo.content);
+
+        let memos = get_memo_all_fn(&data, "t1").unwrap();
+        assert_eq!(2, memos.len());
+    }
+
+    #[test]
+    fn test_append_memo_fn_new_topic() {
+        let data = setup_appdate();
+
+        let memo = append_memo_fn(&data, "inbox", "text", None).unwrap();
+        assert_ne!("inbox", memo.topic_id);
+        assert_eq!("# inbox\n\ntext\n", memo.content);
+
+        let topic = find_topic_fn(&data, "inbox").unwrap();
+        assert_eq!(memo.topic_id, topic.id);
+    }
+
+    #[test]
+    fn test_append_memo_fn_long_topic() {
+        let data = setup_appdate();
+
+        let first = append_memo_fn(&data, "Meeting notes", "a", None).unwrap();
+        let second = append_memo_fn(&data, "Meeting notes", "b", None).unwrap();
+        assert_eq!(first.topic_id, second.topic_id);
+        assert_eq!("# Meeting notes\n\na\n\nb\n", second.content);
+        assert_eq!(1, get_topics_fn(&data, "").unwrap().len());
+
+        let topic = find_topic_fn(&data, "Meeting notes").unwrap();
+        assert_eq!(first.topic_id, topic.id);
+    }
+
+    #[test]
+    fn test_append_memo_fn_no_topic() {
+        let data = setup_appdate();
+        {
+            let conn = data.db.writer();
+            db::Topic::create(&conn, "t1", "", 0).unwrap();
+        }
+
+        let memo = append_memo_fn(&data, "", "text", None).unwrap();
+        assert_ne!("t1", memo.topic_id);
+        assert_eq!("text\n", memo.content);
+    }
+
+    #[test]
+    fn test_append_content() {
+        assert_eq!("a\n", append_content("", "a\n\n", None));
+        assert_eq!("## h\n\na\n", append_content("", "a", Some("h")));
+        assert_eq!("b\n\na\n", append_content("b\n\n", "a", None));
+    }
 
     #[test]
     fn test_create_memo_fn_new_topic() {
@@ -211,7 +928,7 @@ mod tests {
         assert_eq!("content1", memo.content);
 
         {
-            let conn = data.db.lock().unwrap();
+            let conn = data.db.writer();
 
             let memos = db::Memo::all_by_topic(&conn, "t1").unwrap();
             for m in memos {
@@ -233,7 +950,7 @@ mod tests {
     fn test_create_memo_fn_exist_topic() {
         let data = setup_appdate();
         {
-            let conn = data.db.lock().unwrap();
+            let conn = data.db.writer();
             db::Topic::create(&conn, "t1", "", 0).unwrap();
         }
 
@@ -244,7 +961,7 @@ mod tests {
         assert_eq!("content2content2", memo.content);
 
         {
-            let conn = data.db.lock().unwrap();
+            let conn = data.db.writer();
 
             let memos = db::Memo::all_by_topic(&conn, "t1").unwrap();
             for m in memos {
@@ -266,18 +983,19 @@ mod tests {
     fn test_delete_memo_fn_id_default() {
         let data = setup_appdate();
         {
-            let conn = data.db.lock().unwrap();
+            let conn = data.db.writer();
             db::Memo::create(&conn, "m1", "t1", 0, "content1").unwrap();
             db::Memo::create(&conn, "m2", "t1", 0, "content2").unwrap();
             db::Topic::create(&conn, "t1", "title1", 0).unwrap();
             db::TopicTag::create(&conn, "tag1", "t1").unwrap();
+            db::Attachment::create(&conn, "a1", "t1", "a.png", &[]).unwrap();
         }
 
         let deleted = delete_memo_fn(&data, "t1", None).unwrap();
         assert_eq!(0, deleted);
 
         {
-            let conn = data.db.lock().unwrap();
+            let conn = data.db.writer();
 
             let memos = db::Memo::all_by_topic(&conn, "t1").unwrap();
             assert_eq!(0, memos.len());
@@ -288,6 +1006,9 @@ mod tests {
 
             let tags = db::TopicTag::all_by_topic(&conn, "t1").unwrap();
             assert_eq!(0, tags.len());
+
+            let attachments = db::Attachment::all_by_topic(&conn, "t1").unwrap();
+            assert_eq!(0, attachments.len());
         }
     }
 
@@ -295,7 +1016,7 @@ mod tests {
     fn test_delete_memo_fn_all() {
         let data = setup_appdate();
         {
-            let conn = data.db.lock().unwrap();
+            let conn = data.db.writer();
             db::Memo::create(&conn, "m1", "t1", 0, "content1").unwrap();
             db::Topic::create(&conn, "t1", "title1", 0).unwrap();
             db::TopicTag::create(&conn, "tag1", "t1").unwrap();
@@ -305,7 +1026,7 @@ mod tests {
         assert_eq!(0, deleted);
 
         {
-            let conn = data.db.lock().unwrap();
+            let conn = data.db.writer();
 
             let memos = db::Memo::all_by_topic(&conn, "t1").unwrap();
             assert_eq!(0, memos.len());
@@ -323,7 +1044,7 @@ mod tests {
     fn test_delete_memo_fn_not_all() {
         let data = setup_appdate();
         {
-            let conn = data.db.lock().unwrap();
+            let conn = data.db.writer();
             db::Memo::create(&conn, "m1", "t1", 0, "content1").unwrap();
             db::Memo::create(&conn, "m2", "t1", 0, "content2").unwrap();
             db::Topic::create(&conn, "t1", "title1", 0).unwrap();
@@ -334,7 +1055,7 @@ mod tests {
         assert_eq!(1, deleted);
 
         {
-            let conn = data.db.lock().unwrap();
+            let conn = data.db.writer();
 
             let memos = db::Memo::all_by_topic(&conn, "t1").unwrap();
             assert_eq!(1, memos.len());
@@ -348,11 +1069,159 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_create_memo_fn_rollback() {
+        let data = setup_appdate();
+        inject_failure(&data, "INSERT ON memo");
+
+        let e = create_memo_fn(&data, "t1", "# title1");
+        assert!(e.is_err());
+
+        {
+            let conn = data.db.writer();
+            let topics = db::Topic::all(&conn).unwrap();
+            assert_eq!(0, topics.len());
+        }
+    }
+
+    #[test]
+    fn test_append_memo_fn_rollback() {
+        let data = setup_appdate();
+        {
+            let conn = data.db.writer();
+            db::Topic::create(&conn, "t1", "title1", 0).unwrap();
+            db::Memo::create(&conn, "m1", "t1", 0, "# title1\n").unwrap();
+        }
+        inject_failure(&data, "INSERT ON memo");
+
+        let e = append_memo_fn(&data, "t1", "text", None);
+        assert!(e.is_err());
+
+        {
+            let conn = data.db.writer();
+            let topics = db::Topic::all(&conn).unwrap();
+            assert_eq!("title1", topics[0].title);
+            assert_eq!(0, topics[0].timestamp);
+        }
+    }
+
+    #[test]
+    fn test_delete_memo_fn_not_found() {
+        let data = setup_appdate();
+        create_memo_fn(&data, "t1", "# title1").unwrap();
+        let (seq, _) = crate::event::poll(&data, 0).unwrap();
+
+        let e = delete_memo_fn(&data, "t1", Some("m2")).err();
+        assert!(matches!(e, Some(Error::NotFound(_))));
+        let e = delete_memo_fn(&data, "t2", None).err();
+        assert!(matches!(e, Some(Error::NotFound(_))));
+        let e = delete_memo_fn(&data, "t2", Some("m2")).err();
+        assert!(matches!(e, Some(Error::NotFound(_))));
+
+        let conn = data.db.reader();
+        assert_eq!(1, db::Memo::all_by_topic(&conn, "t1").unwrap().len());
+        assert_eq!(0, db::Event::all_since(&conn, seq).unwrap().len());
+    }
+
+    #[test]
+    fn test_delete_memo_fn_rollback() {
+        let data = setup_appdate();
+        {
+            let conn = data.db.writer();
+            db::Memo::create(&conn, "m1", "t1", 0, "content1").unwrap();
+            db::Memo::create(&conn, "m2", "t1", 0, "content2").unwrap();
+            db::Topic::create(&conn, "t1", "title1", 0).unwrap();
+            db::TopicTag::create(&conn, "tag1", "t1").unwrap();
+        }
+        inject_failure(&data, "DELETE ON topic_tag");
+
+        let e = delete_topic_fn(&data, "t1");
+        assert!(e.is_err());
+
+        {
+            let conn = data.db.writer();
+
+            let memos = db::Memo::all_by_topic(&conn, "t1").unwrap();
+            assert_eq!(2, memos.len());
+
+            let topics = db::Topic::all(&conn).unwrap();
+            assert_eq!(1, topics.len());
+        }
+    }
+
+    #[test]
+    fn test_events() {
+        let data = setup_appdate();
+
+        let memo = create_memo_fn(&data, "t1", "# title1").unwrap();
+        create_memo_fn(&data, "t1", "# title2").unwrap();
+        add_memo_tag_fn(&data, "t1", "tag1").unwrap();
+        delete_topic_fn(&data, "t1").unwrap();
+
+        let (_, events) = event::poll(&data, 0).unwrap();
+        assert_eq!(5, events.len());
+        assert_eq!(
+            Event::TopicCreated {
+                topic_id: "t1".to_owned(),
+                title: "title1".to_owned()
+            },
+            events[0]
+        );
+        assert_eq!(
+            Event::MemoSaved {
+                topic_id: "t1".to_owned(),
+                memo_id: memo.id
+            },
+            events[1]
+        );
+        let names: Vec<_> = events[2..].iter().map(|e| e.name()).collect();
+        assert_eq!(vec!["memo-saved", "tag-added", "topic-deleted"], names);
+    }
+
+    #[test]
+    fn test_events_memo_deleted_tag_removed() {
+        let data = setup_appdate();
+
+        let memo = create_memo_fn(&data, "t1", "# title1").unwrap();
+        create_memo_fn(&data, "t1", "# title2").unwrap();
+        add_memo_tag_fn(&data, "t1", "tag1").unwrap();
+        let (seq, _) = event::poll(&data, 0).unwrap();
+
+        remove_memo_tag_fn(&data, "t1", "tag1").unwrap();
+        delete_memo_fn(&data, "t1", Some(&memo.id)).unwrap();
+
+        let (_, events) = event::poll(&data, seq).unwrap();
+        assert_eq!(
+            vec![
+                Event::TagRemoved {
+                    topic_id: "t1".to_owned(),
+                    tag: "tag1".to_owned()
+                },
+                Event::MemoDeleted {
+                    topic_id: "t1".to_owned(),
+                    memo_id: memo.id
+                }
+            ],
+            events
+        );
+    }
+
+    #[test]
+    fn test_events_rollback() {
+        let data = setup_appdate();
+        inject_failure(&data, "INSERT ON memo");
+
+        assert!(create_memo_fn(&data, "t1", "# title1").is_err());
+
+        let (_, events) = event::poll(&data, 0).unwrap();
+        assert!(events.is_empty());
+    }
+
     #[test]
     fn test_get_memo_fn_latest() {
         let data = setup_appdate();
         {
-            let conn = data.db.lock().unwrap();
+            let conn = data.db.writer();
             db::Memo::create(&conn, "m1", "t1", 0, "content1").unwrap();
         }
 
@@ -368,7 +1237,7 @@ mod tests {
     fn test_get_memo_fn_no_latest() {
         let data = setup_appdate();
         {
-            let conn = data.db.lock().unwrap();
+            let conn = data.db.writer();
             db::Memo::create(&conn, "m1", "t1", 0, "content1").unwrap();
             db::Memo::create(&conn, "m2", "t1", 1, "content2").unwrap();
         }
@@ -385,7 +1254,7 @@ mod tests {
     fn test_get_memo_fn_default() {
         let data = setup_appdate();
         {
-            let conn = data.db.lock().unwrap();
+            let conn = data.db.writer();
             db::Memo::create(&conn, "m1", "t1", 0, "content1").unwrap();
             db::Memo::create(&conn, "m2", "t1", 1, "content2").unwrap();
         }
@@ -402,7 +1271,7 @@ mod tests {
     fn test_get_memo_fn_new() {
         let data = setup_appdate();
         {
-            let conn = data.db.lock().unwrap();
+            let conn = data.db.writer();
             db::Memo::create(&conn, "m1", "t1", 0, "content1").unwrap();
             db::Memo::create(&conn, "m2", "t1", 1, "content2").unwrap();
         }
@@ -419,7 +1288,7 @@ mod tests {
     fn test_get_memo_all_fn() {
         let data = setup_appdate();
         {
-            let conn = data.db.lock().unwrap();
+            let conn = data.db.writer();
             db::Memo::create(&conn, "m1", "t1", 0, "content1").unwrap();
         }
 
@@ -460,11 +1329,31 @@ mod tests {
         assert_eq!("0123456789", &t);
     }
 
+    #[test]
+    fn test_find_topic_fn() {
+        let data = setup_appdate();
+        {
+            let conn = data.db.writer();
+            db::Topic::create(&conn, "t1", "title1", 0).unwrap();
+        }
+
+        let topic = find_topic_fn(&data, "t1").unwrap();
+        assert_eq!("t1", topic.id);
+
+        let topic = find_topic_fn(&data, "Title1").unwrap();
+        assert_eq!("t1", topic.id);
+        assert_eq!("title1", topic.title);
+        assert_eq!(0, topic.timestamp);
+
+        let e = find_topic_fn(&data, "t2").err();
+        assert!(matches!(e, Some(Error::NotFound(_))));
+    }
+
     #[test]
     fn test_get_topics_fn_all() {
         let data = setup_appdate();
         {
-            let conn = data.db.lock().unwrap();
+            let conn = data.db.writer();
             db::Topic::create(&conn, "t1", "title1", 0).unwrap();
         }
 
@@ -476,11 +1365,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_search_topics_fn() {
+        let data = setup_appdate();
+        {
+            let conn = data.db.writer();
+            db::Topic::create(&conn, "t1", "title1", 0).unwrap();
+            db::Memo::create(&conn, "m1", "t1", 0, "content1").unwrap();
+        }
+
+        let t1 = data.searches.begin();
+        let t2 = data.searches.begin();
+
+        let e = search_topics_fn(&data, "tent", t1).err();
+        assert!(matches!(e, Some(Error::Cancelled)));
+
+        let topics = search_topics_fn(&data, "tent", t2).unwrap();
+        assert_eq!(1, topics.len());
+    }
+
     #[test]
     fn test_get_topics_fn_keyword() {
         let data = setup_appdate();
         {
-            let conn = data.db.lock().unwrap();
+            let conn = data.db.writer();
             db::Topic::create(&conn, "t1", "title1", 0).unwrap();
             db::Memo::create(&conn, "m1", "t1", 0, "content1").unwrap();
         }
@@ -493,6 +1401,153 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_lock_topic_fn() {
+        let data = setup_appdate();
+        create_memo_fn(&data, "t1", "# title1\nsecret1").unwrap();
+        create_memo_fn(&data, "t2", "# title2\nsecret2").unwrap();
+
+        lock_topic_fn(&data, "t1", "pass").unwrap();
+        {
+            let conn = data.db.writer();
+            let memos = db::Memo::all_by_topic(&conn, "t1").unwrap();
+            assert!(!memos[0].content.contains("secret1"));
+        }
+
+        let topics = get_topics_fn(&data, "").unwrap();
+        let t1 = topics.iter().find(|t| t.id == "t1").unwrap();
+        assert!(t1.locked);
+        assert_eq!("", t1.title);
+        assert_eq!(1, get_topics_fn(&data, "secret").unwrap().len());
+        assert!(matches!(
+            get_memo_fn(&data, "t1", None),
+            Err(Error::Passphrase)
+        ));
+        assert!(matches!(
+            create_memo_fn(&data, "t1", "a"),
+            Err(Error::Passphrase)
+        ));
+        assert!(matches!(
+            delete_topic_fn(&data, "t1"),
+            Err(Error::Passphrase)
+        ));
+
+        let e = unlock_topic_fn(&data, "t1", "wrong").err();
+        assert!(matches!(e, Some(Error::Passphrase)));
+        unlock_topic_fn(&data, "t1", "pass").unwrap();
+
+        assert_eq!("title1", find_topic_fn(&data, "t1").unwrap().title);
+        assert_eq!(2, get_topics_fn(&data, "SECRET").unwrap().len());
+        let memo = append_memo_fn(&data, "t1", "secret3", None).unwrap();
+        assert_eq!("# title1\nsecret1\n\nsecret3\n", memo.content);
+        let saved = get_memo_fn(&data, "t1", Some(&memo.id)).unwrap();
+        assert_eq!(memo.content, saved.content);
+        assert_eq!(2, get_memo_all_fn(&data, "t1").unwrap().len());
+
+        relock_topic_fn(&data, "t1").unwrap();
+        assert!(matches!(
+            get_memo_fn(&data, "t1", None),
+            Err(Error::Passphrase)
+        ));
+
+        remove_topic_lock_fn(&data, "t1", "pass").unwrap();
+        let topic = find_topic_fn(&data, "title1").unwrap();
+        assert!(!topic.locked);
+        {
+            let conn = data.db.writer();
+            let memos = db::Memo::all_by_topic(&conn, "t1").unwrap();
+            assert!(memos.iter().any(|m| m.content.contains("secret3")));
+        }
+    }
+
+    #[test]
+    fn test_lock_topic_fn_secure_delete() {
+        let data = setup_appdate();
+        create_memo_fn(&data, "t1", "# title1").unwrap();
+
+        lock_topic_fn(&data, "t1", "pass").unwrap();
+        let e = lock_topic_fn(&data, "t1", "pass").err();
+        assert!(matches!(e, Some(Error::Internal(_))));
+
+        // Enabled only while locking, also when it failed.
+        let secure_delete: bool = data
+            .db
+            .writer()
+            .pragma_query_value(None, "secure_delete", |r| r.get(0))
+            .unwrap();
+        assert!(!secure_delete);
+    }
+
+    #[test]
+    fn test_remove_topic_lock_fn_failed() {
+        let data = setup_appdate();
+        create_memo_fn(&data, "t1", "# title1").unwrap();
+        lock_topic_fn(&data, "t1", "pass").unwrap();
+
+        inject_failure(&data, "DELETE ON topic_lock");
+        assert!(remove_topic_lock_fn(&data, "t1", "pass").is_err());
+
+        // Key is not left unlocked by failed removal.
+        assert!(matches!(
+            get_memo_fn(&data, "t1", None),
+            Err(Error::Passphrase)
+        ));
+    }
+
+    #[test]
+    fn test_remove_topic_lock_fn_without_memo() {
+        let data = setup_appdate();
+        db::Topic::create(&data.db.writer(), "t1", "title1", 0).unwrap();
+        lock_topic_fn(&data, "t1", "pass").unwrap();
+
+        remove_topic_lock_fn(&data, "t1", "pass").unwrap();
+        let topic = find_topic_fn(&data, "t1").unwrap();
+        assert!(!topic.locked);
+        assert_eq!("title1", topic.title);
+    }
+
+    #[test]
+    fn test_lock_topic_fn_tags_attachments() {
+        let data = setup_appdate();
+        create_memo_fn(&data, "t1", "# title1").unwrap();
+        add_memo_tag_fn(&data, "t1", "tag1").unwrap();
+        {
+            let conn = data.db.writer();
+            db::Attachment::create(&conn, "a1", "t1", "a.png", b"image").unwrap();
+        }
+
+        lock_topic_fn(&data, "t1", "pass").unwrap();
+        {
+            let conn = data.db.writer();
+            let tags = db::TopicTag::all_by_topic(&conn, "t1").unwrap();
+            assert!(!tags[0].contains("tag1"));
+            let a = &db::Attachment::all_by_topic(&conn, "t1").unwrap()[0];
+            assert_ne!("a.png", a.name);
+            assert_ne!(b"image".to_vec(), a.content);
+            let events = db::Event::all_since(&conn, 0).unwrap();
+            assert!(events.iter().all(|e| !e.payload.contains("title1")));
+        }
+        assert!(get_all_tags_fn(&data).unwrap().is_empty());
+        assert!(matches!(
+            get_memo_tag_fn(&data, "t1"),
+            Err(Error::Passphrase)
+        ));
+
+        unlock_topic_fn(&data, "t1", "pass").unwrap();
+        assert_eq!(vec!["tag1"], tag_names(&data, "t1"));
+        add_memo_tag_fn(&data, "t1", "tag2").unwrap();
+        add_memo_tag_fn(&data, "t1", "tag2").unwrap();
+        remove_memo_tag_fn(&data, "t1", "tag1").unwrap();
+        assert_eq!(vec!["tag2"], tag_names(&data, "t1"));
+
+        remove_topic_lock_fn(&data, "t1", "pass").unwrap();
+        assert_eq!(vec!["tag2"], tag_names(&data, "t1"));
+        let conn = data.db.writer();
+        let a = &db::Attachment::all_by_topic(&conn, "t1").unwrap()[0];
+        assert_eq!("a.png", a.name);
+        assert_eq!(b"image".to_vec(), a.content);
+    }
+
     #[test]
     fn test_add_memo_tag_fn() {
         let data = setup_appdate();
@@ -500,26 +1555,32 @@ mod tests {
         add_memo_tag_fn(&data, "t1", "tag1").unwrap();
 
         {
-            let conn = data.db.lock().unwrap();
+            let conn = data.db.writer();
             let tags = db::TopicTag::all_by_topic(&conn, "t1").unwrap();
             for t in tags {
                 assert_eq!("tag1", t);
             }
         }
+
+        for name in ["", "/a", "a/", "a//b"] {
+            let e = add_memo_tag_fn(&data, "t1", name).err();
+            assert!(matches!(e, Some(Error::Invalid(_))));
+        }
+        assert_eq!(vec!["tag1"], tag_names(&data, "t1"));
     }
 
     #[test]
     fn test_remove_memo_tag_fn() {
         let data = setup_appdate();
         {
-            let conn = data.db.lock().unwrap();
+            let conn = data.db.writer();
             db::TopicTag::create(&conn, "tag1", "t1").unwrap();
         }
 
         remove_memo_tag_fn(&data, "t1", "tag1").unwrap();
 
         {
-            let conn = data.db.lock().unwrap();
+            let conn = data.db.writer();
             let tags = db::TopicTag::all_by_topic(&conn, "t1").unwrap();
             assert_eq!(0, tags.len());
         }
@@ -529,26 +1590,237 @@ mod tests {
     fn test_get_memo_tag_fn() {
         let data = setup_appdate();
         {
-            let conn = data.db.lock().unwrap();
+            let conn = data.db.writer();
             db::TopicTag::create(&conn, "tag1", "t1").unwrap();
         }
 
         let tags = get_memo_tag_fn(&data, "t1").unwrap();
         for tag in tags {
-            assert_eq!("tag1", tag);
+            assert_eq!("tag1", tag.name);
+            assert!(tag.color.is_none());
+        }
+    }
+
+    #[test]
+    fn test_create_memo_fn_inline_tags() {
+        let data = AppData {
+            inline_tags: true,
+            ..setup_appdate()
+        };
+        add_memo_tag_fn(&data, "t1", "manual").unwrap();
+
+        create_memo_fn(
+            &data,
+            "t1",
+            "# title #head\ntext #todo #manual\n```\n#code\n```",
+        )
+        .unwrap();
+        let tags = get_memo_tag_fn(&data, "t1").unwrap();
+        let names: Vec<_> = tags.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(vec!["manual", "todo"], names);
+        assert!(!tags[0].inline);
+        assert!(tags[1].inline);
+
+        let (seq, _) = event::poll(&data, 0).unwrap();
+        create_memo_fn(&data, "t1", "# title\ntext #done").unwrap();
+        assert_eq!(vec!["manual", "done"], tag_names(&data, "t1"));
+        let (_, events) = event::poll(&data, seq).unwrap();
+        assert!(events.contains(&Event::TagRemoved {
+            topic_id: "t1".to_owned(),
+            tag: "todo".to_owned()
+        }));
+
+        add_memo_tag_fn(&data, "t1", "done").unwrap();
+        create_memo_fn(&data, "t1", "# title\ntext").unwrap();
+        assert_eq!(vec!["manual", "done"], tag_names(&data, "t1"));
+
+        create_memo_fn(&data, "t2", "# title2").unwrap();
+        let memo = append_memo_fn(&data, "t2", "#later", None).unwrap();
+        assert_eq!(vec!["later"], tag_names(&data, "t2"));
+
+        lock_topic_fn(&data, "t2", "pass").unwrap();
+        unlock_topic_fn(&data, "t2", "pass").unwrap();
+        create_memo_fn(&data, "t2", &format!("{}#secret", memo.content)).unwrap();
+        assert_eq!(vec!["later"], tag_names(&data, "t2"));
+
+        // Disabled by default.
+        let data = setup_appdate();
+        create_memo_fn(&data, "t3", "#todo").unwrap();
+        assert!(tag_names(&data, "t3").is_empty());
+    }
+
+    #[test]
+    fn test_set_tag_fn() {
+        let data = setup_appdate();
+        for topic_id in ["t1", "t2", "t3"] {
+            create_memo_fn(&data, topic_id, "# title").unwrap();
         }
+        add_memo_tag_fn(&data, "t1", "javascript").unwrap();
+        add_memo_tag_fn(&data, "t2", "js/react").unwrap();
+
+        let aliases = vec!["js".to_owned(), "ecmascript".to_owned()];
+        set_tag_fn(
+            &data,
+            "javascript",
+            Some("#ffcc00"),
+            Some("JS"),
+            Some(&aliases),
+        )
+        .unwrap();
+        assert_eq!(vec!["javascript/react"], tag_names(&data, "t2"));
+
+        let tags = get_memo_tag_fn(&data, "t1").unwrap();
+        assert_eq!(Some("#ffcc00"), tags[0].color.as_deref());
+        assert_eq!(Some("JS"), tags[0].description.as_deref());
+        assert_eq!(vec!["ecmascript", "js"], tags[0].aliases);
+
+        add_memo_tag_fn(&data, "t3", "js").unwrap();
+        assert_eq!(vec!["javascript"], tag_names(&data, "t3"));
+        assert_eq!(3, get_topics_fn(&data, "#js").unwrap().len());
+        assert_eq!(1, get_topics_fn(&data, "#ecmascript/react").unwrap().len());
+
+        let e = set_tag_fn(&data, "other", None, None, Some(&["js".to_owned()])).err();
+        assert!(matches!(e, Some(Error::Invalid(_))));
+        let e = set_tag_fn(
+            &data,
+            "javascript/react",
+            None,
+            None,
+            Some(&["javascript".to_owned()]),
+        )
+        .err();
+        assert!(matches!(e, Some(Error::Invalid(_))));
+        let e = set_tag_fn(&data, "js", None, None, None).err();
+        assert!(matches!(e, Some(Error::Invalid(_))));
+        let e = set_tag_fn(&data, "javascript", Some("red;x:y"), None, None).err();
+        assert!(matches!(e, Some(Error::Invalid(_))));
+
+        // Fields not passed are kept.
+        set_tag_fn(&data, "javascript", Some("#00AAff"), None, None).unwrap();
+        let tags = get_memo_tag_fn(&data, "t1").unwrap();
+        assert_eq!(Some("#00AAff"), tags[0].color.as_deref());
+        assert_eq!(Some("JS"), tags[0].description.as_deref());
+        assert_eq!(vec!["ecmascript", "js"], tags[0].aliases);
+
+        remove_memo_tag_fn(&data, "t3", "js").unwrap();
+        assert!(tag_names(&data, "t3").is_empty());
+        add_memo_tag_fn(&data, "t3", "js").unwrap();
+
+        rename_tag_fn(&data, "javascript", "lang/js").unwrap();
+        assert_eq!(vec!["lang/js"], tag_names(&data, "t3"));
+        assert_eq!(3, get_topics_fn(&data, "#js").unwrap().len());
+        assert_eq!(
+            Some("JS"),
+            get_memo_tag_fn(&data, "t3").unwrap()[0]
+                .description
+                .as_deref()
+        );
+
+        delete_tag_fn(&data, "lang").unwrap();
+        add_memo_tag_fn(&data, "t1", "js").unwrap();
+        assert_eq!(vec!["js"], tag_names(&data, "t1"));
     }
 
-    fn setup_appdate() -> AppData {
-        let conn = setup_connect();
-        AppData {
-            db: Mutex::new(conn),
+    #[test]
+    fn test_get_all_tags_fn() {
+        let data = setup_appdate();
+        {
+            let conn = data.db.writer();
+            db::Topic::create(&conn, "t1", "title1", 10).unwrap();
+            db::Topic::create(&conn, "t2", "title2", 20).unwrap();
+            db::TopicTag::create(&conn, "tag2", "t1").unwrap();
+            db::TopicTag::create(&conn, "tag1", "t1").unwrap();
+            db::TopicTag::create(&conn, "tag1", "t2").unwrap();
         }
+
+        let tags = get_all_tags_fn(&data).unwrap();
+        let names: Vec<_> = tags.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(vec!["tag1", "tag2"], names);
+        assert_eq!((2, 20), (tags[0].count, tags[0].last_used));
+        assert_eq!((1, 10), (tags[1].count, tags[1].last_used));
+    }
+
+    #[test]
+    fn test_complete_tag_fn() {
+        let data = setup_appdate();
+        add_memo_tag_fn(&data, "t1", "javascript").unwrap();
+        add_memo_tag_fn(&data, "t2", "java").unwrap();
+        add_memo_tag_fn(&data, "t3", "java").unwrap();
+        add_memo_tag_fn(&data, "t1", "lang/jsx").unwrap();
+        add_memo_tag_fn(&data, "t1", "project").unwrap();
+
+        assert_eq!(
+            vec!["java", "javascript"],
+            complete_tag_fn(&data, "#Ja").unwrap()
+        );
+        assert_eq!(
+            vec!["lang/jsx", "javascript"],
+            complete_tag_fn(&data, "js").unwrap()
+        );
+        assert_eq!(vec!["project"], complete_tag_fn(&data, "ject").unwrap());
+        assert!(complete_tag_fn(&data, "").unwrap().is_empty());
+        assert!(complete_tag_fn(&data, "xyz").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rename_tag_fn() {
+        let data = setup_appdate();
+        add_memo_tag_fn(&data, "t1", "project").unwrap();
+        add_memo_tag_fn(&data, "t2", "project/alpha").unwrap();
+        add_memo_tag_fn(&data, "t2", "work").unwrap();
+
+        let e = rename_tag_fn(&data, "project", "work").err();
+        assert!(matches!(e, Some(Error::Invalid(_))));
+        let e = rename_tag_fn(&data, "project", "a/").err();
+        assert!(matches!(e, Some(Error::Invalid(_))));
+        let e = rename_tag_fn(&data, "none", "other").err();
+        assert!(matches!(e, Some(Error::NotFound(_))));
+
+        assert_eq!(2, rename_tag_fn(&data, "project", "archive").unwrap());
+        assert_eq!(vec!["archive"], tag_names(&data, "t1"));
+        assert_eq!(vec!["archive/alpha", "work"], tag_names(&data, "t2"));
+    }
+
+    #[test]
+    fn test_merge_tag_fn() {
+        let data = setup_appdate();
+        add_memo_tag_fn(&data, "t1", "js").unwrap();
+        add_memo_tag_fn(&data, "t1", "javascript").unwrap();
+        add_memo_tag_fn(&data, "t2", "js").unwrap();
+
+        assert_eq!(2, merge_tag_fn(&data, "js", "javascript").unwrap());
+        assert_eq!(vec!["javascript"], tag_names(&data, "t1"));
+        assert_eq!(vec!["javascript"], tag_names(&data, "t2"));
+    }
+
+    #[test]
+    fn test_delete_tag_fn() {
+        let data = setup_appdate();
+        add_memo_tag_fn(&data, "t1", "project").unwrap();
+        add_memo_tag_fn(&data, "t2", "project/alpha").unwrap();
+        add_memo_tag_fn(&data, "t2", "work").unwrap();
+
+        assert_eq!(2, delete_tag_fn(&data, "project").unwrap());
+        assert_eq!(vec!["work"], tag_names(&data, "t2"));
+        assert_eq!(1, get_all_tags_fn(&data).unwrap().len());
+
+        let e = delete_tag_fn(&data, "project").err();
+        assert!(matches!(e, Some(Error::NotFound(_))));
+    }
+
+    fn tag_names(data: &AppData, topic_id: &str) -> Vec<String> {
+        get_memo_tag_fn(data, topic_id)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.name)
+            .collect()
     }
 
-    fn setup_connect() -> Connection {
-        let conn = Connection::open_in_memory().unwrap();
-        create_table_if_not_exists(&conn).unwrap();
-        conn
+    fn inject_failure(data: &AppData, event: &str) {
+        let conn = data.db.writer();
+        let sql = format!(
+            "CREATE TEMP TRIGGER failure BEFORE {event} BEGIN SELECT RAISE(ABORT, 'injected'); END"
+        );
+        conn.execute(&sql, []).unwrap();
     }
 }
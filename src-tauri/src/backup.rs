@@ -0,0 +1,235 @@
+use crate::error::Error;
+use crate::{db, model, AppData};
+use chrono::Utc;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+
+const PREFIX: &str = "memo-";
+const SUFFIX: &str = ".db";
+
+/// Where and how often database is backed up, taken from config.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub dir: PathBuf,
+    pub keep: usize,
+    /// Back up only at startup and exit if `None`.
+    pub interval: Option<Duration>,
+}
+
+/// Back up database, and remove old backups exceeding `keep`.
+pub fn backup_fn(data: &AppData, settings: &Settings) -> Result<model::Backup, Error> {
+    let backup = write(data, &settings.dir)?;
+    rotate(&settings.dir, settings.keep)?;
+    Ok(backup)
+}
+
+/// Backups newest first.
+pub fn list_backups_fn(settings: &Settings) -> Result<Vec<model::Backup>, Error> {
+    list(&settings.dir)
+}
+
+/// Replace database by backup, after backing up current one.
+pub fn restore_backup_fn(
+    data: &AppData,
+    settings: &Settings,
+    name: &str,
+) -> Result<model::Backup, Error> {
+    let backup = list(&settings.dir)?
+        .into_iter()
+        .find(|b| b.name == name)
+        .ok_or_else(|| Error::NotFound(name.to_owned()))?;
+
+    let current = write(data, &settings.dir)?;
+    log::info!("backup before restore name={}", current.name);
+
+    {
+        let mut db = data.db.writer();
+        // Backup taken before rekey is not readable by current passphrase.
+        db::restore(
+            &mut db,
+            &settings.dir.join(&backup.name),
+            data.passphrase.as_deref(),
+        )
+        .map_err(Error::from_open)?;
+    }
+
+    rotate(&settings.dir, settings.keep)?;
+    Ok(backup)
+}
+
+/// Back up now and every interval on background thread.
+pub fn schedule(data: AppData, settings: Settings) {
+    thread::spawn(move || loop {
+        match backup_fn(&data, &settings) {
+            Ok(b) => log::info!("backup name={} size={}", b.name, b.size),
+            Err(e) => log::warn!("backup error={e}"),
+        }
+
+        match settings.interval {
+            Some(interval) => thread::sleep(interval),
+            None => break,
+        }
+    });
+}
+
+fn write(data: &AppData, dir: &Path) -> Result<model::Backup, Error> {
+    fs::create_dir_all(dir)?;
+
+    let name = format!("{PREFIX}{}{SUFFIX}", Utc::now().format("%Y%m%d-%H%M%S-%6f"));
+    let path = dir.join(&name);
+
+    // Incomplete copy is not listed.
+    let tmp = path.with_extension("tmp");
+    if let Err(e) = db::backup(&data.db.reader(), &tmp, data.passphrase.as_deref()) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e.into());
+    }
+    fs::rename(&tmp, &path)?;
+
+    describe(&path, name)
+}
+
+fn list(dir: &Path) -> Result<Vec<model::Backup>, Error> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut backups = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with(PREFIX) && name.ends_with(SUFFIX) {
+            backups.push(describe(&entry.path(), name)?);
+        }
+    }
+
+    // Names are ordered by time of backup.
+    backups.sort_by(|a, b| b.name.cmp(&a.name));
+    Ok(backups)
+}
+
+fn rotate(dir: &Path, keep: usize) -> Result<(), Error> {
+    for backup in list(dir)?.iter().skip(keep.max(1)) {
+        log::info!("remove backup name={}", backup.name);
+        fs::remove_file(dir.join(&backup.name))?;
+    }
+    Ok(())
+}
+
+fn describe(path: &Path, name: String) -> Result<model::Backup, Error> {
+    let metadata = fs::metadata(path)?;
+    let timestamp = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+
+    Ok(model::Backup {
+        name,
+        timestamp,
+        size: metadata.len(),
+    })
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{create_memo_fn, get_topics_fn};
+    use crate::testing::{remove_db, setup_appdate, temp_db_path};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_backup_fn() {
+        let data = setup_appdate();
+        let settings = setup_settings(2);
+        assert!(list_backups_fn(&settings).unwrap().is_empty());
+
+        create_memo_fn(&data, "t1", "# title1").unwrap();
+        let first = backup_fn(&data, &settings).unwrap();
+        assert!(first.size > 0);
+
+        let second = backup_fn(&data, &settings).unwrap();
+        let third = backup_fn(&data, &settings).unwrap();
+        fs::write(settings.dir.join("other.txt"), "").unwrap();
+
+        let names: Vec<_> = list_backups_fn(&settings)
+            .unwrap()
+            .into_iter()
+            .map(|b| b.name)
+            .collect();
+        assert_eq!(vec![third.name, second.name], names);
+
+        fs::remove_dir_all(settings.dir).unwrap();
+    }
+
+    #[test]
+    fn test_restore_backup_fn() {
+        let data = setup_appdate();
+        let settings = setup_settings(10);
+
+        create_memo_fn(&data, "t1", "# title1").unwrap();
+        let backup = backup_fn(&data, &settings).unwrap();
+        create_memo_fn(&data, "t2", "# title2").unwrap();
+
+        restore_backup_fn(&data, &settings, &backup.name).unwrap();
+        let topics = get_topics_fn(&data, "").unwrap();
+        assert_eq!(1, topics.len());
+        assert_eq!("t1", topics[0].id);
+
+        // Replaced database is backed up.
+        assert_eq!(2, list_backups_fn(&settings).unwrap().len());
+
+        let e = restore_backup_fn(&data, &settings, "../memo.db").err();
+        assert!(matches!(e, Some(Error::NotFound(_))));
+
+        fs::remove_dir_all(settings.dir).unwrap();
+    }
+
+    #[test]
+    fn test_restore_backup_fn_passphrase() {
+        let (old, new) = (temp_db_path(), temp_db_path());
+        let settings = setup_settings(10);
+        let backup = {
+            let data = AppData::open(Some(&old), false, Some("secret"), None).unwrap();
+            create_memo_fn(&data, "t1", "# title1").unwrap();
+            backup_fn(&data, &settings).unwrap()
+        };
+
+        // Passphrase changed after backup.
+        let data = AppData::open(Some(&new), false, Some("changed"), None).unwrap();
+        let e = restore_backup_fn(&data, &settings, &backup.name).err();
+        assert!(matches!(e, Some(Error::Passphrase)));
+        assert!(get_topics_fn(&data, "").unwrap().is_empty());
+
+        drop(data);
+        remove_db(&old);
+        remove_db(&new);
+        fs::remove_dir_all(settings.dir).unwrap();
+    }
+
+    #[test]
+    fn test_backup_fn_error() {
+        let data = AppData {
+            passphrase: Some("secret".into()),
+            ..setup_appdate()
+        };
+        let settings = setup_settings(10);
+
+        create_memo_fn(&data, "t1", "# title1").unwrap();
+        assert!(backup_fn(&data, &settings).is_err());
+        assert_eq!(0, fs::read_dir(&settings.dir).unwrap().count());
+
+        fs::remove_dir_all(settings.dir).unwrap();
+    }
+
+    fn setup_settings(keep: usize) -> Settings {
+        Settings {
+            dir: std::env::temp_dir().join(Uuid::new_v4().to_string()),
+            keep,
+            interval: None,
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use rusqlite::{Connection, InterruptHandle};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+/// Tracker of search requests where a newer request supersedes older ones.
+#[derive(Default)]
+pub struct Searches {
+    latest: AtomicU64,
+    running: Mutex<Option<(u64, InterruptHandle)>>,
+}
+
+impl Searches {
+    /// Issue ticket for new request, and interrupt running query of older one.
+    pub fn begin(&self) -> u64 {
+        let ticket = self.latest.fetch_add(1, Ordering::SeqCst) + 1;
+
+        let running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((t, handle)) = running.as_ref() {
+            if *t < ticket {
+                handle.interrupt();
+            }
+        }
+
+        ticket
+    }
+
+    pub fn is_superseded(&self, ticket: u64) -> bool {
+        self.latest.load(Ordering::SeqCst) != ticket
+    }
+
+    /// Run query on connection unless superseded, which can be interrupted by newer request.
+    pub fn run<T, E>(
+        &self,
+        ticket: u64,
+        conn: &Connection,
+        f: impl FnOnce(&Connection) -> Result<T, E>,
+    ) -> Option<Result<T, E>> {
+        {
+            let mut running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+            if self.is_superseded(ticket) {
+                return None;
+            }
+            *running = Some((ticket, conn.get_interrupt_handle()));
+        }
+
+        let result = f(conn);
+
+        let mut running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+        if matches!(running.as_ref(), Some((t, _)) if *t == ticket) {
+            *running = None;
+        }
+
+        if self.is_superseded(ticket) {
+            None
+        } else {
+            Some(result)
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::ErrorCode;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_begin() {
+        let searches = Searches::default();
+        let t1 = searches.begin();
+        assert!(!searches.is_superseded(t1));
+
+        let t2 = searches.begin();
+        assert!(searches.is_superseded(t1));
+        assert!(!searches.is_superseded(t2));
+    }
+
+    #[test]
+    fn test_run_superseded() {
+        let searches = Searches::default();
+        let conn = Connection::open_in_memory().unwrap();
+
+        let t1 = searches.begin();
+        let _ = searches.begin();
+        let result = searches.run(t1, &conn, |_| Ok::<(), ()>(()));
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_run_interrupted() {
+        let searches = Arc::new(Searches::default());
+        let interrupted = Arc::new(AtomicBool::new(false));
+
+        let s = searches.clone();
+        let i = interrupted.clone();
+        let ticket = searches.begin();
+        let handle = thread::spawn(move || {
+            let conn = Connection::open_in_memory().unwrap();
+            s.run(ticket, &conn, |c| {
+                let result = c.query_row(
+                    "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 1000000000) \
+                     SELECT COUNT(*) FROM n",
+                    [],
+                    |r| r.get::<_, i64>(0),
+                );
+                let code = result.as_ref().err().and_then(|e| e.sqlite_error_code());
+                i.store(code == Some(ErrorCode::OperationInterrupted), Ordering::SeqCst);
+                result
+            })
+        });
+
+        while searches.running.lock().unwrap().is_none() {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // Interrupt is ignored until query starts.
+        while !handle.is_finished() {
+            searches.begin();
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert!(handle.join().unwrap().is_none());
+        assert!(interrupted.load(Ordering::SeqCst));
+    }
+}
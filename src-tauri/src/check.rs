@@ -0,0 +1,154 @@
+use crate::api::parse_title;
+use crate::error::Error;
+use crate::event::Event;
+use crate::{db, model, AppData};
+use rusqlite::{Connection, TransactionBehavior};
+
+/// Find corruption and inconsistency of database, and repair the latter if `repair`.
+///
+/// Nothing is repaired while database is corrupted, restore backup instead.
+pub fn check_database_fn(data: &AppData, repair: bool) -> Result<model::CheckReport, Error> {
+    // Checked by reader not to block writes.
+    let mut report = {
+        let db = data.db.reader();
+        let integrity = db::integrity_check(&db)?;
+        inconsistency(&db, integrity)?
+    };
+    for message in &report.integrity {
+        log::warn!("integrity check message={message}");
+    }
+
+    if !repair || !report.integrity.is_empty() || is_ok(&report) {
+        return Ok(report);
+    }
+
+    let mut db = data.db.writer();
+    let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    // Found again in transaction, since it may be changed after checked.
+    report = inconsistency(&tx, report.integrity)?;
+
+    for topic_id in &report.missing_topics {
+        recreate_topic(&tx, topic_id)?;
+    }
+    for topic_id in &report.orphan_tags {
+        let count = db::TopicTag::delete_by_topic(&tx, topic_id)?;
+        log::info!("repair orphan tags topic_id={topic_id} count={count}");
+    }
+    for topic in db::Topic::all_empty(&tx)? {
+        db::TopicTag::delete_by_topic(&tx, &topic.id)?;
+        if let Some(lock) = db::TopicLock::find(&tx, &topic.id)? {
+            lock.delete(&tx)?;
+        }
+        topic.delete(&tx)?;
+        log::info!("repair empty topic topic_id={}", topic.id);
+    }
+    Event::DatabaseChanged.record(&tx)?;
+
+    tx.commit()?;
+    report.repaired = true;
+    Ok(report)
+}
+
+/// Report with inconsistency found in `db`.
+fn inconsistency(db: &Connection, integrity: Vec<String>) -> Result<model::CheckReport, Error> {
+    Ok(model::CheckReport {
+        integrity,
+        missing_topics: db::Memo::orphan_topic_ids(db)?,
+        orphan_tags: db::TopicTag::orphan_topic_ids(db)?,
+        empty_topics: db::Topic::all_empty(db)?
+            .into_iter()
+            .map(|t| t.id)
+            .collect(),
+        repaired: false,
+    })
+}
+
+/// No problem is found.
+pub fn is_ok(report: &model::CheckReport) -> bool {
+    report.integrity.is_empty()
+        && report.missing_topics.is_empty()
+        && report.orphan_tags.is_empty()
+        && report.empty_topics.is_empty()
+}
+
+fn recreate_topic(db: &Connection, topic_id: &str) -> Result<(), Error> {
+    let memo = db::Memo::latest_by_topic(db, topic_id)?;
+    // Title of locked topic can not be encrypted without key. It is left empty to be hidden,
+    // and taken from memo again when saved or lock is removed.
+    let title = if db::TopicLock::find(db, topic_id)?.is_some() {
+        String::new()
+    } else {
+        parse_title(&memo.content)
+    };
+
+    db::Topic::create(db, topic_id, &title, memo.timestamp)?;
+    log::info!("repair missing topic topic_id={topic_id}");
+    Ok(())
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{
+        add_memo_tag_fn, create_memo_fn, find_topic_fn, lock_topic_fn, remove_topic_lock_fn,
+    };
+    use crate::testing::setup_appdate;
+
+    #[test]
+    fn test_check_database_fn() {
+        let data = setup_appdate();
+        create_memo_fn(&data, "t1", "# title1").unwrap();
+
+        let report = check_database_fn(&data, true).unwrap();
+        assert!(is_ok(&report));
+        assert!(!report.repaired);
+    }
+
+    #[test]
+    fn test_check_database_fn_repair() {
+        let data = setup_appdate();
+        create_memo_fn(&data, "t1", "# title1").unwrap();
+        create_memo_fn(&data, "t2", "# title2").unwrap();
+        add_memo_tag_fn(&data, "t3", "tag3").unwrap();
+        {
+            let db = data.db.writer();
+            db::Topic::create(&db, "t4", "title4", 0).unwrap();
+            db.execute("DELETE FROM topic WHERE id = 't2'", []).unwrap();
+        }
+
+        let report = check_database_fn(&data, false).unwrap();
+        assert_eq!(vec!["t2"], report.missing_topics);
+        assert_eq!(vec!["t3"], report.orphan_tags);
+        assert_eq!(vec!["t4"], report.empty_topics);
+        assert!(!report.repaired);
+
+        let report = check_database_fn(&data, true).unwrap();
+        assert!(report.repaired);
+        assert_eq!("t2", find_topic_fn(&data, "title2").unwrap().id);
+        assert!(find_topic_fn(&data, "title4").is_err());
+
+        let report = check_database_fn(&data, false).unwrap();
+        assert!(is_ok(&report));
+    }
+
+    #[test]
+    fn test_check_database_fn_repair_locked() {
+        let data = setup_appdate();
+        create_memo_fn(&data, "t1", "# title1").unwrap();
+        lock_topic_fn(&data, "t1", "pass").unwrap();
+        {
+            let db = data.db.writer();
+            db.execute("DELETE FROM topic WHERE id = 't1'", []).unwrap();
+        }
+
+        assert!(check_database_fn(&data, true).unwrap().repaired);
+        let topic = find_topic_fn(&data, "t1").unwrap();
+        assert!(topic.locked);
+        assert_eq!("", topic.title);
+
+        remove_topic_lock_fn(&data, "t1", "pass").unwrap();
+        assert_eq!("title1", find_topic_fn(&data, "t1").unwrap().title);
+    }
+}
@@ -0,0 +1,1039 @@
+use crate::api::{
+    add_memo_tag_fn, append_memo_fn, create_memo_fn, delete_tag_fn, find_topic_fn, get_all_tags_fn,
+    get_memo_all_fn, get_memo_fn, get_memo_tag_fn, get_topics_fn, merge_tag_fn, remove_memo_tag_fn,
+    rename_tag_fn, set_tag_fn,
+};
+use crate::check::{check_database_fn, is_ok};
+use crate::config::Config;
+use crate::epub::export_epub_fn;
+use crate::error::Error;
+use crate::maintenance::maintain_database_fn;
+use crate::obsidian::{export_obsidian_fn, import_obsidian_fn};
+use crate::site::export_site_fn;
+use crate::{db, instance, logger, mcp, model, AppData};
+use chrono::{DateTime, Local};
+use clap::{Parser, Subcommand, ValueEnum};
+use log::LevelFilter;
+use serde::Serialize;
+use std::fs;
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use uuid::Uuid;
+
+#[derive(Parser)]
+#[command(name = "memo", about = "Markdown MEMO", version)]
+pub struct Cli {
+    /// Database file path
+    #[arg(short, long, global = true)]
+    pub path: Option<PathBuf>,
+
+    /// Use in-memory database
+    #[arg(long, global = true)]
+    pub memory: bool,
+
+    /// Output as JSON
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    /// Log level (off, error, warn, info, debug, trace)
+    #[arg(long, global = true)]
+    pub log_level: Option<LevelFilter>,
+
+    /// Passphrase of encrypted database
+    #[arg(long, global = true, env = "MEMO_PASSPHRASE", hide_env_values = true)]
+    pub passphrase: Option<String>,
+
+    /// Serve HTTP API on localhost with specified port
+    #[arg(long)]
+    pub http_port: Option<u16>,
+
+    /// Token required by HTTP API (default: generated in data directory)
+    #[arg(long, env = "MEMO_HTTP_TOKEN", hide_env_values = true)]
+    pub http_token: Option<String>,
+
+    /// Topic id or title to open in window
+    #[arg(long)]
+    pub open: Option<String>,
+
+    /// Append text to topic specified by --open, or new topic
+    #[arg(long)]
+    pub capture: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// List all topics
+    List,
+
+    /// Show memo of topic
+    Show {
+        /// Topic id or title
+        topic: String,
+
+        /// Memo id in history (default: latest)
+        #[arg(long)]
+        id: Option<String>,
+    },
+
+    /// Search topics using keywords or #tags
+    Search {
+        #[arg(required = true)]
+        query: Vec<String>,
+    },
+
+    /// Save memo read from stdin
+    Add {
+        /// Topic id or title (default: new topic)
+        #[arg(long)]
+        topic: Option<String>,
+    },
+
+    /// Append text read from stdin to topic
+    Append {
+        /// Topic id or title (default: new topic)
+        #[arg(long)]
+        topic: Option<String>,
+
+        /// Put timestamp heading before text
+        #[arg(long)]
+        heading: bool,
+    },
+
+    /// Manage tags of topic
+    Tag {
+        #[command(subcommand)]
+        command: TagCommand,
+    },
+
+    /// List memo history of topic
+    History {
+        /// Topic id or title
+        topic: String,
+    },
+
+    /// Export topics
+    Export {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = Format::Obsidian)]
+        format: Format,
+
+        /// Export only topics matched with keywords or #tags
+        #[arg(long, default_value = "")]
+        keyword: String,
+
+        /// Language of epub like `ja`
+        #[arg(long, default_value = "en")]
+        language: String,
+
+        /// Output directory (or file for epub)
+        dest: PathBuf,
+    },
+
+    /// Import Obsidian vault
+    Import {
+        /// Vault directory
+        vault: PathBuf,
+    },
+
+    /// Serve Model Context Protocol over stdio
+    Mcp,
+
+    /// Check database for corruption and inconsistency
+    Check {
+        /// Recreate missing topics and remove orphan tags and empty topics
+        #[arg(long)]
+        repair: bool,
+    },
+
+    /// Report database size, then vacuum and optimize it
+    Maintain {
+        /// Only report size
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Encrypt, decrypt or change passphrase of database while GUI is not running
+    Rekey {
+        /// New passphrase (default: decrypt)
+        #[arg(long, env = "MEMO_NEW_PASSPHRASE", hide_env_values = true)]
+        new_passphrase: Option<String>,
+    },
+}
+
+impl Command {
+    fn name(&self) -> &'static str {
+        match self {
+            Command::List => "list",
+            Command::Show { .. } => "show",
+            Command::Search { .. } => "search",
+            Command::Add { .. } => "add",
+            Command::Append { .. } => "append",
+            Command::Tag { .. } => "tag",
+            Command::History { .. } => "history",
+            Command::Export { .. } => "export",
+            Command::Import { .. } => "import",
+            Command::Mcp => "mcp",
+            Command::Check { .. } => "check",
+            Command::Maintain { .. } => "maintain",
+            Command::Rekey { .. } => "rekey",
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum TagCommand {
+    /// List tags of topic, or all tags with number of topics
+    List { topic: Option<String> },
+
+    /// Add tag to topic
+    Add { topic: String, tag: String },
+
+    /// Remove tag from topic
+    Remove { topic: String, tag: String },
+
+    /// Rename tag with its children like `tag/child` in all topics
+    Rename { from: String, to: String },
+
+    /// Merge tag with its children into other tag in all topics
+    Merge { from: String, into: String },
+
+    /// Delete tag with its children from all topics
+    Delete { tag: String },
+
+    /// Set color, description and aliases of tag, merging tags of the aliases into it
+    Set {
+        tag: String,
+        /// Color like `#ffcc00`, empty to clear
+        #[arg(long)]
+        color: Option<String>,
+        /// Empty to clear
+        #[arg(long)]
+        description: Option<String>,
+        /// Resolved into the tag when searching and adding, replacing current aliases
+        #[arg(long)]
+        alias: Vec<String>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    Epub,
+    Html,
+    Obsidian,
+}
+
+#[derive(Serialize)]
+struct Count {
+    count: usize,
+}
+
+/// Exit status when `check` finds problems which are not repaired.
+pub const EXIT_PROBLEMS: i32 = 2;
+
+// -----------------------------------------------------------------------------------------------
+
+pub fn run(cli: Cli, config: &Config) -> i32 {
+    let Some(command) = cli.command else {
+        return 0;
+    };
+
+    attach_console();
+
+    let passphrase = cli.passphrase.as_deref().filter(|p| !p.is_empty());
+    if let Command::Rekey { new_passphrase } = &command {
+        let path = cli.path.unwrap_or_else(db::create_default_path);
+        let new_passphrase = new_passphrase.as_deref().filter(|p| !p.is_empty());
+        let lock = instance::lock_path(&path);
+        return match rekey(&path, &lock, passphrase, new_passphrase) {
+            Ok(_) => {
+                log::info!("command=rekey path={}", path.display());
+                0
+            }
+            Err(e) => {
+                log::error!("command=rekey error={}", logger::error_chain(&e));
+                eprintln!("error: {e}");
+                1
+            }
+        };
+    }
+
+    let data = match AppData::open(
+        cli.path.as_deref(),
+        cli.memory,
+        passphrase,
+        config.auto_vacuum,
+    ) {
+        Ok(data) => AppData {
+            inline_tags: config.inline_tags.unwrap_or_default(),
+            ..data
+        },
+        Err(e) => {
+            eprintln!("error: {e}");
+            return 1;
+        }
+    };
+
+    let name = command.name();
+    let start = Instant::now();
+
+    let mut stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let result = execute(&data, command, cli.json, &mut stdin, &mut stdout);
+
+    let elapsed = start.elapsed().as_millis();
+    match result {
+        Ok(status) => {
+            log::info!("command={name} duration_ms={elapsed} status={status}");
+            status
+        }
+        Err(e) => {
+            log::error!(
+                "command={name} duration_ms={elapsed} error={}",
+                logger::error_chain(&e)
+            );
+            eprintln!("error: {e}");
+            1
+        }
+    }
+}
+
+pub fn execute(
+    data: &AppData,
+    command: Command,
+    json: bool,
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+) -> Result<i32, Error> {
+    match command {
+        Command::List => {
+            let topics = get_topics_fn(data, "")?;
+            write_topics(output, &topics, json)?;
+        }
+        Command::Show { topic, id } => {
+            let topic = find_topic_fn(data, &topic)?;
+            let memo = get_memo_fn(data, &topic.id, id.as_deref())?;
+            if json {
+                write_json(output, &memo)?;
+            } else {
+                write!(output, "{}", memo.content)?;
+            }
+        }
+        Command::Search { query } => {
+            let topics = get_topics_fn(data, &query.join(" "))?;
+            write_topics(output, &topics, json)?;
+        }
+        Command::Add { topic } => {
+            let topic_id = match topic {
+                Some(t) => find_topic_fn(data, &t)?.id,
+                None => Uuid::new_v4().to_string(),
+            };
+
+            let mut content = String::new();
+            input.read_to_string(&mut content)?;
+
+            let memo = create_memo_fn(data, &topic_id, &content)?;
+            if json {
+                write_json(output, &memo)?;
+            } else {
+                writeln!(output, "{}", memo.topic_id)?;
+            }
+        }
+        Command::Append { topic, heading } => {
+            let mut text = String::new();
+            input.read_to_string(&mut text)?;
+
+            let heading = heading.then(|| Local::now().format("%Y-%m-%d %H:%M").to_string());
+            let topic = topic.unwrap_or_default();
+            let memo = append_memo_fn(data, &topic, &text, heading.as_deref())?;
+            if json {
+                write_json(output, &memo)?;
+            } else {
+                writeln!(output, "{}", memo.topic_id)?;
+            }
+        }
+        Command::Tag { command } => match command {
+            TagCommand::List { topic: Some(topic) } => {
+                let topic = find_topic_fn(data, &topic)?;
+                let tags = get_memo_tag_fn(data, &topic.id)?;
+                if json {
+                    write_json(output, &tags)?;
+                } else {
+                    for tag in tags {
+                        writeln!(output, "{}", tag.name)?;
+                    }
+                }
+            }
+            TagCommand::List { topic: None } => {
+                let tags = get_all_tags_fn(data)?;
+                if json {
+                    write_json(output, &tags)?;
+                } else {
+                    for tag in tags {
+                        writeln!(output, "{}\t{}", tag.name, tag.count)?;
+                    }
+                }
+            }
+            TagCommand::Add { topic, tag } => {
+                let topic = find_topic_fn(data, &topic)?;
+                add_memo_tag_fn(data, &topic.id, &tag)?;
+            }
+            TagCommand::Remove { topic, tag } => {
+                let topic = find_topic_fn(data, &topic)?;
+                remove_memo_tag_fn(data, &topic.id, &tag)?;
+            }
+            TagCommand::Rename { from, to } => {
+                rename_tag_fn(data, &from, &to)?;
+            }
+            TagCommand::Merge { from, into } => {
+                merge_tag_fn(data, &from, &into)?;
+            }
+            TagCommand::Delete { tag } => {
+                delete_tag_fn(data, &tag)?;
+            }
+            TagCommand::Set {
+                tag,
+                color,
+                description,
+                alias,
+            } => {
+                let aliases = (!alias.is_empty()).then_some(alias.as_slice());
+                set_tag_fn(
+                    data,
+                    &tag,
+                    color.as_deref(),
+                    description.as_deref(),
+                    aliases,
+                )?;
+            }
+        },
+        Command::History { topic } => {
+            let topic = find_topic_fn(data, &topic)?;
+            let memos = get_memo_all_fn(data, &topic.id)?;
+            if json {
+                write_json(output, &memos)?;
+            } else {
+                for memo in memos {
+                    let latest = if memo.latest { "\t(latest)" } else { "" };
+                    writeln!(
+                        output,
+                        "{}\t{}{latest}",
+                        memo.id,
+                        format_timestamp(memo.timestamp)
+                    )?;
+                }
+            }
+        }
+        Command::Export {
+            format,
+            keyword,
+            language,
+            dest,
+        } => {
+            let count = match format {
+                Format::Epub => export_epub_fn(data, &dest, &keyword, &language),
+                Format::Html => export_site_fn(data, &dest, &keyword),
+                Format::Obsidian => export_obsidian_fn(data, &dest, &keyword),
+            }?;
+            write_count(output, "Exported", count, json)?;
+        }
+        Command::Import { vault } => {
+            let count = import_obsidian_fn(data, &vault)?;
+            write_count(output, "Imported", count, json)?;
+        }
+        Command::Mcp => {
+            mcp::serve(data, &mut BufReader::new(input), output)?;
+        }
+        Command::Check { repair } => {
+            let report = check_database_fn(data, repair)?;
+            write_report(output, &report, json)?;
+            if !report.repaired && !is_ok(&report) {
+                log::warn!("database has problems");
+                return Ok(EXIT_PROBLEMS);
+            }
+        }
+        Command::Maintain { dry_run } => {
+            let maintenance = maintain_database_fn(data, dry_run)?;
+            if json {
+                write_json(output, &maintenance)?;
+            } else {
+                write_stats(output, &maintenance.before)?;
+                if let Some(after) = &maintenance.after {
+                    let before = maintenance.before.size;
+                    writeln!(
+                        output,
+                        "Vacuumed from {before} to {} bytes, reclaimed {} bytes.",
+                        after.size,
+                        (before - after.size).max(0)
+                    )?;
+                }
+            }
+        }
+        Command::Rekey { .. } => {
+            return Err(Error::Internal("rekey needs closed database".to_owned()));
+        }
+    }
+
+    Ok(0)
+}
+
+/// Replace database file by copy encrypted with new passphrase, unless `lock` is held by other process.
+pub fn rekey(
+    path: &Path,
+    lock: &Path,
+    passphrase: Option<&str>,
+    new_passphrase: Option<&str>,
+) -> Result<(), Error> {
+    if !path.exists() {
+        return Err(Error::NotFound(path.display().to_string()));
+    }
+
+    // Renaming over database used by running instance loses its writes.
+    let Some(_lock) = instance::InstanceLock::acquire(lock)? else {
+        return Err(Error::LockError(
+            "database is used by other process".to_owned(),
+        ));
+    };
+
+    let tmp = path.with_extension("rekey");
+    if tmp.exists() {
+        fs::remove_file(&tmp)?;
+    }
+    remove_sidecars(&tmp)?;
+
+    db::export(path, passphrase, &tmp, new_passphrase).map_err(Error::from_open)?;
+    // Write-ahead log of old database, checkpointed by export, must not be applied to new one.
+    remove_sidecars(path)?;
+    fs::rename(&tmp, path)?;
+
+    Ok(())
+}
+
+/// Remove `-wal` and `-shm` files next to database.
+fn remove_sidecars(path: &Path) -> io::Result<()> {
+    for suffix in ["-wal", "-shm"] {
+        let mut sidecar = path.as_os_str().to_owned();
+        sidecar.push(suffix);
+        let sidecar = PathBuf::from(sidecar);
+        if sidecar.exists() {
+            fs::remove_file(sidecar)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_topics(output: &mut dyn Write, topics: &[model::Topic], json: bool) -> Result<(), Error> {
+    if json {
+        write_json(output, &topics)?;
+    } else {
+        for topic in topics {
+            writeln!(
+                output,
+                "{}\t{}\t{}",
+                topic.id,
+                format_timestamp(topic.timestamp),
+                topic.title
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_report(
+    output: &mut dyn Write,
+    report: &model::CheckReport,
+    json: bool,
+) -> Result<(), Error> {
+    if json {
+        return write_json(output, report);
+    }
+
+    for message in &report.integrity {
+        writeln!(output, "corrupted\t{message}")?;
+    }
+    for topic_id in &report.missing_topics {
+        writeln!(output, "missing topic\t{topic_id}")?;
+    }
+    for topic_id in &report.orphan_tags {
+        writeln!(output, "orphan tags\t{topic_id}")?;
+    }
+    for topic_id in &report.empty_topics {
+        writeln!(output, "empty topic\t{topic_id}")?;
+    }
+
+    if is_ok(report) {
+        writeln!(output, "No problems found.")?;
+    } else if report.repaired {
+        writeln!(output, "Repaired.")?;
+    } else if !report.integrity.is_empty() {
+        writeln!(output, "Database is corrupted, restore backup.")?;
+    }
+
+    Ok(())
+}
+
+fn write_stats(output: &mut dyn Write, stats: &model::DatabaseStats) -> Result<(), Error> {
+    writeln!(output, "size\t{}\tfree\t{}", stats.size, stats.free)?;
+    writeln!(
+        output,
+        "topics\t{}\trevisions\t{}",
+        stats.topics, stats.revisions
+    )?;
+    for table in &stats.tables {
+        writeln!(output, "table\t{}\t{}", table.name, table.size)?;
+    }
+    for topic in &stats.largest_topics {
+        writeln!(
+            output,
+            "topic\t{}\t{}\t{}\t{}",
+            topic.id, topic.revisions, topic.size, topic.title
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_count(output: &mut dyn Write, verb: &str, count: usize, json: bool) -> Result<(), Error> {
+    if json {
+        write_json(output, &Count { count })?;
+    } else {
+        writeln!(output, "{verb} {count} topics.")?;
+    }
+
+    Ok(())
+}
+
+fn write_json<T: Serialize + ?Sized>(output: &mut dyn Write, value: &T) -> Result<(), Error> {
+    serde_json::to_writer(&mut *output, value).map_err(io::Error::from)?;
+    writeln!(output)?;
+    Ok(())
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+#[cfg(windows)]
+fn attach_console() {
+    use windows_sys::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+
+    // Release build is windows subsystem, so output is lost without console.
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::testing::{remove_db, setup_connect, temp_db_path};
+
+    #[test]
+    fn test_parse_gui() {
+        let cli = Cli::try_parse_from(["memo", "--path", "a.db"]).unwrap();
+        assert_eq!(Some(PathBuf::from("a.db")), cli.path);
+        assert!(!cli.memory);
+        assert!(cli.command.is_none());
+
+        let cli = Cli::try_parse_from(["memo", "--memory"]).unwrap();
+        assert!(cli.memory);
+
+        let cli =
+            Cli::try_parse_from(["memo", "--http-port", "8080", "--http-token", "a"]).unwrap();
+        assert_eq!(Some(8080), cli.http_port);
+        assert_eq!(Some("a".to_owned()), cli.http_token);
+
+        let cli = Cli::try_parse_from(["memo", "--open", "inbox", "--capture", "idea"]).unwrap();
+        assert_eq!(Some("inbox".to_owned()), cli.open);
+        assert_eq!(Some("idea".to_owned()), cli.capture);
+    }
+
+    #[test]
+    fn test_parse_command() {
+        let cli = Cli::try_parse_from(["memo", "list", "-p", "a.db", "--json"]).unwrap();
+        assert_eq!(Some(PathBuf::from("a.db")), cli.path);
+        assert!(cli.json);
+        assert!(cli.log_level.is_none());
+
+        let cli = Cli::try_parse_from(["memo", "list", "--log-level", "debug"]).unwrap();
+        assert_eq!(Some(LevelFilter::Debug), cli.log_level);
+        assert!(matches!(cli.command, Some(Command::List)));
+
+        let cli = Cli::try_parse_from(["memo", "tag", "add", "t1", "tag1"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Tag {
+                command: TagCommand::Add { .. }
+            })
+        ));
+
+        assert!(Cli::try_parse_from(["memo", "search"]).is_err());
+    }
+
+    #[test]
+    fn test_execute_list() {
+        let data = setup_appdate();
+
+        let output = execute_ok(&data, Command::List, false, "");
+        assert!(output.starts_with("t2\t"));
+        assert!(output.ends_with("\ttitle1\n"));
+
+        let output = execute_ok(&data, Command::List, true, "");
+        let topics: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(2, topics.as_array().unwrap().len());
+        assert_eq!("t2", topics[0]["id"]);
+    }
+
+    #[test]
+    fn test_execute_show() {
+        let data = setup_appdate();
+
+        let command = Command::Show {
+            topic: "title1".to_owned(),
+            id: None,
+        };
+        let output = execute_ok(&data, command, false, "");
+        assert_eq!("content1", output);
+
+        let command = Command::Show {
+            topic: "t3".to_owned(),
+            id: None,
+        };
+        let mut output = vec![];
+        let e = execute(&data, command, false, &mut io::empty(), &mut output).err();
+        assert!(matches!(e, Some(Error::NotFound(_))));
+    }
+
+    #[test]
+    fn test_execute_search() {
+        let data = setup_appdate();
+
+        let command = Command::Search {
+            query: vec!["#tag1".to_owned()],
+        };
+        let output = execute_ok(&data, command, true, "");
+        let topics: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(1, topics.as_array().unwrap().len());
+        assert_eq!("t1", topics[0]["id"]);
+    }
+
+    #[test]
+    fn test_execute_add() {
+        let data = setup_appdate();
+
+        let command = Command::Add { topic: None };
+        let output = execute_ok(&data, command, false, "new content");
+        let topic_id = output.trim();
+
+        let memo = get_memo_fn(&data, topic_id, None).unwrap();
+        assert_eq!("new content", memo.content);
+
+        let command = Command::Add {
+            topic: Some("t1".to_owned()),
+        };
+        let output = execute_ok(&data, command, true, "content2");
+        let memo: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!("t1", memo["topic_id"]);
+        assert_eq!("content2", memo["content"]);
+    }
+
+    #[test]
+    fn test_execute_append() {
+        let data = setup_appdate();
+
+        let command = Command::Append {
+            topic: Some("title1".to_owned()),
+            heading: false,
+        };
+        let output = execute_ok(&data, command, false, "appended\n");
+        assert_eq!("t1\n", output);
+
+        let memo = get_memo_fn(&data, "t1", None).unwrap();
+        assert_eq!("content1\n\nappended\n", memo.content);
+
+        let command = Command::Append {
+            topic: None,
+            heading: true,
+        };
+        let output = execute_ok(&data, command, true, "captured");
+        let memo: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_ne!("t1", memo["topic_id"]);
+        assert!(memo["content"].as_str().unwrap().starts_with("## "));
+        assert!(memo["content"]
+            .as_str()
+            .unwrap()
+            .ends_with("\n\ncaptured\n"));
+    }
+
+    #[test]
+    fn test_execute_tag() {
+        let data = setup_appdate();
+
+        let command = Command::Tag {
+            command: TagCommand::Add {
+                topic: "t2".to_owned(),
+                tag: "tag2".to_owned(),
+            },
+        };
+        execute_ok(&data, command, false, "");
+
+        let command = Command::Tag {
+            command: TagCommand::Remove {
+                topic: "t1".to_owned(),
+                tag: "tag1".to_owned(),
+            },
+        };
+        execute_ok(&data, command, false, "");
+
+        let command = Command::Tag {
+            command: TagCommand::List {
+                topic: Some("t2".to_owned()),
+            },
+        };
+        let output = execute_ok(&data, command, false, "");
+        assert_eq!("tag2\n", output);
+
+        let tags = get_memo_tag_fn(&data, "t1").unwrap();
+        assert!(tags.is_empty());
+    }
+
+    #[test]
+    fn test_execute_tag_global() {
+        let data = setup_appdate();
+        add_memo_tag_fn(&data, "t2", "tag1/sub").unwrap();
+
+        let command = Command::Tag {
+            command: TagCommand::Rename {
+                from: "tag1".to_owned(),
+                to: "tag2".to_owned(),
+            },
+        };
+        execute_ok(&data, command, false, "");
+
+        let command = Command::Tag {
+            command: TagCommand::List { topic: None },
+        };
+        let output = execute_ok(&data, command, false, "");
+        assert_eq!("tag2\t1\ntag2/sub\t1\n", output);
+
+        let command = Command::Tag {
+            command: TagCommand::Merge {
+                from: "tag2/sub".to_owned(),
+                into: "tag2".to_owned(),
+            },
+        };
+        execute_ok(&data, command, false, "");
+        let tags = get_memo_tag_fn(&data, "t2").unwrap();
+        assert_eq!(1, tags.len());
+        assert_eq!("tag2", tags[0].name);
+
+        let command = Command::Tag {
+            command: TagCommand::Delete {
+                tag: "tag2".to_owned(),
+            },
+        };
+        execute_ok(&data, command, false, "");
+        assert!(get_all_tags_fn(&data).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_execute_tag_set() {
+        let data = setup_appdate();
+
+        let command = Command::Tag {
+            command: TagCommand::Set {
+                tag: "tag2".to_owned(),
+                color: Some("#ff0000".to_owned()),
+                description: None,
+                alias: vec!["tag1".to_owned()],
+            },
+        };
+        execute_ok(&data, command, false, "");
+
+        let tags = get_memo_tag_fn(&data, "t1").unwrap();
+        assert_eq!("tag2", tags[0].name);
+        assert_eq!(Some("#ff0000"), tags[0].color.as_deref());
+    }
+
+    #[test]
+    fn test_execute_history() {
+        let data = setup_appdate();
+
+        let command = Command::History {
+            topic: "t1".to_owned(),
+        };
+        let output = execute_ok(&data, command, false, "");
+        let lines = output.lines().collect::<Vec<&str>>();
+        assert_eq!(2, lines.len());
+        assert!(lines[0].starts_with("m2\t"));
+        assert!(lines[0].ends_with("\t(latest)"));
+        assert!(lines[1].starts_with("m1\t"));
+    }
+
+    #[test]
+    fn test_execute_export_import() {
+        let data = setup_appdate();
+        let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+
+        let command = Command::Export {
+            format: Format::Obsidian,
+            keyword: "".to_owned(),
+            language: "en".to_owned(),
+            dest: dir.clone(),
+        };
+        let output = execute_ok(&data, command, false, "");
+        assert_eq!("Exported 2 topics.\n", output);
+
+        let command = Command::Import { vault: dir.clone() };
+        let output = execute_ok(&data, command, true, "");
+        assert_eq!("{\"count\":2}\n", output);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_execute_mcp() {
+        let data = setup_appdate();
+
+        let input = r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#;
+        let output = execute_ok(&data, Command::Mcp, false, input);
+        assert_eq!("{\"id\":1,\"jsonrpc\":\"2.0\",\"result\":{}}\n", output);
+    }
+
+    #[test]
+    fn test_execute_check() {
+        let data = setup_appdate();
+
+        let output = execute_ok(&data, Command::Check { repair: false }, false, "");
+        assert_eq!("No problems found.\n", output);
+
+        add_memo_tag_fn(&data, "t3", "tag3").unwrap();
+        let mut output = vec![];
+        let command = Command::Check { repair: false };
+        let status = execute(&data, command, false, &mut io::empty(), &mut output).unwrap();
+        assert_eq!(EXIT_PROBLEMS, status);
+        assert_eq!(b"orphan tags\tt3\n", output.as_slice());
+
+        let output = execute_ok(&data, Command::Check { repair: true }, true, "");
+        let report: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(true, report["repaired"]);
+    }
+
+    #[test]
+    fn test_execute_maintain() {
+        let data = setup_appdate();
+
+        let output = execute_ok(&data, Command::Maintain { dry_run: true }, false, "");
+        assert!(output.starts_with("size\t"));
+        assert!(output.contains("\ntopic\tt1\t2\t"));
+        assert!(!output.contains("Vacuumed"));
+
+        let output = execute_ok(&data, Command::Maintain { dry_run: false }, false, "");
+        assert!(output.contains("\nVacuumed from "));
+        assert!(output.ends_with(" bytes, reclaimed 0 bytes.\n"));
+
+        let output = execute_ok(&data, Command::Maintain { dry_run: false }, true, "");
+        let maintenance: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(0, maintenance["after"]["free"]);
+    }
+
+    #[test]
+    fn test_rekey() {
+        let path = temp_db_path();
+        let lock = path.with_extension("lock");
+        {
+            let data = AppData::open(Some(&path), false, None, None).unwrap();
+            create_memo_fn(&data, "t1", "content1").unwrap();
+        }
+
+        rekey(&path, &lock, None, Some("secret")).unwrap();
+        let e = AppData::open(Some(&path), false, None, None).err();
+        assert!(matches!(e, Some(Error::Passphrase)));
+        let e = rekey(&path, &lock, Some("wrong"), None).err();
+        assert!(matches!(e, Some(Error::Passphrase)));
+
+        rekey(&path, &lock, Some("secret"), Some("changed")).unwrap();
+        {
+            let data = AppData::open(Some(&path), false, Some("changed"), None).unwrap();
+            assert_eq!("content1", get_memo_fn(&data, "t1", None).unwrap().content);
+        }
+
+        let held = instance::InstanceLock::acquire(&lock).unwrap().unwrap();
+        let e = rekey(&path, &lock, Some("changed"), None).err();
+        assert!(matches!(e, Some(Error::LockError(_))));
+        drop(held);
+
+        rekey(&path, &lock, Some("changed"), None).unwrap();
+        {
+            let data = AppData::open(Some(&path), false, None, None).unwrap();
+            assert_eq!(1, get_topics_fn(&data, "").unwrap().len());
+        }
+
+        remove_db(&path);
+    }
+
+    #[test]
+    fn test_rekey_wal() {
+        let path = temp_db_path();
+        let lock = path.with_extension("lock");
+        let mut wal = path.as_os_str().to_owned();
+        wal.push("-wal");
+        let wal = PathBuf::from(wal);
+
+        // Log is kept while other connection is open, like one of crashed process.
+        let other = {
+            let data = AppData::open(Some(&path), false, None, None).unwrap();
+            let other = rusqlite::Connection::open(&path).unwrap();
+            other
+                .query_row("SELECT COUNT(*) FROM memo", [], |_| Ok(()))
+                .unwrap();
+            create_memo_fn(&data, "t1", "content1").unwrap();
+            other
+        };
+        assert!(fs::metadata(&wal).unwrap().len() > 0);
+
+        rekey(&path, &lock, None, Some("secret")).unwrap();
+        assert!(!wal.exists());
+        drop(other);
+        {
+            let data = AppData::open(Some(&path), false, Some("secret"), None).unwrap();
+            assert_eq!("content1", get_memo_fn(&data, "t1", None).unwrap().content);
+        }
+
+        remove_db(&path);
+    }
+
+    fn execute_ok(data: &AppData, command: Command, json: bool, input: &str) -> String {
+        let mut output = vec![];
+        let status = execute(data, command, json, &mut input.as_bytes(), &mut output).unwrap();
+        assert_eq!(0, status);
+        String::from_utf8(output).unwrap()
+    }
+
+    fn setup_appdate() -> AppData {
+        let conn = setup_connect();
+        db::Topic::create(&conn, "t1", "title1", 0).unwrap();
+        db::Topic::create(&conn, "t2", "title2", 1).unwrap();
+        db::Memo::create(&conn, "m1", "t1", 0, "content0").unwrap();
+        db::Memo::create(&conn, "m2", "t1", 1, "content1").unwrap();
+        db::Memo::create(&conn, "m3", "t2", 1, "content2").unwrap();
+        db::TopicTag::create(&conn, "tag1", "t1").unwrap();
+
+        AppData::new(conn)
+    }
+}
@@ -0,0 +1,119 @@
+use crate::error::Error;
+use crate::{backup, db};
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const BACKUP_KEEP: usize = 10;
+const BACKUP_INTERVAL_MINUTES: u64 = 60;
+const TRASH_DAYS: u64 = 30;
+
+/// Settings read from `config.json` in data directory.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    pub log_level: Option<String>,
+    pub backup_dir: Option<PathBuf>,
+    pub backup_keep: Option<usize>,
+    /// 0 to back up only at startup and exit.
+    pub backup_interval_minutes: Option<u64>,
+    pub auto_vacuum: Option<db::AutoVacuum>,
+    /// Days to keep deleted memos in trash, 0 to keep until purged.
+    pub trash_days: Option<u64>,
+    /// Sync `#tag` in saved memo into tags of topic.
+    pub inline_tags: Option<bool>,
+}
+
+impl Config {
+    pub fn load() -> Result<Self, Error> {
+        Config::load_from(&db::data_dir().join("config.json"))
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, Error> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        let content = fs::read_to_string(path)?;
+        let config = serde_json::from_str(&content).map_err(io::Error::from)?;
+        Ok(config)
+    }
+
+    pub fn log_level(&self) -> Option<LevelFilter> {
+        self.log_level.as_deref().and_then(|l| l.parse().ok())
+    }
+
+    pub fn trash_days(&self) -> Option<u64> {
+        Some(self.trash_days.unwrap_or(TRASH_DAYS)).filter(|d| *d > 0)
+    }
+
+    pub fn backup(&self) -> backup::Settings {
+        let minutes = self
+            .backup_interval_minutes
+            .unwrap_or(BACKUP_INTERVAL_MINUTES);
+
+        backup::Settings {
+            dir: self
+                .backup_dir
+                .clone()
+                .unwrap_or_else(|| db::data_dir().join("backups")),
+            keep: self.backup_keep.unwrap_or(BACKUP_KEEP),
+            interval: (minutes > 0).then(|| Duration::from_secs(minutes * 60)),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_load_from_not_exists() {
+        let path = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let config = Config::load_from(&path).unwrap();
+        assert!(config.log_level.is_none());
+    }
+
+    #[test]
+    fn test_load_from() {
+        let path = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        fs::write(&path, r#"{"log_level":"debug","unknown":1}"#).unwrap();
+
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(Some(LevelFilter::Debug), config.log_level());
+        assert_eq!(None, config.auto_vacuum);
+        assert_eq!(Some(30), config.trash_days());
+        assert_eq!(None, config.inline_tags);
+
+        fs::write(
+            &path,
+            r#"{"auto_vacuum":"incremental","trash_days":0,"inline_tags":true}"#,
+        )
+        .unwrap();
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(Some(db::AutoVacuum::Incremental), config.auto_vacuum);
+        assert_eq!(None, config.trash_days());
+        assert_eq!(Some(true), config.inline_tags);
+
+        fs::write(
+            &path,
+            r#"{"backup_dir":"/tmp/b","backup_keep":3,"backup_interval_minutes":0}"#,
+        )
+        .unwrap();
+        let settings = Config::load_from(&path).unwrap().backup();
+        assert_eq!(PathBuf::from("/tmp/b"), settings.dir);
+        assert_eq!(3, settings.keep);
+        assert_eq!(None, settings.interval);
+
+        fs::write(&path, "{").unwrap();
+        assert!(Config::load_from(&path).is_err());
+
+        fs::remove_file(path).unwrap();
+    }
+}
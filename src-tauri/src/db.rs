@@ -1,9 +1,149 @@
 use dirs;
 use regex::Regex;
-use rusqlite::Connection;
+use rusqlite::backup::Backup;
 use rusqlite::Error as DbError;
+use rusqlite::{Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+// Copy by steps not to block writes for long.
+const BACKUP_PAGES: i32 = 256;
+const BACKUP_PAUSE: Duration = Duration::from_millis(10);
+
+// -----------------------------------------------------------------------------------------------
+
+pub struct Attachment {
+    pub id: String,
+    pub topic_id: String,
+    pub name: String,
+    pub content: Vec<u8>,
+}
+
+impl Attachment {
+    pub fn all_by_topic(conn: &Connection, topic_id: &str) -> Result<Vec<Attachment>, DbError> {
+        let mut attachments = vec![];
+
+        let mut stmt =
+            conn.prepare("SELECT id, topic_id, name, content FROM attachment WHERE topic_id = ?1")?;
+        let attachment_iter = stmt.query_map([topic_id], |row| {
+            Ok(Attachment {
+                id: row.get(0)?,
+                topic_id: row.get(1)?,
+                name: row.get(2)?,
+                content: row.get(3)?,
+            })
+        })?;
+
+        for a in attachment_iter {
+            attachments.push(a?);
+        }
+
+        Ok(attachments)
+    }
+
+    pub fn create(
+        conn: &Connection,
+        id: &str,
+        topic_id: &str,
+        name: &str,
+        content: &[u8],
+    ) -> Result<Self, DbError> {
+        let _ = conn.execute(
+            "INSERT INTO attachment (id, topic_id, name, content) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![id, topic_id, name, content],
+        )?;
+
+        Ok(Attachment {
+            id: id.to_owned(),
+            topic_id: topic_id.to_owned(),
+            name: name.to_owned(),
+            content: content.to_vec(),
+        })
+    }
+
+    pub fn update(&self, conn: &Connection, name: &str, content: &[u8]) -> Result<(), DbError> {
+        let _ = conn.execute(
+            "UPDATE attachment SET name = ?2, content = ?3 WHERE id = ?1",
+            rusqlite::params![&self.id, name, content],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn delete(&self, conn: &Connection) -> Result<(), DbError> {
+        let _ = conn.execute("DELETE FROM attachment WHERE id = ?1", [&self.id])?;
+
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+pub struct Event {
+    pub seq: i64,
+    pub payload: String,
+    pub timestamp: i64,
+}
+
+impl Event {
+    pub fn all_since(conn: &Connection, seq: i64) -> Result<Vec<Event>, DbError> {
+        let mut events = vec![];
+
+        let mut stmt =
+            conn.prepare("SELECT seq, payload, timestamp FROM event WHERE seq > ?1 ORDER BY seq")?;
+        let event_iter = stmt.query_map([seq], |row| {
+            Ok(Event {
+                seq: row.get(0)?,
+                payload: row.get(1)?,
+                timestamp: row.get(2)?,
+            })
+        })?;
+
+        for e in event_iter {
+            events.push(e?);
+        }
+
+        Ok(events)
+    }
+
+    pub fn create(conn: &Connection, payload: &str, timestamp: i64) -> Result<Self, DbError> {
+        let _ = conn.execute(
+            "INSERT INTO event (payload, timestamp) VALUES (?1, ?2)",
+            rusqlite::params![payload, timestamp],
+        )?;
+
+        Ok(Event {
+            seq: conn.last_insert_rowid(),
+            payload: payload.to_owned(),
+            timestamp,
+        })
+    }
+
+    /// Delete old events except the last one, whose sequence tells restored database.
+    pub fn delete_before(conn: &Connection, timestamp: i64) -> Result<usize, DbError> {
+        conn.execute(
+            "DELETE FROM event WHERE timestamp < ?1 AND seq < (SELECT MAX(seq) FROM event)",
+            [timestamp],
+        )
+    }
+
+    /// Clear title in payloads of events of topic, not to be left in plain text when locked.
+    pub fn clear_title(conn: &Connection, topic_id: &str) -> Result<usize, DbError> {
+        conn.execute(
+            "UPDATE event SET payload = json_set(payload, '$.title', '') WHERE json_valid(payload) AND json_extract(payload, '$.topic_id') = ?1 AND json_type(payload, '$.title') IS NOT NULL",
+            [topic_id],
+        )
+    }
+
+    pub fn last_seq(conn: &Connection) -> Result<i64, DbError> {
+        conn.query_row("SELECT COALESCE(MAX(seq), 0) FROM event", [], |row| {
+            row.get(0)
+        })
+    }
+}
 
 // -----------------------------------------------------------------------------------------------
 
@@ -78,6 +218,64 @@ impl Memo {
 
         Ok(())
     }
+
+    /// Move memo to trash.
+    pub fn trash(&self, conn: &Connection, deleted_at: i64) -> Result<(), DbError> {
+        let _ = conn.execute(
+            "INSERT INTO trash_memo (id, topic_id, timestamp, content, deleted_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![&self.id, &self.topic_id, self.timestamp, &self.content, deleted_at],
+        )?;
+        self.delete(conn)
+    }
+
+    pub fn update_content(&self, conn: &Connection, content: &str) -> Result<(), DbError> {
+        let _ = conn.execute(
+            "UPDATE memo SET content = ?2 WHERE id = ?1",
+            rusqlite::params![&self.id, content],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn count(conn: &Connection) -> Result<i64, DbError> {
+        conn.query_row("SELECT COUNT(*) FROM memo", [], |row| row.get(0))
+    }
+
+    /// Topics having the largest revisions in total.
+    pub fn largest_topics(conn: &Connection, limit: usize) -> Result<Vec<TopicSize>, DbError> {
+        let mut sizes = vec![];
+
+        let mut stmt = conn.prepare(
+            "SELECT topic_id, COUNT(*), SUM(LENGTH(CAST(content AS BLOB))) AS size FROM memo GROUP BY topic_id ORDER BY size DESC, topic_id LIMIT ?1",
+        )?;
+        let size_iter = stmt.query_map([limit as i64], |row| {
+            Ok(TopicSize {
+                topic_id: row.get(0)?,
+                revisions: row.get(1)?,
+                size: row.get(2)?,
+            })
+        })?;
+
+        for s in size_iter {
+            sizes.push(s?);
+        }
+
+        Ok(sizes)
+    }
+
+    /// Topic ids of memos whose topic is missing.
+    pub fn orphan_topic_ids(conn: &Connection) -> Result<Vec<String>, DbError> {
+        query_ids(
+            conn,
+            "SELECT DISTINCT topic_id FROM memo WHERE topic_id NOT IN (SELECT id FROM topic) ORDER BY topic_id",
+        )
+    }
+}
+
+pub struct TopicSize {
+    pub topic_id: String,
+    pub revisions: i64,
+    pub size: i64,
 }
 
 // -----------------------------------------------------------------------------------------------
@@ -108,16 +306,54 @@ impl Topic {
         Ok(topics)
     }
 
+    /// Topics without memo.
+    pub fn all_empty(conn: &Connection) -> Result<Vec<Topic>, DbError> {
+        let mut topics = vec![];
+
+        let mut stmt = conn.prepare(
+            "SELECT id, title, timestamp FROM topic WHERE id NOT IN (SELECT topic_id FROM memo) ORDER BY id",
+        )?;
+        let topic_iter = stmt.query_map([], |row| {
+            Ok(Topic {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                timestamp: row.get(2)?,
+            })
+        })?;
+
+        for t in topic_iter {
+            topics.push(t?);
+        }
+
+        Ok(topics)
+    }
+
+    pub fn resolve<'a>(topics: &'a [Topic], target: &str) -> Option<&'a Topic> {
+        // Link target is topic id or title.
+        topics.iter().find(|t| t.id == target).or_else(|| {
+            topics
+                .iter()
+                .find(|t| t.title.to_lowercase() == target.to_lowercase())
+        })
+    }
+
     pub fn search(conn: &Connection, keyword: &str) -> Result<Vec<Topic>, DbError> {
         let (words, tags) = Topic::split_keyword(keyword);
+        let tags = tags
+            .iter()
+            .map(|t| Tag::resolve(conn, t))
+            .collect::<Result<Vec<_>, _>>()?;
 
         let mut wheres = vec![];
+        let mut params = vec![];
 
-        // TODO: Use placeholder
         if !words.is_empty() {
             let conditions = words
                 .iter()
-                .map(|w| format!("content like '%{w}%'"))
+                .map(|w| {
+                    params.push(format!("%{w}%"));
+                    format!("content like ?{}", params.len())
+                })
                 .collect::<Vec<String>>();
             let condition = conditions.join(" or ");
 
@@ -126,16 +362,19 @@ impl Topic {
             ));
         }
 
-        // TODO: Use placeholder
         if !tags.is_empty() {
+            // Parent tag matches its children.
             let conditions = tags
                 .iter()
-                .map(|t| format!("'{t}'"))
+                .map(|t| {
+                    params.push(t.clone());
+                    tag_under(params.len())
+                })
                 .collect::<Vec<String>>();
-            let condition = conditions.join(", ");
+            let condition = conditions.join(" or ");
 
             wheres.push(format!(
-                "id IN (SELECT DISTINCT topic_id FROM topic_tag WHERE name IN ({condition}))"
+                "id IN (SELECT DISTINCT topic_id FROM topic_tag WHERE {condition})"
             ));
         }
 
@@ -148,7 +387,7 @@ impl Topic {
         let mut topics = vec![];
 
         let mut stmt = conn.prepare(&query)?;
-        let topic_iter = stmt.query_map([], |row| {
+        let topic_iter = stmt.query_map(rusqlite::params_from_iter(&params), |row| {
             Ok(Topic {
                 id: row.get(0)?,
                 title: row.get(1)?,
@@ -198,7 +437,8 @@ impl Topic {
         })
     }
 
-    fn split_keyword(keyword: &str) -> (Vec<String>, Vec<String>) {
+    /// Split keyword into words and #tags.
+    pub fn split_keyword(keyword: &str) -> (Vec<String>, Vec<String>) {
         let re = Regex::new(r"\s").unwrap();
         let words_and_tags = re.split(keyword).collect::<Vec<&str>>();
 
@@ -212,67 +452,759 @@ impl Topic {
             }
         }
 
-        (words, tags)
+        (words, tags)
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Salt and encrypted verifier of topic locked by its own passphrase.
+pub struct TopicLock {
+    pub topic_id: String,
+    pub salt: Vec<u8>,
+    pub verifier: String,
+}
+
+impl TopicLock {
+    pub fn all(conn: &Connection) -> Result<Vec<TopicLock>, DbError> {
+        let mut locks = vec![];
+
+        let mut stmt = conn.prepare("SELECT topic_id, salt, verifier FROM topic_lock")?;
+        let lock_iter = stmt.query_map([], |row| {
+            Ok(TopicLock {
+                topic_id: row.get(0)?,
+                salt: row.get(1)?,
+                verifier: row.get(2)?,
+            })
+        })?;
+
+        for l in lock_iter {
+            locks.push(l?);
+        }
+
+        Ok(locks)
+    }
+
+    pub fn find(conn: &Connection, topic_id: &str) -> Result<Option<TopicLock>, DbError> {
+        let lock = conn.query_row(
+            "SELECT topic_id, salt, verifier FROM topic_lock WHERE topic_id = ?1",
+            [topic_id],
+            |row| {
+                Ok(TopicLock {
+                    topic_id: row.get(0)?,
+                    salt: row.get(1)?,
+                    verifier: row.get(2)?,
+                })
+            },
+        );
+
+        match lock {
+            Ok(l) => Ok(Some(l)),
+            Err(DbError::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn create(
+        conn: &Connection,
+        topic_id: &str,
+        salt: &[u8],
+        verifier: &str,
+    ) -> Result<Self, DbError> {
+        let _ = conn.execute(
+            "INSERT INTO topic_lock (topic_id, salt, verifier) VALUES (?1, ?2, ?3)",
+            rusqlite::params![topic_id, salt, verifier],
+        )?;
+
+        Ok(TopicLock {
+            topic_id: topic_id.to_owned(),
+            salt: salt.to_vec(),
+            verifier: verifier.to_owned(),
+        })
+    }
+
+    pub fn delete(&self, conn: &Connection) -> Result<(), DbError> {
+        let _ = conn.execute(
+            "DELETE FROM topic_lock WHERE topic_id = ?1",
+            [&self.topic_id],
+        )?;
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+pub struct TopicTag {
+    pub name: String,
+    pub topic_id: String,
+}
+
+impl TopicTag {
+    pub fn all(conn: &Connection) -> Result<Vec<String>, DbError> {
+        let mut names = vec![];
+
+        // Tags of locked topic are encrypted.
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT name FROM topic_tag WHERE topic_id NOT IN (SELECT topic_id FROM topic_lock) ORDER BY name",
+        )?;
+        let name_iter = stmt.query_map([], |row| row.get(0))?;
+
+        for n in name_iter {
+            names.push(n?);
+        }
+
+        Ok(names)
+    }
+
+    pub fn all_by_topic(conn: &Connection, topic_id: &str) -> Result<Vec<String>, DbError> {
+        let mut names = vec![];
+
+        let mut stmt = conn.prepare("SELECT name FROM topic_tag WHERE topic_id = ?1")?;
+        let name_iter = stmt.query_map([topic_id], |row| row.get(0))?;
+
+        for n in name_iter {
+            names.push(n?);
+        }
+
+        Ok(names)
+    }
+
+    /// Names of tags collected from `#tag` in content of topic.
+    pub fn inline_by_topic(conn: &Connection, topic_id: &str) -> Result<Vec<String>, DbError> {
+        let mut names = vec![];
+
+        let mut stmt =
+            conn.prepare("SELECT name FROM topic_tag WHERE topic_id = ?1 AND inline = 1")?;
+        let name_iter = stmt.query_map([topic_id], |row| row.get(0))?;
+
+        for n in name_iter {
+            names.push(n?);
+        }
+
+        Ok(names)
+    }
+
+    pub fn create(conn: &Connection, name: &str, topic_id: &str) -> Result<Self, DbError> {
+        let _ = conn.execute(
+            "INSERT INTO topic_tag (name, topic_id) VALUES (?1, ?2)",
+            [name, topic_id],
+        )?;
+
+        Ok(TopicTag {
+            name: name.to_owned(),
+            topic_id: topic_id.to_owned(),
+        })
+    }
+
+    /// Create tag collected from content, removed when it disappears from content.
+    pub fn create_inline(conn: &Connection, name: &str, topic_id: &str) -> Result<Self, DbError> {
+        let _ = conn.execute(
+            "INSERT INTO topic_tag (name, topic_id, inline) VALUES (?1, ?2, 1)",
+            [name, topic_id],
+        )?;
+
+        Ok(TopicTag {
+            name: name.to_owned(),
+            topic_id: topic_id.to_owned(),
+        })
+    }
+
+    pub fn delete(&self, conn: &Connection) -> Result<(), DbError> {
+        let _ = conn.execute(
+            "DELETE FROM topic_tag WHERE name = ?1 and topic_id = ?2",
+            [&self.name, &self.topic_id],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn delete_by_topic(conn: &Connection, topic_id: &str) -> Result<usize, DbError> {
+        conn.execute("DELETE FROM topic_tag WHERE topic_id = ?1", [topic_id])
+    }
+
+    /// Tag is `parent` itself or its descendant like `parent/child`.
+    pub fn is_under(name: &str, parent: &str) -> bool {
+        name.strip_prefix(parent)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+
+    /// Names with number of tagged topics and when the latest of them was updated.
+    pub fn usages(conn: &Connection) -> Result<Vec<TagUsage>, DbError> {
+        let mut usages = vec![];
+
+        let mut stmt = conn.prepare(
+            "SELECT tt.name, COUNT(DISTINCT tt.topic_id), COALESCE(MAX(t.timestamp), 0) FROM topic_tag tt LEFT JOIN topic t ON t.id = tt.topic_id WHERE tt.topic_id NOT IN (SELECT topic_id FROM topic_lock) GROUP BY tt.name ORDER BY tt.name",
+        )?;
+        let usage_iter = stmt.query_map([], |row| {
+            Ok(TagUsage {
+                name: row.get(0)?,
+                count: row.get(1)?,
+                last_used: row.get(2)?,
+            })
+        })?;
+
+        for u in usage_iter {
+            usages.push(u?);
+        }
+
+        Ok(usages)
+    }
+
+    /// Rename tag and its descendants of all topics, also in trash. Tags duplicated by renaming
+    /// into existing tag are merged, and renamed inline tags are kept as manual ones.
+    pub fn rename(conn: &Connection, from: &str, to: &str) -> Result<usize, DbError> {
+        let mut count = 0;
+        for table in ["topic_tag", "trash_topic_tag"] {
+            count += conn.execute(
+                &format!(
+                    "UPDATE {table} SET name = ?2 || substr(name, length(?1) + 1), inline = 0 WHERE {}",
+                    tag_under(1)
+                ),
+                [from, to],
+            )?;
+            conn.execute(
+                &format!(
+                    "DELETE FROM {table} WHERE rowid NOT IN (SELECT MIN(rowid) FROM {table} GROUP BY name, topic_id)"
+                ),
+                [],
+            )?;
+        }
+        Ok(count)
+    }
+
+    /// Delete tag and its descendants from all topics, also in trash.
+    pub fn delete_all(conn: &Connection, name: &str) -> Result<usize, DbError> {
+        let mut count = 0;
+        for table in ["topic_tag", "trash_topic_tag"] {
+            count += conn.execute(
+                &format!("DELETE FROM {table} WHERE {}", tag_under(1)),
+                [name],
+            )?;
+        }
+        Ok(count)
+    }
+
+    /// Topic ids of tags whose topic is missing and has no memo.
+    pub fn orphan_topic_ids(conn: &Connection) -> Result<Vec<String>, DbError> {
+        query_ids(
+            conn,
+            "SELECT DISTINCT topic_id FROM topic_tag WHERE topic_id NOT IN (SELECT id FROM topic) AND topic_id NOT IN (SELECT topic_id FROM memo) ORDER BY topic_id",
+        )
+    }
+}
+
+/// Condition of `TopicTag::is_under` with parent as parameter of `index`.
+fn tag_under(index: usize) -> String {
+    format!("(name = ?{index} OR substr(name, 1, length(?{index}) + 1) = ?{index} || '/')")
+}
+
+pub struct TagUsage {
+    pub name: String,
+    pub count: i64,
+    pub last_used: i64,
+}
+
+fn query_ids(conn: &Connection, sql: &str) -> Result<Vec<String>, DbError> {
+    let mut ids = vec![];
+
+    let mut stmt = conn.prepare(sql)?;
+    let id_iter = stmt.query_map([], |row| row.get(0))?;
+
+    for id in id_iter {
+        ids.push(id?);
+    }
+
+    Ok(ids)
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Metadata of tag, and aliases resolved into its name.
+pub struct Tag {
+    pub name: String,
+    pub color: Option<String>,
+    pub description: Option<String>,
+    pub aliases: Vec<String>,
+}
+
+impl Tag {
+    pub fn all(conn: &Connection) -> Result<Vec<Tag>, DbError> {
+        let mut tags = vec![];
+
+        let mut stmt = conn.prepare("SELECT name, color, description FROM tag ORDER BY name")?;
+        let tag_iter = stmt.query_map([], |row| {
+            Ok(Tag {
+                name: row.get(0)?,
+                color: row.get(1)?,
+                description: row.get(2)?,
+                aliases: vec![],
+            })
+        })?;
+
+        for t in tag_iter {
+            tags.push(t?);
+        }
+
+        let mut stmt = conn.prepare("SELECT alias, name FROM tag_alias ORDER BY alias")?;
+        let alias_iter = stmt.query_map([], |row| Ok((row.get(0)?, row.get::<_, String>(1)?)))?;
+
+        for a in alias_iter {
+            let (alias, name) = a?;
+            if let Some(t) = tags.iter_mut().find(|t| t.name == name) {
+                t.aliases.push(alias);
+            }
+        }
+
+        Ok(tags)
+    }
+
+    /// Insert or update metadata of tag, keeping fields of None and clearing fields of empty
+    /// string.
+    pub fn upsert(
+        conn: &Connection,
+        name: &str,
+        color: Option<&str>,
+        description: Option<&str>,
+    ) -> Result<(), DbError> {
+        let _ = conn.execute(
+            "INSERT INTO tag (name, color, description) VALUES (?1, NULLIF(?2, ''), NULLIF(?3, '')) ON CONFLICT(name) DO UPDATE SET color = CASE WHEN ?2 IS NULL THEN color ELSE excluded.color END, description = CASE WHEN ?3 IS NULL THEN description ELSE excluded.description END",
+            rusqlite::params![name, color, description],
+        )?;
+
+        Ok(())
+    }
+
+    /// Replace aliases of tag.
+    pub fn set_aliases(conn: &Connection, name: &str, aliases: &[String]) -> Result<(), DbError> {
+        let _ = conn.execute("DELETE FROM tag_alias WHERE name = ?1", [name])?;
+        for alias in aliases {
+            let _ = conn.execute(
+                "INSERT INTO tag_alias (alias, name) VALUES (?1, ?2)",
+                [alias, name],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Name of tag replaced by the longest alias matching it or its parent, e.g. `js/react`
+    /// into `javascript/react` by alias `js`.
+    pub fn resolve(conn: &Connection, name: &str) -> Result<String, DbError> {
+        let alias = conn.query_row(
+            "SELECT alias, name FROM tag_alias WHERE alias = ?1 OR substr(?1, 1, length(alias) + 1) = alias || '/' ORDER BY length(alias) DESC LIMIT 1",
+            [name],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        );
+
+        match alias {
+            Ok((alias, target)) => Ok(format!("{target}{}", &name[alias.len()..])),
+            Err(DbError::QueryReturnedNoRows) => Ok(name.to_owned()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Move metadata of tag and its descendants like `TopicTag::rename`, keeping existing
+    /// metadata of new name.
+    pub fn rename(conn: &Connection, from: &str, to: &str) -> Result<(), DbError> {
+        let _ = conn.execute(
+            &format!(
+                "UPDATE OR IGNORE tag SET name = ?2 || substr(name, length(?1) + 1) WHERE {}",
+                tag_under(1)
+            ),
+            [from, to],
+        )?;
+        let _ = conn.execute(&format!("DELETE FROM tag WHERE {}", tag_under(1)), [from])?;
+        let _ = conn.execute(
+            &format!(
+                "UPDATE tag_alias SET name = ?2 || substr(name, length(?1) + 1) WHERE {}",
+                tag_under(1)
+            ),
+            [from, to],
+        )?;
+        Ok(())
+    }
+
+    /// Delete metadata and aliases of tag and its descendants.
+    pub fn delete_all(conn: &Connection, name: &str) -> Result<(), DbError> {
+        let _ = conn.execute(&format!("DELETE FROM tag WHERE {}", tag_under(1)), [name])?;
+        let _ = conn.execute(
+            &format!("DELETE FROM tag_alias WHERE {}", tag_under(1)),
+            [name],
+        )?;
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Memo moved to trash by deletion.
+pub struct TrashMemo {
+    pub id: String,
+    pub topic_id: String,
+    pub timestamp: i64,
+    pub content: String,
+    pub deleted_at: i64,
+}
+
+impl TrashMemo {
+    /// Recently deleted first.
+    pub fn all(conn: &Connection) -> Result<Vec<TrashMemo>, DbError> {
+        let mut memos = vec![];
+
+        let mut stmt = conn.prepare(
+            "SELECT id, topic_id, timestamp, content, deleted_at FROM trash_memo ORDER BY deleted_at DESC, timestamp DESC",
+        )?;
+        let memo_iter = stmt.query_map([], |row| {
+            Ok(TrashMemo {
+                id: row.get(0)?,
+                topic_id: row.get(1)?,
+                timestamp: row.get(2)?,
+                content: row.get(3)?,
+                deleted_at: row.get(4)?,
+            })
+        })?;
+
+        for m in memo_iter {
+            memos.push(m?);
+        }
+
+        Ok(memos)
+    }
+
+    pub fn all_by_topic(conn: &Connection, topic_id: &str) -> Result<Vec<TrashMemo>, DbError> {
+        let mut memos = vec![];
+
+        let mut stmt = conn.prepare(
+            "SELECT id, topic_id, timestamp, content, deleted_at FROM trash_memo WHERE topic_id = ?1",
+        )?;
+        let memo_iter = stmt.query_map([topic_id], |row| {
+            Ok(TrashMemo {
+                id: row.get(0)?,
+                topic_id: row.get(1)?,
+                timestamp: row.get(2)?,
+                content: row.get(3)?,
+                deleted_at: row.get(4)?,
+            })
+        })?;
+
+        for m in memo_iter {
+            memos.push(m?);
+        }
+
+        Ok(memos)
+    }
+
+    pub fn update_content(&self, conn: &Connection, content: &str) -> Result<(), DbError> {
+        let _ = conn.execute(
+            "UPDATE trash_memo SET content = ?2 WHERE id = ?1",
+            rusqlite::params![&self.id, content],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn restore(&self, conn: &Connection) -> Result<(), DbError> {
+        let _ = conn.execute(
+            "INSERT INTO memo (id, topic_id, timestamp, content) SELECT id, topic_id, timestamp, content FROM trash_memo WHERE id = ?1",
+            [&self.id],
+        )?;
+        self.purge(conn)
+    }
+
+    pub fn purge(&self, conn: &Connection) -> Result<(), DbError> {
+        let _ = conn.execute("DELETE FROM trash_memo WHERE id = ?1", [&self.id])?;
+        Ok(())
+    }
+}
+
+/// Topic moved to trash with its tags and attachments, when all memos are deleted.
+pub struct TrashTopic {
+    pub id: String,
+    pub title: String,
+    pub timestamp: i64,
+    pub deleted_at: i64,
+}
+
+impl TrashTopic {
+    pub fn all(conn: &Connection) -> Result<Vec<TrashTopic>, DbError> {
+        let mut topics = vec![];
+
+        let mut stmt = conn.prepare("SELECT id, title, timestamp, deleted_at FROM trash_topic")?;
+        let topic_iter = stmt.query_map([], |row| {
+            Ok(TrashTopic {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                timestamp: row.get(2)?,
+                deleted_at: row.get(3)?,
+            })
+        })?;
+
+        for t in topic_iter {
+            topics.push(t?);
+        }
+
+        Ok(topics)
+    }
+
+    /// Move topic to trash, also tags and attachments of topic missing by inconsistency.
+    pub fn create(conn: &Connection, topic_id: &str, deleted_at: i64) -> Result<(), DbError> {
+        // Topic of the same id may be deleted again after recreated, whose tags and attachments
+        // are replaced not to be duplicated when restored.
+        let _ = conn.execute("DELETE FROM trash_topic WHERE id = ?1", [topic_id])?;
+        let _ = conn.execute(
+            "DELETE FROM trash_topic_tag WHERE topic_id = ?1",
+            [topic_id],
+        )?;
+        let _ = conn.execute(
+            "DELETE FROM trash_attachment WHERE topic_id = ?1",
+            [topic_id],
+        )?;
+        let _ = conn.execute(
+            "INSERT INTO trash_topic (id, timestamp, title, deleted_at) SELECT id, timestamp, title, ?2 FROM topic WHERE id = ?1",
+            rusqlite::params![topic_id, deleted_at],
+        )?;
+        let _ = conn.execute(
+            "INSERT INTO trash_topic_tag (name, topic_id, inline) SELECT name, topic_id, inline FROM topic_tag WHERE topic_id = ?1",
+            [topic_id],
+        )?;
+        let _ = conn.execute(
+            "INSERT INTO trash_attachment (id, topic_id, name, content) SELECT id, topic_id, name, content FROM attachment WHERE topic_id = ?1",
+            [topic_id],
+        )?;
+
+        let _ = conn.execute("DELETE FROM topic_tag WHERE topic_id = ?1", [topic_id])?;
+        let _ = conn.execute("DELETE FROM attachment WHERE topic_id = ?1", [topic_id])?;
+        let _ = conn.execute("DELETE FROM topic WHERE id = ?1", [topic_id])?;
+
+        Ok(())
+    }
+
+    /// Restore topic with its tags and attachments, merged into topic recreated after deletion.
+    pub fn restore(&self, conn: &Connection) -> Result<(), DbError> {
+        let _ = conn.execute(
+            "INSERT INTO topic (id, timestamp, title) SELECT id, timestamp, title FROM trash_topic WHERE id = ?1 AND id NOT IN (SELECT id FROM topic)",
+            [&self.id],
+        )?;
+        let _ = conn.execute(
+            "INSERT INTO topic_tag (name, topic_id, inline) SELECT name, topic_id, MIN(inline) FROM trash_topic_tag WHERE topic_id = ?1 AND name NOT IN (SELECT name FROM topic_tag WHERE topic_id = ?1) GROUP BY name, topic_id",
+            [&self.id],
+        )?;
+        let _ = conn.execute(
+            "INSERT INTO attachment (id, topic_id, name, content) SELECT id, topic_id, name, content FROM trash_attachment WHERE topic_id = ?1",
+            [&self.id],
+        )?;
+        self.purge(conn)
+    }
+
+    pub fn purge(&self, conn: &Connection) -> Result<(), DbError> {
+        let _ = conn.execute("DELETE FROM trash_topic WHERE id = ?1", [&self.id])?;
+        let _ = conn.execute(
+            "DELETE FROM trash_topic_tag WHERE topic_id = ?1",
+            [&self.id],
+        )?;
+        let _ = conn.execute(
+            "DELETE FROM trash_attachment WHERE topic_id = ?1",
+            [&self.id],
+        )?;
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Open database, which is encrypted by SQLCipher when `passphrase` is specified.
+///
+/// Fails with `ErrorCode::NotADatabase` if passphrase does not match.
+pub fn setup(
+    file_path: Option<&Path>,
+    in_memory: bool,
+    passphrase: Option<&str>,
+    auto_vacuum: Option<AutoVacuum>,
+) -> Result<Connection, DbError> {
+    let db = if in_memory {
+        log::info!("open database in memory");
+        Connection::open_in_memory()
+    } else {
+        let db_path = file_path.map_or_else(create_default_path, Path::to_path_buf);
+        log::info!("open database path={}", db_path.display());
+        Connection::open(db_path)
+    }?;
+    apply_key(&db, passphrase)?;
+    if let Some(mode) = auto_vacuum {
+        apply_auto_vacuum(&db, mode)?;
+    }
+
+    // Readers do not block writer, and vice versa.
+    let _: String = db.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+    db.pragma_update(None, "synchronous", "NORMAL")?;
+    db.busy_timeout(BUSY_TIMEOUT)?;
+
+    create_table_if_not_exists(&db)?;
+
+    Ok(db)
+}
+
+/// Mode of `PRAGMA auto_vacuum`, which shrinks file when data is deleted.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AutoVacuum {
+    None = 0,
+    Full = 1,
+    Incremental = 2,
+}
+
+fn apply_auto_vacuum(conn: &Connection, mode: AutoVacuum) -> Result<(), DbError> {
+    let current: i64 = conn.pragma_query_value(None, "auto_vacuum", |row| row.get(0))?;
+    if current == mode as i64 {
+        return Ok(());
+    }
+
+    // Existing database is converted only by vacuum.
+    log::info!("change auto_vacuum from={current} to={mode:?}");
+    conn.pragma_update(None, "auto_vacuum", mode as i64)?;
+    conn.execute_batch("VACUUM")
+}
+
+/// Open read-only connections to the same file as `conn`.
+///
+/// Returns nothing for in-memory database which cannot be shared.
+pub fn open_readers(
+    conn: &Connection,
+    count: usize,
+    passphrase: Option<&str>,
+) -> Result<Vec<Connection>, DbError> {
+    let path = match conn.path() {
+        Some(p) if !p.is_empty() => p,
+        _ => return Ok(vec![]),
+    };
+
+    let flags = OpenFlags::SQLITE_OPEN_READ_ONLY
+        | OpenFlags::SQLITE_OPEN_NO_MUTEX
+        | OpenFlags::SQLITE_OPEN_URI;
+
+    let mut readers = vec![];
+    for _ in 0..count {
+        let reader = Connection::open_with_flags(path, flags)?;
+        apply_key(&reader, passphrase)?;
+        reader.busy_timeout(BUSY_TIMEOUT)?;
+        readers.push(reader);
     }
+
+    Ok(readers)
 }
 
-// -----------------------------------------------------------------------------------------------
+/// Copy database to `dest` encrypted by `new_passphrase`, or as plain text if `None`.
+///
+/// Write-ahead log of database is checkpointed and truncated, not to be left with pages.
+pub fn export(
+    path: &Path,
+    passphrase: Option<&str>,
+    dest: &Path,
+    new_passphrase: Option<&str>,
+) -> Result<(), DbError> {
+    // Attached database is created by flags of this connection.
+    let conn = Connection::open(path)?;
+    apply_key(&conn, passphrase)?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+
+    conn.execute(
+        "ATTACH DATABASE ?1 AS dest KEY ?2",
+        rusqlite::params![dest.to_string_lossy(), new_passphrase.unwrap_or("")],
+    )?;
+    conn.query_row("SELECT sqlcipher_export('dest')", [], |_| Ok(()))?;
+    conn.execute("DETACH DATABASE dest", [])?;
+
+    let busy: i64 = conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |r| r.get(0))?;
+    if busy != 0 {
+        return Err(DbError::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+            Some("checkpoint is blocked by other connection".to_owned()),
+        ));
+    }
 
-pub struct TopicTag {
-    pub name: String,
-    pub topic_id: String,
+    Ok(())
 }
 
-impl TopicTag {
-    pub fn all_by_topic(conn: &Connection, topic_id: &str) -> Result<Vec<String>, DbError> {
-        let mut names = vec![];
-
-        let mut stmt = conn.prepare("SELECT name FROM topic_tag WHERE topic_id = ?1")?;
-        let name_iter = stmt.query_map([topic_id], |row| row.get(0))?;
+/// Copy database of `conn` to `dest` by online backup, while other connections keep using it.
+pub fn backup(conn: &Connection, dest: &Path, passphrase: Option<&str>) -> Result<(), DbError> {
+    let mut to = Connection::open(dest)?;
+    // Encrypted database is copied only to one with the same key.
+    apply_key(&to, passphrase)?;
+    let backup = Backup::new(conn, &mut to)?;
+    backup.run_to_completion(BACKUP_PAGES, BACKUP_PAUSE, None)
+}
 
-        for n in name_iter {
-            names.push(n?);
-        }
+/// Replace database of `conn` by content of `src` copied by online backup.
+pub fn restore(conn: &mut Connection, src: &Path, passphrase: Option<&str>) -> Result<(), DbError> {
+    let from = Connection::open_with_flags(src, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    apply_key(&from, passphrase)?;
+    let backup = Backup::new(&from, conn)?;
+    backup.run_to_completion(BACKUP_PAGES, BACKUP_PAUSE, None)
+}
 
-        Ok(names)
+fn apply_key(conn: &Connection, passphrase: Option<&str>) -> Result<(), DbError> {
+    // Key must be set before any other statement.
+    if let Some(p) = passphrase {
+        conn.pragma_update(None, "key", p)?;
     }
+    Ok(())
+}
 
-    pub fn create(conn: &Connection, name: &str, topic_id: &str) -> Result<Self, DbError> {
-        let _ = conn.execute(
-            "INSERT INTO topic_tag (name, topic_id) VALUES (?1, ?2)",
-            [name, topic_id],
-        )?;
+/// Version changed when other connection, including other process, commits to the database.
+///
+/// Commits by `conn` itself do not change it.
+pub fn data_version(conn: &Connection) -> Result<i64, DbError> {
+    conn.pragma_query_value(None, "data_version", |row| row.get(0))
+}
 
-        Ok(TopicTag {
-            name: name.to_owned(),
-            topic_id: topic_id.to_owned(),
-        })
-    }
+/// Messages of `PRAGMA integrity_check`, empty if database is not corrupted.
+pub fn integrity_check(conn: &Connection) -> Result<Vec<String>, DbError> {
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    let messages = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
 
-    pub fn delete(&self, conn: &Connection) -> Result<(), DbError> {
-        let _ = conn.execute(
-            "DELETE FROM topic_tag WHERE name = ?1 and topic_id = ?2",
-            [&self.name, &self.topic_id],
-        )?;
+    Ok(messages.into_iter().filter(|m| m != "ok").collect())
+}
 
-        Ok(())
-    }
+pub struct TableSize {
+    pub name: String,
+    pub size: i64,
 }
 
-// -----------------------------------------------------------------------------------------------
+/// Bytes used by each table and index, largest first.
+pub fn table_sizes(conn: &Connection) -> Result<Vec<TableSize>, DbError> {
+    let mut sizes = vec![];
 
-pub fn setup(file_path: Option<&Path>, in_memory: bool) -> Result<Connection, DbError> {
-    let db = if in_memory {
-        Connection::open_in_memory()
-    } else if let Some(db_path) = file_path {
-        Connection::open(db_path)
-    } else {
-        Connection::open(create_default_path())
-    }?;
+    let mut stmt =
+        conn.prepare("SELECT name, SUM(pgsize) FROM dbstat GROUP BY name ORDER BY 2 DESC, name")?;
+    let size_iter = stmt.query_map([], |row| {
+        Ok(TableSize {
+            name: row.get(0)?,
+            size: row.get(1)?,
+        })
+    })?;
 
-    create_table_if_not_exists(&db)?;
+    for s in size_iter {
+        sizes.push(s?);
+    }
 
-    Ok(db)
+    Ok(sizes)
+}
+
+/// Bytes of database file, and of its free pages.
+pub fn file_size(conn: &Connection) -> Result<(i64, i64), DbError> {
+    let pragma = |name| conn.pragma_query_value(None, name, |row| row.get::<_, i64>(0));
+    let page_size = pragma("page_size")?;
+    Ok((
+        page_size * pragma("page_count")?,
+        page_size * pragma("freelist_count")?,
+    ))
+}
+
+/// Reclaim free pages and update statistics for query planner.
+pub fn vacuum(conn: &Connection) -> Result<(), DbError> {
+    conn.execute_batch("VACUUM; ANALYZE; PRAGMA optimize;")
 }
 
 pub fn create_table_if_not_exists(conn: &Connection) -> Result<(), DbError> {
@@ -285,20 +1217,85 @@ pub fn create_table_if_not_exists(conn: &Connection) -> Result<(), DbError> {
         [],
     )?;
     let _ = conn.execute(
-        "CREATE TABLE IF NOT EXISTS topic_tag (name TEXT, topic_id TEXT)",
+        "CREATE TABLE IF NOT EXISTS topic_tag (name TEXT, topic_id TEXT, inline INTEGER NOT NULL DEFAULT 0)",
+        [],
+    )?;
+    let _ = conn.execute(
+        "CREATE TABLE IF NOT EXISTS tag (name TEXT PRIMARY KEY, color TEXT, description TEXT)",
+        [],
+    )?;
+    let _ = conn.execute(
+        "CREATE TABLE IF NOT EXISTS tag_alias (alias TEXT PRIMARY KEY, name TEXT)",
+        [],
+    )?;
+    let _ = conn.execute(
+        "CREATE TABLE IF NOT EXISTS attachment (id TEXT, topic_id TEXT, name TEXT, content BLOB)",
+        [],
+    )?;
+    let _ = conn.execute(
+        "CREATE TABLE IF NOT EXISTS topic_lock (topic_id TEXT PRIMARY KEY, salt BLOB, verifier TEXT)",
+        [],
+    )?;
+    let _ = conn.execute(
+        "CREATE TABLE IF NOT EXISTS trash_memo (id TEXT, topic_id TEXT, timestamp INTEGER, content TEXT, deleted_at INTEGER)",
+        [],
+    )?;
+    let _ = conn.execute(
+        "CREATE TABLE IF NOT EXISTS trash_topic (id TEXT, timestamp INTEGER, title TEXT, deleted_at INTEGER)",
+        [],
+    )?;
+    let _ = conn.execute(
+        "CREATE TABLE IF NOT EXISTS trash_topic_tag (name TEXT, topic_id TEXT, inline INTEGER NOT NULL DEFAULT 0)",
+        [],
+    )?;
+    let _ = conn.execute(
+        "CREATE TABLE IF NOT EXISTS trash_attachment (id TEXT, topic_id TEXT, name TEXT, content BLOB)",
+        [],
+    )?;
+    let _ = conn.execute(
+        "CREATE TABLE IF NOT EXISTS event (seq INTEGER PRIMARY KEY AUTOINCREMENT, payload TEXT, timestamp INTEGER)",
         [],
     )?;
+
+    // Added to tables created by older versions.
+    for table in ["topic_tag", "trash_topic_tag"] {
+        add_column_if_not_exists(conn, table, "inline", "INTEGER NOT NULL DEFAULT 0")?;
+    }
+    Ok(())
+}
+
+fn add_column_if_not_exists(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), DbError> {
+    let exists: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info(?1) WHERE name = ?2",
+        [table, column],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        let _ = conn.execute(
+            &format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"),
+            [],
+        )?;
+    }
     Ok(())
 }
 
-fn create_default_path() -> PathBuf {
+pub fn data_dir() -> PathBuf {
     let base = dirs::data_dir().unwrap().join("markdown-memo");
 
     if !base.exists() {
         fs::create_dir_all(&base).unwrap();
     }
 
-    base.join("memo.db")
+    base
+}
+
+pub(crate) fn create_default_path() -> PathBuf {
+    data_dir().join("memo.db")
 }
 
 // -----------------------------------------------------------------------------------------------
@@ -306,6 +1303,44 @@ fn create_default_path() -> PathBuf {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::{remove_db, setup_connect, temp_db_path};
+    use rusqlite::ErrorCode;
+
+    #[test]
+    fn test_attachment_all_by_topic_is_empty() {
+        let conn = setup_connect();
+        let a = Attachment::all_by_topic(&conn, "t1").unwrap();
+        assert_eq!(0, a.len());
+    }
+
+    #[test]
+    fn test_attachment_create() {
+        let conn = setup_connect();
+        let a = Attachment::create(&conn, "a1", "t1", "a.png", &[1, 2]).unwrap();
+        assert_eq!("a1", &a.id);
+        assert_eq!("t1", &a.topic_id);
+        assert_eq!("a.png", &a.name);
+        assert_eq!(vec![1, 2], a.content);
+
+        let attachments = Attachment::all_by_topic(&conn, "t1").unwrap();
+        for a in attachments {
+            assert_eq!("a1", &a.id);
+            assert_eq!("t1", &a.topic_id);
+            assert_eq!("a.png", &a.name);
+            assert_eq!(vec![1, 2], a.content);
+        }
+    }
+
+    #[test]
+    fn test_attachment_delete() {
+        let conn = setup_connect();
+        let a = Attachment::create(&conn, "a1", "t1", "a.png", &[1, 2]).unwrap();
+
+        a.delete(&conn).unwrap();
+
+        let attachments = Attachment::all_by_topic(&conn, "t1").unwrap();
+        assert_eq!(0, attachments.len());
+    }
 
     #[test]
     fn test_memo_all_by_topic_is_empty() {
@@ -363,6 +1398,26 @@ mod tests {
         assert_eq!(0, m.len());
     }
 
+    #[test]
+    fn test_topic_resolve() {
+        let topics = vec![
+            Topic {
+                id: "t1".to_owned(),
+                title: "Title".to_owned(),
+                timestamp: 0,
+            },
+            Topic {
+                id: "t2".to_owned(),
+                title: "t1".to_owned(),
+                timestamp: 0,
+            },
+        ];
+
+        assert_eq!("t1", Topic::resolve(&topics, "t1").unwrap().id);
+        assert_eq!("t1", Topic::resolve(&topics, "title").unwrap().id);
+        assert!(Topic::resolve(&topics, "t3").is_none());
+    }
+
     #[test]
     fn test_topic_search_words() {
         let conn = setup_connect();
@@ -401,6 +1456,44 @@ mod tests {
         assert_eq!(0, topics.len());
     }
 
+    #[test]
+    fn test_topic_search_tags_hierarchy() {
+        let conn = setup_connect();
+        Topic::create(&conn, "t1", "title1", 0).unwrap();
+        Topic::create(&conn, "t2", "title2", 1).unwrap();
+        Topic::create(&conn, "t3", "title3", 2).unwrap();
+        TopicTag::create(&conn, "project", "t1").unwrap();
+        TopicTag::create(&conn, "project/alpha", "t2").unwrap();
+        TopicTag::create(&conn, "projects", "t3").unwrap();
+
+        let topics = Topic::search(&conn, "#project").unwrap();
+        assert_eq!(2, topics.len());
+
+        let topics = Topic::search(&conn, "#project/alpha").unwrap();
+        assert_eq!(1, topics.len());
+        assert_eq!("t2", topics[0].id);
+    }
+
+    #[test]
+    fn test_topic_search_quote() {
+        let conn = setup_connect();
+        Topic::create(&conn, "t1", "title1", 0).unwrap();
+        Topic::create(&conn, "t2", "title2", 1).unwrap();
+        Memo::create(&conn, "m1", "t1", 0, "it's").unwrap();
+        TopicTag::create(&conn, "o'neil", "t1").unwrap();
+        TopicTag::create(&conn, "tag", "t2").unwrap();
+
+        let topics = Topic::search(&conn, "#o'neil").unwrap();
+        assert_eq!(1, topics.len());
+        assert_eq!("t1", topics[0].id);
+
+        let topics = Topic::search(&conn, "#x') OR 1=1 OR ('").unwrap();
+        assert!(topics.is_empty());
+
+        let topics = Topic::search(&conn, "it's").unwrap();
+        assert_eq!(1, topics.len());
+    }
+
     #[test]
     fn test_topic_search_words_tags() {
         let conn = setup_connect();
@@ -421,6 +1514,13 @@ mod tests {
 
         let topics = Topic::search(&conn, "cd #cde").unwrap();
         assert_eq!(0, topics.len());
+
+        // Tags follow parameters of words.
+        let topics = Topic::search(&conn, "ab cd #xyz #abc").unwrap();
+        assert_eq!(2, topics.len());
+
+        let topics = Topic::search(&conn, "ab de #xyz #cde").unwrap();
+        assert_eq!(1, topics.len());
     }
 
     #[test]
@@ -489,6 +1589,87 @@ mod tests {
         assert_eq!(vec!["bc".to_owned()], t);
     }
 
+    #[test]
+    fn test_event_create() {
+        let conn = setup_connect();
+        assert_eq!(0, Event::last_seq(&conn).unwrap());
+
+        let e1 = Event::create(&conn, "a", 0).unwrap();
+        let e2 = Event::create(&conn, "b", 1).unwrap();
+        assert!(e1.seq < e2.seq);
+        assert_eq!(e2.seq, Event::last_seq(&conn).unwrap());
+
+        let events = Event::all_since(&conn, e1.seq).unwrap();
+        assert_eq!(1, events.len());
+        assert_eq!("b", events[0].payload);
+    }
+
+    #[test]
+    fn test_event_delete_before() {
+        let conn = setup_connect();
+        Event::create(&conn, "a", 0).unwrap();
+        let e = Event::create(&conn, "b", 1).unwrap();
+
+        assert_eq!(1, Event::delete_before(&conn, 1).unwrap());
+        assert_eq!(e.seq, Event::last_seq(&conn).unwrap());
+        assert_eq!(1, Event::all_since(&conn, 0).unwrap().len());
+
+        assert_eq!(0, Event::delete_before(&conn, 2).unwrap());
+        assert_eq!(e.seq, Event::last_seq(&conn).unwrap());
+    }
+
+    #[test]
+    fn test_event_clear_title() {
+        let conn = setup_connect();
+        Event::create(
+            &conn,
+            r#"{"kind":"topic-created","topic_id":"t1","title":"a"}"#,
+            0,
+        )
+        .unwrap();
+        Event::create(
+            &conn,
+            r#"{"kind":"topic-created","topic_id":"t2","title":"b"}"#,
+            0,
+        )
+        .unwrap();
+        Event::create(&conn, "invalid", 0).unwrap();
+
+        assert_eq!(1, Event::clear_title(&conn, "t1").unwrap());
+        let events = Event::all_since(&conn, 0).unwrap();
+        assert_eq!(
+            r#"{"kind":"topic-created","topic_id":"t1","title":""}"#,
+            events[0].payload
+        );
+        assert!(events[1].payload.contains(r#""title":"b""#));
+    }
+
+    #[test]
+    fn test_topic_lock() {
+        let conn = setup_connect();
+        assert!(TopicLock::find(&conn, "t1").unwrap().is_none());
+
+        TopicLock::create(&conn, "t1", &[1, 2], "v").unwrap();
+        let lock = TopicLock::find(&conn, "t1").unwrap().unwrap();
+        assert_eq!(vec![1, 2], lock.salt);
+        assert_eq!("v", lock.verifier);
+        assert!(TopicLock::create(&conn, "t1", &[], "").is_err());
+
+        lock.delete(&conn).unwrap();
+        assert_eq!(0, TopicLock::all(&conn).unwrap().len());
+    }
+
+    #[test]
+    fn test_topic_tag_all() {
+        let conn = setup_connect();
+        TopicTag::create(&conn, "b", "t1").unwrap();
+        TopicTag::create(&conn, "a", "t1").unwrap();
+        TopicTag::create(&conn, "b", "t2").unwrap();
+
+        let tags = TopicTag::all(&conn).unwrap();
+        assert_eq!(vec!["a".to_owned(), "b".to_owned()], tags);
+    }
+
     #[test]
     fn test_topic_tag_all_by_topic_is_empty() {
         let conn = setup_connect();
@@ -520,10 +1701,114 @@ mod tests {
         assert_eq!(0, tags.len());
     }
 
-    fn setup_connect() -> Connection {
+    #[test]
+    fn test_topic_tag_is_under() {
+        assert!(TopicTag::is_under("project", "project"));
+        assert!(TopicTag::is_under("project/alpha", "project"));
+        assert!(!TopicTag::is_under("projects", "project"));
+        assert!(!TopicTag::is_under("project", "project/alpha"));
+    }
+
+    #[test]
+    fn test_topic_tag_usages() {
+        let conn = setup_connect();
+        Topic::create(&conn, "t1", "title1", 10).unwrap();
+        Topic::create(&conn, "t2", "title2", 20).unwrap();
+        TopicTag::create(&conn, "b", "t1").unwrap();
+        TopicTag::create(&conn, "a", "t1").unwrap();
+        TopicTag::create(&conn, "b", "t2").unwrap();
+
+        let usages = TopicTag::usages(&conn).unwrap();
+        assert_eq!(2, usages.len());
+        assert_eq!("a", usages[0].name);
+        assert_eq!((1, 10), (usages[0].count, usages[0].last_used));
+        assert_eq!("b", usages[1].name);
+        assert_eq!((2, 20), (usages[1].count, usages[1].last_used));
+    }
+
+    #[test]
+    fn test_topic_tag_rename() {
+        let conn = setup_connect();
+        TopicTag::create(&conn, "project", "t1").unwrap();
+        TopicTag::create(&conn, "project/alpha", "t2").unwrap();
+        TopicTag::create(&conn, "projects", "t3").unwrap();
+        TopicTag::create(&conn, "work", "t1").unwrap();
+
+        assert_eq!(2, TopicTag::rename(&conn, "project", "work").unwrap());
+        assert_eq!(vec!["work"], TopicTag::all_by_topic(&conn, "t1").unwrap());
+        assert_eq!(
+            vec!["work/alpha"],
+            TopicTag::all_by_topic(&conn, "t2").unwrap()
+        );
+        assert_eq!(
+            vec!["projects"],
+            TopicTag::all_by_topic(&conn, "t3").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_topic_tag_delete_all() {
+        let conn = setup_connect();
+        TopicTag::create(&conn, "project", "t1").unwrap();
+        TopicTag::create(&conn, "project/alpha", "t2").unwrap();
+        TopicTag::create(&conn, "projects", "t3").unwrap();
+
+        assert_eq!(2, TopicTag::delete_all(&conn, "project").unwrap());
+        assert_eq!(vec!["projects"], TopicTag::all(&conn).unwrap());
+    }
+
+    #[test]
+    fn test_topic_tag_inline_by_topic() {
+        let conn = setup_connect();
+        TopicTag::create(&conn, "a", "t1").unwrap();
+        TopicTag::create_inline(&conn, "b", "t1").unwrap();
+        TopicTag::create_inline(&conn, "c", "t2").unwrap();
+
+        assert_eq!(vec!["a", "b"], TopicTag::all_by_topic(&conn, "t1").unwrap());
+        assert_eq!(vec!["b"], TopicTag::inline_by_topic(&conn, "t1").unwrap());
+
+        TopicTag::rename(&conn, "b", "d").unwrap();
+        assert!(TopicTag::inline_by_topic(&conn, "t1").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_create_table_add_column() {
         let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE topic_tag (name TEXT, topic_id TEXT)", [])
+            .unwrap();
+        conn.execute("INSERT INTO topic_tag VALUES ('a', 't1')", [])
+            .unwrap();
+
+        create_table_if_not_exists(&conn).unwrap();
         create_table_if_not_exists(&conn).unwrap();
-        conn
+        assert!(TopicTag::inline_by_topic(&conn, "t1").unwrap().is_empty());
+        TopicTag::create_inline(&conn, "b", "t1").unwrap();
+        assert_eq!(vec!["b"], TopicTag::inline_by_topic(&conn, "t1").unwrap());
+    }
+
+    #[test]
+    fn test_tag_upsert_resolve() {
+        let conn = setup_connect();
+        Tag::upsert(&conn, "javascript", Some("#ffcc00"), Some("JS")).unwrap();
+        Tag::set_aliases(&conn, "javascript", &["js".to_owned()]).unwrap();
+
+        // Fields not passed are kept.
+        Tag::upsert(&conn, "javascript", None, Some("")).unwrap();
+        let tags = Tag::all(&conn).unwrap();
+        assert_eq!(1, tags.len());
+        assert_eq!(Some("#ffcc00"), tags[0].color.as_deref());
+        assert_eq!(None, tags[0].description);
+        assert_eq!(vec!["js"], tags[0].aliases);
+
+        assert_eq!("javascript", Tag::resolve(&conn, "js").unwrap());
+        assert_eq!("javascript/react", Tag::resolve(&conn, "js/react").unwrap());
+        assert_eq!("jsx", Tag::resolve(&conn, "jsx").unwrap());
+
+        Tag::rename(&conn, "javascript", "lang/js").unwrap();
+        assert_eq!("lang/js", Tag::resolve(&conn, "js").unwrap());
+        Tag::delete_all(&conn, "lang").unwrap();
+        assert!(Tag::all(&conn).unwrap().is_empty());
+        assert_eq!("js", Tag::resolve(&conn, "js").unwrap());
     }
 
     #[test]
@@ -541,6 +1826,252 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_setup_wal() {
+        let path = temp_db_path();
+        let conn = setup(Some(&path), false, None, None).unwrap();
+        let mode: String = conn
+            .query_row("PRAGMA journal_mode", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!("wal", mode);
+
+        let readers = open_readers(&conn, 2, None).unwrap();
+        assert_eq!(2, readers.len());
+        assert!(readers[0].execute("DELETE FROM memo", []).is_err());
+
+        drop(readers);
+        drop(conn);
+        remove_db(&path);
+    }
+
+    #[test]
+    fn test_open_readers_in_memory() {
+        let conn = setup(None, true, None, None).unwrap();
+        assert!(open_readers(&conn, 2, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_setup_passphrase() {
+        let path = temp_db_path();
+        {
+            let conn = setup(Some(&path), false, Some("secret"), None).unwrap();
+            Topic::create(&conn, "t1", "title1", 0).unwrap();
+
+            let readers = open_readers(&conn, 1, Some("secret")).unwrap();
+            assert_eq!(1, Topic::all(&readers[0]).unwrap().len());
+        }
+
+        let e = setup(Some(&path), false, None, None).err().unwrap();
+        assert_eq!(Some(ErrorCode::NotADatabase), e.sqlite_error_code());
+        let e = setup(Some(&path), false, Some("wrong"), None)
+            .err()
+            .unwrap();
+        assert_eq!(Some(ErrorCode::NotADatabase), e.sqlite_error_code());
+
+        let conn = setup(Some(&path), false, Some("secret"), None).unwrap();
+        assert_eq!(1, Topic::all(&conn).unwrap().len());
+
+        drop(conn);
+        remove_db(&path);
+    }
+
+    #[test]
+    fn test_export() {
+        let path = temp_db_path();
+        let encrypted = temp_db_path();
+        let decrypted = temp_db_path();
+        {
+            let conn = setup(Some(&path), false, None, None).unwrap();
+            Topic::create(&conn, "t1", "title1", 0).unwrap();
+        }
+
+        export(&path, None, &encrypted, Some("secret")).unwrap();
+        assert!(setup(Some(&encrypted), false, None, None).is_err());
+
+        export(&encrypted, Some("secret"), &decrypted, None).unwrap();
+        let conn = setup(Some(&decrypted), false, None, None).unwrap();
+        assert_eq!(1, Topic::all(&conn).unwrap().len());
+
+        drop(conn);
+        for p in [path, encrypted, decrypted] {
+            remove_db(&p);
+        }
+    }
+
+    #[test]
+    fn test_trash() {
+        let conn = setup_connect();
+        Topic::create(&conn, "t1", "title1", 0).unwrap();
+        TopicTag::create(&conn, "tag1", "t1").unwrap();
+        Attachment::create(&conn, "a1", "t1", "a.png", &[1]).unwrap();
+        let m1 = Memo::create(&conn, "m1", "t1", 0, "content1").unwrap();
+        let m2 = Memo::create(&conn, "m2", "t1", 1, "content2").unwrap();
+
+        m1.trash(&conn, 10).unwrap();
+        m2.trash(&conn, 11).unwrap();
+        TrashTopic::create(&conn, "t1", 11).unwrap();
+        assert!(Topic::all(&conn).unwrap().is_empty());
+        assert!(TopicTag::all_by_topic(&conn, "t1").unwrap().is_empty());
+        assert!(Attachment::all_by_topic(&conn, "t1").unwrap().is_empty());
+
+        let memos = TrashMemo::all(&conn).unwrap();
+        assert_eq!(
+            vec!["m2", "m1"],
+            memos.iter().map(|m| &m.id).collect::<Vec<_>>()
+        );
+        let topics = TrashTopic::all(&conn).unwrap();
+        assert_eq!(1, topics.len());
+        assert_eq!(("t1", 11), (topics[0].id.as_str(), topics[0].deleted_at));
+
+        memos[0].restore(&conn).unwrap();
+        topics[0].restore(&conn).unwrap();
+        assert_eq!(
+            "content2",
+            Memo::latest_by_topic(&conn, "t1").unwrap().content
+        );
+        assert_eq!("title1", Topic::all(&conn).unwrap()[0].title);
+        assert_eq!(vec!["tag1"], TopicTag::all_by_topic(&conn, "t1").unwrap());
+        assert_eq!(1, Attachment::all_by_topic(&conn, "t1").unwrap().len());
+        assert!(TrashTopic::all(&conn).unwrap().is_empty());
+
+        memos[1].purge(&conn).unwrap();
+        assert!(TrashMemo::all(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_trash_again() {
+        let conn = setup_connect();
+        Topic::create(&conn, "t1", "title1", 0).unwrap();
+        TopicTag::create(&conn, "tag1", "t1").unwrap();
+        Attachment::create(&conn, "a1", "t1", "a.png", &[1]).unwrap();
+        TrashTopic::create(&conn, "t1", 10).unwrap();
+
+        // Recreated topic has the tag and the attachment again, like restored partially.
+        Topic::create(&conn, "t1", "title1", 0).unwrap();
+        TopicTag::create(&conn, "tag1", "t1").unwrap();
+        Attachment::create(&conn, "a1", "t1", "a.png", &[1]).unwrap();
+        TrashTopic::create(&conn, "t1", 11).unwrap();
+
+        TrashTopic::all(&conn).unwrap()[0].restore(&conn).unwrap();
+        assert_eq!(vec!["tag1"], TopicTag::all_by_topic(&conn, "t1").unwrap());
+        assert_eq!(1, Attachment::all_by_topic(&conn, "t1").unwrap().len());
+    }
+
+    #[test]
+    fn test_orphans() {
+        let conn = setup_connect();
+        Memo::create(&conn, "m9", "t9", 0, "# title9").unwrap();
+        TopicTag::create(&conn, "tag9", "t9").unwrap();
+        TopicTag::create(&conn, "tag8", "t8").unwrap();
+        Topic::create(&conn, "t7", "title7", 0).unwrap();
+        TopicTag::create(&conn, "tag7", "t7").unwrap();
+
+        assert_eq!(vec!["t9"], Memo::orphan_topic_ids(&conn).unwrap());
+        assert_eq!(vec!["t8"], TopicTag::orphan_topic_ids(&conn).unwrap());
+        let ids: Vec<_> = Topic::all_empty(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(vec!["t7"], ids);
+
+        assert_eq!(1, TopicTag::delete_by_topic(&conn, "t8").unwrap());
+        assert!(TopicTag::orphan_topic_ids(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_integrity_check() {
+        let conn = setup_connect();
+        assert!(integrity_check(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sizes() {
+        let conn = setup_connect();
+        Memo::create(&conn, "m1", "t1", 0, "a").unwrap();
+        Memo::create(&conn, "m2", "t1", 1, "ab").unwrap();
+        Memo::create(&conn, "m3", "t2", 0, "abcd").unwrap();
+        Memo::create(&conn, "m4", "t3", 0, "").unwrap();
+
+        assert_eq!(4, Memo::count(&conn).unwrap());
+        let sizes = Memo::largest_topics(&conn, 2).unwrap();
+        assert_eq!(2, sizes.len());
+        assert_eq!(
+            ("t2", 1, 4),
+            (
+                sizes[0].topic_id.as_str(),
+                sizes[0].revisions,
+                sizes[0].size
+            )
+        );
+        assert_eq!(
+            ("t1", 2, 3),
+            (
+                sizes[1].topic_id.as_str(),
+                sizes[1].revisions,
+                sizes[1].size
+            )
+        );
+
+        let tables = table_sizes(&conn).unwrap();
+        assert!(tables.iter().any(|t| t.name == "memo" && t.size > 0));
+
+        conn.execute("DELETE FROM memo", []).unwrap();
+        vacuum(&conn).unwrap();
+        let (size, free) = file_size(&conn).unwrap();
+        assert!(size > 0);
+        assert_eq!(0, free);
+    }
+
+    #[test]
+    fn test_setup_auto_vacuum() {
+        let path = temp_db_path();
+        let mode = |conn: &Connection| -> i64 {
+            conn.pragma_query_value(None, "auto_vacuum", |r| r.get(0))
+                .unwrap()
+        };
+        {
+            let conn = setup(Some(&path), false, None, None).unwrap();
+            assert_eq!(0, mode(&conn));
+        }
+        {
+            let conn = setup(Some(&path), false, None, Some(AutoVacuum::Incremental)).unwrap();
+            assert_eq!(2, mode(&conn));
+        }
+
+        let conn = setup(Some(&path), false, None, None).unwrap();
+        assert_eq!(2, mode(&conn));
+
+        drop(conn);
+        remove_db(&path);
+    }
+
+    #[test]
+    fn test_backup_restore() {
+        let path = temp_db_path();
+        let dest = path.with_extension("bak");
+        let mut conn = setup(Some(&path), false, Some("secret"), None).unwrap();
+        Topic::create(&conn, "t1", "title1", 0).unwrap();
+
+        backup(&conn, &dest, Some("secret")).unwrap();
+        Topic::create(&conn, "t2", "title2", 0).unwrap();
+
+        restore(&mut conn, &dest, Some("secret")).unwrap();
+        let ids: Vec<_> = Topic::all(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(vec!["t1"], ids);
+
+        let e = restore(&mut conn, &dest, Some("wrong")).unwrap_err();
+        assert_eq!(Some(ErrorCode::NotADatabase), e.sqlite_error_code());
+
+        drop(conn);
+        remove_db(&path);
+        remove_db(&dest);
+    }
+
     #[test]
     fn test_create_table_if_not_exists() {
         let conn = Connection::open_in_memory().unwrap();
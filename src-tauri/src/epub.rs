@@ -0,0 +1,528 @@
+use crate::error::Error;
+use crate::html::{self, escape, Heading};
+use crate::markdown;
+use crate::obsidian::sanitize_file_name;
+use crate::{db, AppData};
+use chrono::Utc;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const CONTAINER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+const STYLE: &str = r#"
+body {
+  line-height: 1.6;
+}
+
+img {
+  max-width: 100%;
+}
+
+pre {
+  white-space: pre-wrap;
+}
+
+table {
+  border-collapse: collapse;
+}
+
+th,
+td {
+  padding: 3px 8px;
+  border: 1px solid #cccccc;
+}
+"#;
+
+const MAX_NAV_LEVEL: u8 = 3;
+
+struct Chapter {
+    file_name: String,
+    title: String,
+    headings: Vec<Heading>,
+}
+
+struct Image {
+    id: String,
+    file_name: String,
+    media_type: &'static str,
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Export topics into EPUB book of `language` like `en` or `ja`.
+pub fn export_epub_fn(
+    data: &AppData,
+    path: &Path,
+    keyword: &str,
+    language: &str,
+) -> Result<usize, Error> {
+    let db = data.db.reader();
+    let mut topics = if keyword.is_empty() {
+        db::Topic::all(&db)
+    } else {
+        db::Topic::search(&db, keyword)
+    }?;
+
+    // Locked topics are never exported.
+    let locked = db::TopicLock::all(&db)?;
+    topics.retain(|t| !locked.iter().any(|l| l.topic_id == t.id));
+
+    topics.sort_unstable_by_key(|t| t.timestamp);
+
+    let mut memos = vec![];
+    for topic in &topics {
+        if let Ok(memo) = db::Memo::latest_by_topic(&db, &topic.id) {
+            memos.push((topic, memo));
+        }
+    }
+
+    // Empty book is invalid, and would overwrite file for nothing.
+    if memos.is_empty() {
+        return Err(Error::NotFound(format!("topics to export: {keyword}")));
+    }
+
+    let chapter_names = memos
+        .iter()
+        .enumerate()
+        .map(|(i, (t, _))| (t.id.clone(), chapter_file_name(i)))
+        .collect::<HashMap<String, String>>();
+    // Anchors of links are resolved into ids given to headings of chapters.
+    let chapter_headings = memos
+        .iter()
+        .map(|(t, m)| (t.id.clone(), html::render(&m.content).1))
+        .collect::<HashMap<String, Vec<Heading>>>();
+
+    let mut zip = ZipWriter::new(File::create(path)?);
+
+    // `mimetype` must be the first entry and not be compressed.
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"application/epub+zip")?;
+
+    zip.start_file("META-INF/container.xml", deflated)?;
+    zip.write_all(CONTAINER.as_bytes())?;
+
+    zip.start_file("OEBPS/style.css", deflated)?;
+    zip.write_all(STYLE.trim_start().as_bytes())?;
+
+    let mut chapters = vec![];
+    let mut images = vec![];
+    for (topic, memo) in &memos {
+        let mut files = HashMap::new();
+        for attachment in db::Attachment::all_by_topic(&db, &topic.id)? {
+            let Some(media_type) = image_media_type(&attachment.name) else {
+                continue;
+            };
+
+            let image = Image {
+                id: format!("img-{}", images.len() + 1),
+                file_name: format!(
+                    "images/{}-{}",
+                    images.len() + 1,
+                    sanitize_file_name(&attachment.name)
+                ),
+                media_type,
+            };
+
+            zip.start_file(format!("OEBPS/{}", image.file_name), stored)?;
+            zip.write_all(&attachment.content)?;
+
+            files.insert(attachment.name, html::encode_url_path(&image.file_name));
+            images.push(image);
+        }
+
+        let content = resolve_links(
+            &memo.content,
+            &topics,
+            &chapter_names,
+            &chapter_headings,
+            &files,
+        );
+        let (body, headings) = html::render(&content);
+
+        let chapter = Chapter {
+            file_name: chapter_names[&topic.id].clone(),
+            title: if topic.title.is_empty() {
+                "Untitled".to_owned()
+            } else {
+                topic.title.clone()
+            },
+            headings,
+        };
+
+        zip.start_file(format!("OEBPS/{}", chapter.file_name), deflated)?;
+        zip.write_all(xhtml(&chapter.title, &body).as_bytes())?;
+
+        chapters.push(chapter);
+    }
+
+    let title = if keyword.is_empty() {
+        "Markdown MEMO".to_owned()
+    } else {
+        format!("Markdown MEMO - {keyword}")
+    };
+
+    zip.start_file("OEBPS/nav.xhtml", deflated)?;
+    zip.write_all(nav(&title, &chapters).as_bytes())?;
+
+    zip.start_file("OEBPS/content.opf", deflated)?;
+    zip.write_all(package(&title, language, &chapters, &images).as_bytes())?;
+
+    zip.finish()?;
+
+    Ok(chapters.len())
+}
+
+fn resolve_links(
+    content: &str,
+    topics: &[db::Topic],
+    chapter_names: &HashMap<String, String>,
+    chapter_headings: &HashMap<String, Vec<Heading>>,
+    files: &HashMap<String, String>,
+) -> String {
+    let content = markdown::replace_wiki_links(content, |link| {
+        let label = html::escape_markdown(link.label.as_ref().unwrap_or(&link.target));
+
+        if let Some(url) = files.get(&link.target) {
+            return Some(format!("![{label}]({url})"));
+        }
+
+        let Some(topic) = db::Topic::resolve(topics, &link.target) else {
+            return Some(label);
+        };
+        let Some(file_name) = chapter_names.get(&topic.id) else {
+            return Some(label);
+        };
+
+        // Heading of the same text as other one has suffixed id.
+        let anchor = link
+            .anchor
+            .as_ref()
+            .map(|a| {
+                let id = chapter_headings
+                    .get(&topic.id)
+                    .and_then(|hs| hs.iter().find(|h| h.text.trim() == a.trim()))
+                    .map(|h| h.id.clone())
+                    .unwrap_or_else(|| html::slug(a));
+                format!("#{id}")
+            })
+            .unwrap_or_default();
+        Some(format!("[{label}]({file_name}{anchor})"))
+    });
+
+    markdown::replace_image_links(&content, |url| {
+        files.get(&url.replace("%20", " ")).map(|u| u.to_owned())
+    })
+}
+
+// -----------------------------------------------------------------------------------------------
+
+fn package(title: &str, language: &str, chapters: &[Chapter], images: &[Image]) -> String {
+    let identifier = Uuid::new_v4();
+    let modified = Utc::now().format("%Y-%m-%dT%H:%M:%SZ");
+
+    let mut manifest = String::new();
+    manifest.push_str(
+        "    <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n",
+    );
+    manifest.push_str("    <item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>\n");
+
+    let mut spine = String::new();
+    for (i, chapter) in chapters.iter().enumerate() {
+        manifest.push_str(&format!(
+            "    <item id=\"chapter-{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+            i + 1,
+            chapter.file_name
+        ));
+        spine.push_str(&format!("    <itemref idref=\"chapter-{}\"/>\n", i + 1));
+    }
+
+    for image in images {
+        manifest.push_str(&format!(
+            "    <item id=\"{}\" href=\"{}\" media-type=\"{}\"/>\n",
+            image.id,
+            escape(&html::encode_url_path(&image.file_name)),
+            image.media_type
+        ));
+    }
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="uid">urn:uuid:{identifier}</dc:identifier>
+    <dc:title>{}</dc:title>
+    <dc:language>{}</dc:language>
+    <meta property="dcterms:modified">{modified}</meta>
+  </metadata>
+  <manifest>
+{manifest}  </manifest>
+  <spine>
+{spine}  </spine>
+</package>
+"#,
+        escape(title),
+        escape(language)
+    )
+}
+
+fn nav(title: &str, chapters: &[Chapter]) -> String {
+    let mut list = String::new();
+    for chapter in chapters {
+        list.push_str(&format!(
+            "<li><a href=\"{}\">{}</a>",
+            chapter.file_name,
+            escape(&chapter.title)
+        ));
+
+        let headings = chapter
+            .headings
+            .iter()
+            .enumerate()
+            .filter(|(i, h)| !(*i == 0 && h.text.starts_with(&chapter.title)))
+            .filter(|(_, h)| h.level <= MAX_NAV_LEVEL)
+            .map(|(_, h)| h)
+            .collect::<Vec<&Heading>>();
+        list.push_str(&heading_list(&chapter.file_name, &headings));
+
+        list.push_str("</li>\n");
+    }
+
+    let body = format!(
+        "<nav epub:type=\"toc\" id=\"toc\">\n<h1>{}</h1>\n<ol>\n{list}</ol>\n</nav>\n",
+        escape(title)
+    );
+    xhtml(title, &body)
+}
+
+fn heading_list(file_name: &str, headings: &[&Heading]) -> String {
+    if headings.is_empty() {
+        return "".to_owned();
+    }
+
+    // Nest headings lower than the first one.
+    let level = headings.iter().map(|h| h.level).min().unwrap();
+
+    let mut list = "\n<ol>\n".to_owned();
+    let mut i = 0;
+    while i < headings.len() {
+        let heading = headings[i];
+        let children = headings[i + 1..]
+            .iter()
+            .take_while(|h| h.level > level)
+            .copied()
+            .collect::<Vec<&Heading>>();
+
+        list.push_str(&format!(
+            "<li><a href=\"{file_name}#{}\">{}</a>{}</li>\n",
+            escape(&heading.id),
+            escape(&heading.text),
+            heading_list(file_name, &children)
+        ));
+
+        i += 1 + children.len();
+    }
+    list.push_str("</ol>\n");
+
+    list
+}
+
+fn xhtml(title: &str, body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head>
+<meta charset="utf-8"/>
+<title>{}</title>
+<link rel="stylesheet" type="text/css" href="style.css"/>
+</head>
+<body>
+{body}</body>
+</html>
+"#,
+        escape(title)
+    )
+}
+
+fn chapter_file_name(index: usize) -> String {
+    format!("chapter-{:03}.xhtml", index + 1)
+}
+
+fn image_media_type(name: &str) -> Option<&'static str> {
+    let (_, ext) = name.rsplit_once('.')?;
+    match ext.to_lowercase().as_str() {
+        "gif" => Some("image/gif"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "png" => Some("image/png"),
+        "svg" => Some("image/svg+xml"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::setup_connect;
+    use std::fs;
+    use std::io::Read;
+    use std::path::PathBuf;
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_export_epub_fn() {
+        let data = setup_appdate();
+        let path = temp_file();
+
+        let count = export_epub_fn(&data, &path, "", "en").unwrap();
+        assert_eq!(2, count);
+
+        let mut zip = ZipArchive::new(File::open(&path).unwrap()).unwrap();
+
+        let mimetype = zip.by_index(0).unwrap();
+        assert_eq!("mimetype", mimetype.name());
+        assert_eq!(CompressionMethod::Stored, mimetype.compression());
+        drop(mimetype);
+
+        let container = read(&mut zip, "META-INF/container.xml");
+        assert!(container.contains("OEBPS/content.opf"));
+
+        let opf = read(&mut zip, "OEBPS/content.opf");
+        assert!(opf.contains("<dc:title>Markdown MEMO</dc:title>"));
+        assert!(opf.contains("<dc:language>en</dc:language>"));
+        assert!(opf.contains("href=\"chapter-001.xhtml\""));
+        assert!(opf.contains("href=\"chapter-002.xhtml\""));
+        assert!(opf.contains("href=\"images/1-a%20b.png\" media-type=\"image/png\""));
+        assert!(!opf.contains("c.pdf"));
+        assert!(opf.contains("<itemref idref=\"chapter-1\"/>\n    <itemref idref=\"chapter-2\"/>"));
+
+        let nav = read(&mut zip, "OEBPS/nav.xhtml");
+        assert!(nav.contains("<li><a href=\"chapter-001.xhtml\">title1</a>\n<ol>\n<li><a href=\"chapter-001.xhtml#h2\">h2</a>\n<ol>\n<li><a href=\"chapter-001.xhtml#h3\">h3</a></li>\n</ol>\n</li>\n</ol>\n</li>"));
+        assert!(nav.contains("<li><a href=\"chapter-002.xhtml\">title2</a></li>"));
+
+        let c1 = read(&mut zip, "OEBPS/chapter-001.xhtml");
+        assert!(c1.contains("<a href=\"chapter-002.xhtml#x-y\">to t2</a>"));
+        assert!(c1.contains("<a href=\"chapter-002.xhtml\">&lt;b&gt;x&lt;/b&gt;</a>"));
+        assert!(c1.contains("<img src=\"images/1-a%20b.png\" alt=\"a b.png\" />"));
+        assert!(c1.contains("c.pdf"));
+
+        let c2 = read(&mut zip, "OEBPS/chapter-002.xhtml");
+        assert!(c2.contains("<a href=\"chapter-001.xhtml\">title1</a>"));
+
+        let mut png = vec![];
+        zip.by_name("OEBPS/images/1-a b.png")
+            .unwrap()
+            .read_to_end(&mut png)
+            .unwrap();
+        assert_eq!(b"png".to_vec(), png);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_export_epub_fn_keyword() {
+        let data = setup_appdate();
+        let path = temp_file();
+
+        let count = export_epub_fn(&data, &path, "#tag1", "ja").unwrap();
+        assert_eq!(1, count);
+
+        let mut zip = ZipArchive::new(File::open(&path).unwrap()).unwrap();
+
+        let opf = read(&mut zip, "OEBPS/content.opf");
+        assert!(opf.contains("<dc:title>Markdown MEMO - #tag1</dc:title>"));
+        assert!(opf.contains("<dc:language>ja</dc:language>"));
+
+        let c1 = read(&mut zip, "OEBPS/chapter-001.xhtml");
+        assert!(!c1.contains("chapter-002.xhtml"));
+        assert!(c1.contains("to t2"));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_export_epub_fn_empty() {
+        let data = setup_appdate();
+        let path = temp_file();
+
+        let e = export_epub_fn(&data, &path, "#nothing", "en").err();
+        assert!(matches!(e, Some(Error::NotFound(_))));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_export_epub_fn_repeated_heading() {
+        let conn = setup_connect();
+        db::Topic::create(&conn, "t1", "title1", 0).unwrap();
+        db::Topic::create(&conn, "t2", "title2", 1).unwrap();
+        db::Memo::create(&conn, "m1", "t1", 0, "# title1\n## Intro!\n## Intro\n").unwrap();
+        db::Memo::create(&conn, "m2", "t2", 1, "[[t1#Intro]]\n").unwrap();
+        let data = AppData::new(conn);
+        let path = temp_file();
+
+        export_epub_fn(&data, &path, "", "en").unwrap();
+        let mut zip = ZipArchive::new(File::open(&path).unwrap()).unwrap();
+
+        let nav = read(&mut zip, "OEBPS/nav.xhtml");
+        assert!(nav.contains("<a href=\"chapter-001.xhtml#intro-1\">Intro</a>"));
+        let c2 = read(&mut zip, "OEBPS/chapter-002.xhtml");
+        assert!(c2.contains("<a href=\"chapter-001.xhtml#intro-1\">"));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_image_media_type() {
+        assert_eq!(Some("image/jpeg"), image_media_type("a.JPG"));
+        assert_eq!(None, image_media_type("a.pdf"));
+        assert_eq!(None, image_media_type("a"));
+    }
+
+    fn read(zip: &mut ZipArchive<File>, name: &str) -> String {
+        let mut content = String::new();
+        zip.by_name(name)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        content
+    }
+
+    fn temp_file() -> PathBuf {
+        std::env::temp_dir().join(format!("{}.epub", Uuid::new_v4()))
+    }
+
+    fn setup_appdate() -> AppData {
+        let conn = setup_connect();
+        db::Topic::create(&conn, "t1", "title1", 0).unwrap();
+        db::Topic::create(&conn, "t2", "title2", 1).unwrap();
+        db::Memo::create(
+            &conn,
+            "m1",
+            "t1",
+            0,
+            "# title1\n## h2\n### h3\n#### h4\n[[t2#X Y|to t2]] ![[a b.png]] ![[c.pdf]] [[t2|<b>x</b>]]\n",
+        )
+        .unwrap();
+        db::Memo::create(&conn, "m2", "t2", 1, "[[title1]]\n").unwrap();
+        db::TopicTag::create(&conn, "tag1", "t1").unwrap();
+        db::Attachment::create(&conn, "a1", "t1", "a b.png", b"png").unwrap();
+        db::Attachment::create(&conn, "a2", "t1", "c.pdf", b"pdf").unwrap();
+
+        AppData::new(conn)
+    }
+}
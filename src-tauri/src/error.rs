@@ -1,11 +1,103 @@
 use rusqlite;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::fmt;
+use std::io;
 use std::sync::PoisonError;
+use zip::result::ZipError;
 
 #[derive(Debug)]
 pub enum Error {
+    Cancelled,
     Database(rusqlite::Error),
+    Internal(String),
+    /// Invalid input by user, e.g. tag name.
+    Invalid(String),
+    Io(io::Error),
     LockError(String),
     NotFound(String),
+    Passphrase,
+    Context(String, Box<Error>),
+}
+
+impl Error {
+    /// Stable identifier of error kind for client.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Cancelled => "cancelled",
+            Error::Database(_) => "database",
+            Error::Internal(_) => "internal",
+            Error::Invalid(_) => "invalid",
+            Error::Io(_) => "io",
+            Error::LockError(_) => "lock",
+            Error::NotFound(_) => "not_found",
+            Error::Passphrase => "passphrase",
+            Error::Context(_, e) => e.code(),
+        }
+    }
+
+    /// Error opening database, which is unreadable by wrong passphrase.
+    pub fn from_open(error: rusqlite::Error) -> Self {
+        match error.sqlite_error_code() {
+            Some(rusqlite::ErrorCode::NotADatabase) => Error::Passphrase,
+            _ => Error::Database(error),
+        }
+    }
+
+    pub fn context(self, context: &str) -> Self {
+        Error::Context(context.to_owned(), Box::new(self))
+    }
+
+    /// Error without context.
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Context(_, e) => e.root(),
+            e => e,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Cancelled => write!(f, "cancelled"),
+            Error::Database(e) => write!(f, "database error: {e}"),
+            Error::Internal(e) => write!(f, "internal error: {e}"),
+            Error::Invalid(e) => write!(f, "invalid: {e}"),
+            Error::Io(e) => write!(f, "io error: {e}"),
+            Error::LockError(e) => write!(f, "lock error: {e}"),
+            Error::NotFound(e) => write!(f, "not found: {e}"),
+            Error::Passphrase => write!(f, "invalid passphrase"),
+            Error::Context(c, e) => write!(f, "{c}: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Database(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Context(_, e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut context = vec![];
+        let mut error = self;
+        while let Error::Context(c, e) = error {
+            context.push(c.as_str());
+            error = e;
+        }
+
+        let mut s = serializer.serialize_struct("Error", 3)?;
+        s.serialize_field("code", error.code())?;
+        s.serialize_field("message", &error.to_string())?;
+        s.serialize_field("context", &context)?;
+        s.end()
+    }
 }
 
 impl<T> From<PoisonError<T>> for Error {
@@ -19,3 +111,55 @@ impl From<rusqlite::Error> for Error {
         Error::Database(error)
     }
 }
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<ZipError> for Error {
+    fn from(error: ZipError) -> Self {
+        Error::Io(error.into())
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let e = Error::NotFound("t1".to_owned()).context("get_memo");
+        assert_eq!("get_memo: not found: t1", e.to_string());
+    }
+
+    #[test]
+    fn test_code() {
+        assert_eq!("lock", Error::LockError("".to_owned()).code());
+        assert_eq!("invalid", Error::Invalid("".to_owned()).code());
+        let e = Error::NotFound("t1".to_owned()).context("a").context("b");
+        assert_eq!("not_found", e.code());
+        assert!(matches!(e.root(), Error::NotFound(_)));
+    }
+
+    #[test]
+    fn test_source() {
+        let e = Error::Database(rusqlite::Error::InvalidQuery).context("a");
+        let source = std::error::Error::source(&e).unwrap();
+        assert!(std::error::Error::source(source).is_some());
+    }
+
+    #[test]
+    fn test_serialize() {
+        let e = Error::NotFound("t1".to_owned())
+            .context("topic_id: t1")
+            .context("get_memo");
+        assert_eq!(
+            r#"{"code":"not_found","message":"not found: t1","context":["get_memo","topic_id: t1"]}"#,
+            serde_json::to_string(&e).unwrap()
+        );
+    }
+}
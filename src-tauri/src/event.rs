@@ -0,0 +1,353 @@
+use crate::error::Error;
+use crate::{db, AppData};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Recorded events older than this are pruned while watching.
+const RETENTION_SECS: i64 = 24 * 60 * 60;
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Change notified to frontend, emitted by `name` with this as payload.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Event {
+    TopicCreated {
+        topic_id: String,
+        title: String,
+    },
+    MemoSaved {
+        topic_id: String,
+        memo_id: String,
+    },
+    /// Revision deleted while topic remains.
+    MemoDeleted {
+        topic_id: String,
+        memo_id: String,
+    },
+    TagAdded {
+        topic_id: String,
+        tag: String,
+    },
+    TagRemoved {
+        topic_id: String,
+        tag: String,
+    },
+    TopicDeleted {
+        topic_id: String,
+    },
+    /// Changed by other process without recording event, e.g. sync tool.
+    DatabaseChanged,
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::TopicCreated { .. } => "topic-created",
+            Event::MemoSaved { .. } => "memo-saved",
+            Event::MemoDeleted { .. } => "memo-deleted",
+            Event::TagAdded { .. } => "tag-added",
+            Event::TagRemoved { .. } => "tag-removed",
+            Event::TopicDeleted { .. } => "topic-deleted",
+            Event::DatabaseChanged => "database-changed",
+        }
+    }
+
+    /// Record event in database, so it is delivered only when transaction of the change is
+    /// committed, and also when the change is made by other process like CLI.
+    pub fn record(&self, conn: &Connection) -> Result<(), Error> {
+        let payload = serde_json::to_string(self).map_err(io::Error::from)?;
+        db::Event::create(conn, &payload, now())?;
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Where delivery of events has reached.
+struct Cursor {
+    seq: i64,
+    /// Read-only connection of watcher, whose data version is changed by commits of the others.
+    /// `None` for in-memory database, which is not written by other process.
+    conn: Option<Connection>,
+    version: i64,
+}
+
+impl Cursor {
+    fn new(data: &AppData) -> Result<Self, Error> {
+        let conn = {
+            let db = data.db.writer();
+            db::open_readers(&db, 1, data.passphrase.as_deref())?.pop()
+        };
+        let mut cursor = Cursor {
+            seq: db::Event::last_seq(&data.db.reader())?,
+            conn,
+            version: 0,
+        };
+        cursor.version = cursor.data_version()?;
+        Ok(cursor)
+    }
+
+    fn data_version(&self) -> Result<i64, Error> {
+        match &self.conn {
+            Some(conn) => Ok(db::data_version(conn)?),
+            None => Ok(self.version),
+        }
+    }
+}
+
+/// Events recorded after `seq`, with sequence number of the last one.
+pub fn poll(data: &AppData, seq: i64) -> Result<(i64, Vec<Event>), Error> {
+    read_since(&data.db.reader(), seq)
+}
+
+fn read_since(db: &Connection, seq: i64) -> Result<(i64, Vec<Event>), Error> {
+    let rows = db::Event::all_since(db, seq)?;
+
+    let mut last = seq;
+    let mut events = vec![];
+    for row in rows {
+        last = row.seq;
+        match serde_json::from_str(&row.payload) {
+            Ok(e) => events.push(e),
+            Err(e) => log::warn!("skip unknown event seq={} error={e}", row.seq),
+        }
+    }
+
+    Ok((last, events))
+}
+
+/// Deliver events recorded from now on, and external changes, to `emit` on background thread.
+pub fn watch<F>(data: AppData, interval: Duration, emit: F) -> Result<(), Error>
+where
+    F: Fn(Event) + Send + 'static,
+{
+    prune(&data)?;
+    let mut cursor = Cursor::new(&data)?;
+    let mut pruned_at = Instant::now();
+
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+
+        if pruned_at.elapsed() >= PRUNE_INTERVAL {
+            pruned_at = Instant::now();
+            // Deleted by this process, not to be notified as external change.
+            match prune(&data).and_then(|_| cursor.data_version()) {
+                Ok(version) => cursor.version = version,
+                Err(e) => log::warn!("prune events error={e}"),
+            }
+        }
+
+        match changes(&data, &mut cursor) {
+            Ok(events) => {
+                for e in events {
+                    log::debug!("emit event name={}", e.name());
+                    emit(e);
+                }
+            }
+            Err(e) => log::warn!("poll events error={e}"),
+        }
+    });
+
+    Ok(())
+}
+
+/// Events since last call, and `Event::DatabaseChanged` for changes without events.
+fn changes(data: &AppData, cursor: &mut Cursor) -> Result<Vec<Event>, Error> {
+    let (seq, mut events, last, version) = match &cursor.conn {
+        // Read in one snapshot, not to take change committed in between as one without events.
+        Some(conn) => {
+            let tx = conn.unchecked_transaction()?;
+            let (seq, events) = read_since(&tx, cursor.seq)?;
+            let last = db::Event::last_seq(&tx)?;
+            (seq, events, last, db::data_version(&tx)?)
+        }
+        None => {
+            let db = data.db.reader();
+            let (seq, events) = read_since(&db, cursor.seq)?;
+            (seq, events, db::Event::last_seq(&db)?, cursor.version)
+        }
+    };
+    let recorded = seq != cursor.seq;
+    cursor.seq = seq;
+
+    // Sequence goes back when database is restored from backup.
+    if last < cursor.seq {
+        log::info!("detect restored database seq={last}");
+        cursor.seq = last;
+        events.push(Event::DatabaseChanged);
+    }
+
+    // Change with recorded events, e.g. by CLI, is notified by them.
+    if version != cursor.version {
+        cursor.version = version;
+        if !recorded && !events.contains(&Event::DatabaseChanged) {
+            log::info!("detect external change data_version={version}");
+            events.push(Event::DatabaseChanged);
+        }
+    }
+
+    Ok(events)
+}
+
+fn prune(data: &AppData) -> Result<(), Error> {
+    let pruned = db::Event::delete_before(&data.db.writer(), now() - RETENTION_SECS)?;
+    log::debug!("pruned events count={pruned}");
+    Ok(())
+}
+
+fn now() -> i64 {
+    let duration = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    duration.as_secs() as i64
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{create_memo_fn, delete_topic_fn};
+    use crate::testing::{remove_db, setup_appdate, temp_db_path};
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_serialize() {
+        let e = Event::MemoSaved {
+            topic_id: "t1".to_owned(),
+            memo_id: "m1".to_owned(),
+        };
+        assert_eq!(
+            r#"{"kind":"memo-saved","topic_id":"t1","memo_id":"m1"}"#,
+            serde_json::to_string(&e).unwrap()
+        );
+        assert_eq!("memo-saved", e.name());
+    }
+
+    #[test]
+    fn test_poll() {
+        let data = setup_appdate();
+        {
+            let conn = data.db.writer();
+            conn.execute(
+                "INSERT INTO event (payload, timestamp) VALUES ('{\"kind\":\"unknown\"}', 0)",
+                [],
+            )
+            .unwrap();
+            Event::TopicDeleted {
+                topic_id: "t1".to_owned(),
+            }
+            .record(&conn)
+            .unwrap();
+        }
+
+        let (seq, events) = poll(&data, 0).unwrap();
+        assert_eq!(2, seq);
+        assert_eq!(
+            vec![Event::TopicDeleted {
+                topic_id: "t1".to_owned()
+            }],
+            events
+        );
+
+        let (seq, events) = poll(&data, seq).unwrap();
+        assert_eq!(2, seq);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_watch() {
+        let data = setup_appdate();
+        create_memo_fn(&data, "t0", "# before watch").unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        watch(data.clone(), Duration::from_millis(10), move |e| {
+            let _ = tx.send(e);
+        })
+        .unwrap();
+
+        create_memo_fn(&data, "t1", "# title1").unwrap();
+        delete_topic_fn(&data, "t1").unwrap();
+
+        let mut names = vec![];
+        for _ in 0..3 {
+            let e = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+            names.push(e.name());
+        }
+        assert_eq!(vec!["topic-created", "memo-saved", "topic-deleted"], names);
+    }
+
+    #[test]
+    fn test_changes_external() {
+        let path = temp_db_path();
+        let data = AppData::open(Some(&path), false, None, None).unwrap();
+
+        let mut cursor = Cursor::new(&data).unwrap();
+
+        create_memo_fn(&data, "t1", "# title1").unwrap();
+        let names: Vec<_> = changes(&data, &mut cursor)
+            .unwrap()
+            .iter()
+            .map(|e| e.name())
+            .collect();
+        assert_eq!(vec!["topic-created", "memo-saved"], names);
+
+        // Other process writing without events.
+        let other = Connection::open(&path).unwrap();
+        db::Topic::create(&other, "t2", "title2", 0).unwrap();
+        assert_eq!(
+            vec![Event::DatabaseChanged],
+            changes(&data, &mut cursor).unwrap()
+        );
+        assert!(changes(&data, &mut cursor).unwrap().is_empty());
+
+        // Other process writing with events is notified only by them.
+        let other = AppData::new(other);
+        create_memo_fn(&other, "t2", "# title2").unwrap();
+        let names: Vec<_> = changes(&data, &mut cursor)
+            .unwrap()
+            .iter()
+            .map(|e| e.name())
+            .collect();
+        assert_eq!(vec!["memo-saved"], names);
+        assert!(changes(&data, &mut cursor).unwrap().is_empty());
+
+        drop(other);
+        drop(data);
+        remove_db(&path);
+    }
+
+    #[test]
+    fn test_changes_restored() {
+        let data = setup_appdate();
+        create_memo_fn(&data, "t1", "# title1").unwrap();
+
+        let mut cursor = Cursor::new(&data).unwrap();
+        assert!(cursor.conn.is_none());
+        data.db.writer().execute("DELETE FROM event", []).unwrap();
+
+        assert_eq!(
+            vec![Event::DatabaseChanged],
+            changes(&data, &mut cursor).unwrap()
+        );
+        assert_eq!(0, cursor.seq);
+    }
+
+    #[test]
+    fn test_prune() {
+        let data = setup_appdate();
+        {
+            let conn = data.db.writer();
+            db::Event::create(&conn, "{}", 0).unwrap();
+            db::Event::create(&conn, "{}", 0).unwrap();
+        }
+        create_memo_fn(&data, "t1", "# title1").unwrap();
+
+        prune(&data).unwrap();
+        let (_, events) = poll(&data, 0).unwrap();
+        assert_eq!(2, events.len());
+    }
+}
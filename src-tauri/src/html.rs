@@ -0,0 +1,214 @@
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag, TagEnd};
+use std::collections::HashSet;
+
+// -----------------------------------------------------------------------------------------------
+
+pub struct Heading {
+    pub level: u8,
+    pub id: String,
+    pub text: String,
+}
+
+/// Render markdown to HTML that is also well-formed XHTML.
+///
+/// Raw HTML in content is escaped, and an id is given to each heading.
+pub fn render(content: &str) -> (String, Vec<Heading>) {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS;
+
+    let mut headings = vec![];
+    let mut used = HashSet::new();
+
+    let mut events = vec![];
+    let mut heading: Option<(usize, String)> = None;
+    for event in Parser::new_ext(content, options) {
+        match event {
+            Event::Start(Tag::Heading { .. }) => {
+                heading = Some((events.len(), String::new()));
+                events.push(event);
+            }
+            Event::End(TagEnd::Heading(level)) => {
+                if let Some((index, text)) = heading.take() {
+                    let id = unique_slug(&text, &mut used);
+                    if let Event::Start(Tag::Heading { id: i, .. }) = &mut events[index] {
+                        *i = Some(CowStr::from(id.clone()));
+                    }
+
+                    headings.push(Heading {
+                        level: level as u8,
+                        id,
+                        text,
+                    });
+                }
+                events.push(event);
+            }
+            Event::Text(t) | Event::Code(t) if heading.is_some() => {
+                heading.as_mut().unwrap().1.push_str(&t);
+                events.push(Event::Text(t));
+            }
+            Event::Html(t) | Event::InlineHtml(t) => {
+                events.push(Event::Text(t));
+            }
+            _ => events.push(event),
+        }
+    }
+
+    let mut body = String::new();
+    html::push_html(&mut body, events.into_iter());
+
+    (body, headings)
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Escape text put into markdown, e.g. link label, to render as it is.
+///
+/// HTML is escaped like `escape`, and markdown syntax is escaped by backslash.
+pub fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in escape(text).chars() {
+        if "\\[]()*_`!~|".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Percent-encode text except unreserved characters.
+pub fn encode_url(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for b in text.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{b:02X}"));
+        }
+    }
+    encoded
+}
+
+/// Percent-encode each segment of relative path.
+pub fn encode_url_path(path: &str) -> String {
+    path.split('/')
+        .map(encode_url)
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+/// Make an id from heading text in the same way as `render`.
+pub fn slug(text: &str) -> String {
+    let slug = text
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| {
+            if c.is_alphanumeric() || c == '_' || c == '-' {
+                Some(c)
+            } else if c.is_whitespace() {
+                Some('-')
+            } else {
+                None
+            }
+        })
+        .collect::<String>();
+
+    if slug.is_empty() {
+        "section".to_owned()
+    } else {
+        slug
+    }
+}
+
+fn unique_slug(text: &str, used: &mut HashSet<String>) -> String {
+    let base = slug(text);
+    let mut id = base.clone();
+    let mut n = 1;
+    while used.contains(&id) {
+        id = format!("{base}-{n}");
+        n += 1;
+    }
+
+    used.insert(id.clone());
+    id
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let (body, headings) = render("# Title\n\ntext\n");
+        assert_eq!("<h1 id=\"title\">Title</h1>\n<p>text</p>\n", body);
+        assert_eq!(1, headings.len());
+        assert_eq!(1, headings[0].level);
+        assert_eq!("title", headings[0].id);
+        assert_eq!("Title", headings[0].text);
+    }
+
+    #[test]
+    fn test_render_headings() {
+        let (_, headings) = render("# A `b`\n## A b\n### \n");
+        assert_eq!(3, headings.len());
+        assert_eq!("a-b", headings[0].id);
+        assert_eq!("A b", headings[0].text);
+        assert_eq!("a-b-1", headings[1].id);
+        assert_eq!(2, headings[1].level);
+        assert_eq!("section", headings[2].id);
+    }
+
+    #[test]
+    fn test_render_escape_html() {
+        let (body, _) = render("<script>x</script>\n\na <b>c</b>\n");
+        assert!(!body.contains("<script>"));
+        assert!(!body.contains("<b>"));
+        assert!(body.contains("&lt;b&gt;"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            "&lt;a href=&quot;&amp;&#39;&quot;&gt;",
+            escape("<a href=\"&'\">")
+        );
+    }
+
+    #[test]
+    fn test_escape_markdown() {
+        assert_eq!("a\\]\\(x\\) &lt;b&gt;", escape_markdown("a](x) <b>"));
+
+        let (body, _) = render(&format!("[{}](u)", escape_markdown("<i>*x*</i> [y]")));
+        assert_eq!(
+            "<p><a href=\"u\">&lt;i&gt;*x*&lt;/i&gt; [y]</a></p>\n",
+            body
+        );
+    }
+
+    #[test]
+    fn test_encode_url() {
+        assert_eq!("a%20b%2F%E3%81%82.png", encode_url("a b/あ.png"));
+    }
+
+    #[test]
+    fn test_encode_url_path() {
+        assert_eq!("a/b%20c.png", encode_url_path("a/b c.png"));
+    }
+
+    #[test]
+    fn test_slug() {
+        assert_eq!("hello-world", slug(" Hello World! "));
+        assert_eq!("日本語", slug("日本語"));
+        assert_eq!("section", slug("!!"));
+    }
+}
@@ -0,0 +1,556 @@
+use crate::api::{
+    add_memo_tag_fn, append_memo_fn, complete_tag_fn, create_memo_fn, delete_memo_fn,
+    delete_topic_fn, find_topic_fn, get_all_tags_fn, get_memo_all_fn, get_memo_fn, get_memo_tag_fn,
+    get_topics_fn, remove_memo_tag_fn,
+};
+use crate::error::Error;
+use crate::{db, logger, AppData};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::thread;
+use std::time::Instant;
+use tiny_http::{Header, Method, Request, Response, Server};
+use uuid::Uuid;
+
+/// Token shorter than this is refused, as it is easily guessed.
+const MIN_TOKEN_LEN: usize = 16;
+
+/// Bytes of request body accepted at most.
+const MAX_BODY: usize = 1024 * 1024;
+
+#[derive(Deserialize)]
+struct MemoBody {
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct AppendBody {
+    text: String,
+    #[serde(default)]
+    heading: bool,
+}
+
+#[derive(Deserialize)]
+struct TagBody {
+    tag: String,
+}
+
+#[derive(Serialize)]
+struct Remains {
+    remains: usize,
+}
+
+#[derive(Serialize)]
+struct Message<'a> {
+    error: &'a str,
+}
+
+struct HttpError {
+    status: u16,
+    message: String,
+}
+
+impl From<Error> for HttpError {
+    fn from(error: Error) -> Self {
+        let status = match error.root() {
+            Error::Cancelled => 409,
+            Error::Invalid(_) => 400,
+            Error::NotFound(_) => 404,
+            // Locked topic is readable only after unlocked in GUI.
+            Error::Passphrase => 423,
+            _ => {
+                // Detail of internal error is not exposed to client.
+                log::error!("http error={}", logger::error_chain(&error));
+                return HttpError::new(500, "internal error");
+            }
+        };
+        HttpError::new(status, &error.to_string())
+    }
+}
+
+impl HttpError {
+    fn new(status: u16, message: &str) -> Self {
+        HttpError {
+            status,
+            message: message.to_owned(),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Start API server listening on loopback interface in background.
+///
+/// Every request must have header `Authorization: Bearer <token>`.
+pub fn start(data: AppData, port: u16, token: &str) -> Result<SocketAddr, Error> {
+    let token = token.trim();
+    if token.len() < MIN_TOKEN_LEN {
+        return Err(Error::Internal(format!(
+            "http token must be {MIN_TOKEN_LEN} or more characters"
+        )));
+    }
+
+    let server = Server::http(("127.0.0.1", port)).map_err(io::Error::other)?;
+    let addr = server
+        .server_addr()
+        .to_ip()
+        .ok_or_else(|| io::Error::other("not ip address"))?;
+
+    let token = token.to_owned();
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            handle(&data, &token, request);
+        }
+    });
+
+    Ok(addr)
+}
+
+/// Read token from data directory, or create it at first time.
+pub fn load_or_create_token() -> Result<String, Error> {
+    let path = db::data_dir().join("http-token");
+    if path.exists() {
+        let token = fs::read_to_string(&path)?.trim().to_owned();
+        if token.len() >= MIN_TOKEN_LEN {
+            return Ok(token);
+        }
+        // Replace token emptied or shortened by hand.
+        log::warn!("recreate http token path={}", path.display());
+        fs::remove_file(&path)?;
+    }
+
+    let token = Uuid::new_v4().simple().to_string();
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(&path)?.write_all(token.as_bytes())?;
+
+    Ok(token)
+}
+
+fn handle(data: &AppData, token: &str, mut request: Request) {
+    let start = Instant::now();
+
+    let result = if !authorized(&request, token) {
+        Err(HttpError::new(401, "unauthorized"))
+    } else if request.body_length().is_some_and(|l| l > MAX_BODY) {
+        Err(HttpError::new(413, "body too large"))
+    } else {
+        let mut body = String::new();
+        let mut reader = request.as_reader().take(MAX_BODY as u64 + 1);
+        match reader.read_to_string(&mut body) {
+            Ok(l) if l > MAX_BODY => Err(HttpError::new(413, "body too large")),
+            Ok(_) => route(data, request.method(), request.url(), &body),
+            Err(_) => Err(HttpError::new(400, "invalid body")),
+        }
+    };
+
+    if let Err(e) = &result {
+        log::warn!(
+            "http method={} url={} status={} error={}",
+            request.method(),
+            request.url(),
+            e.status,
+            e.message
+        );
+    }
+
+    let (status, body) = match result {
+        Ok(Some(json)) => (200, json),
+        Ok(None) => (204, "".to_owned()),
+        Err(e) => {
+            let message = Message { error: &e.message };
+            (e.status, serde_json::to_string(&message).unwrap())
+        }
+    };
+
+    let mut response = Response::from_string(body).with_status_code(status);
+    if status != 204 {
+        let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+        response.add_header(header);
+    }
+
+    log::info!(
+        "http method={} url={} status={status} duration_ms={}",
+        request.method(),
+        request.url(),
+        start.elapsed().as_millis()
+    );
+
+    let _ = request.respond(response);
+}
+
+fn authorized(request: &Request, token: &str) -> bool {
+    request
+        .headers()
+        .iter()
+        .filter(|h| h.field.equiv("Authorization"))
+        .filter_map(|h| h.value.as_str().strip_prefix("Bearer "))
+        .any(|t| constant_time_eq(t.trim().as_bytes(), token.as_bytes()))
+}
+
+fn route(
+    data: &AppData,
+    method: &Method,
+    url: &str,
+    body: &str,
+) -> Result<Option<String>, HttpError> {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let segments = path
+        .trim_matches('/')
+        .split('/')
+        .map(decode)
+        .collect::<Vec<String>>();
+    let segments = segments.iter().map(String::as_str).collect::<Vec<&str>>();
+
+    match (method, segments.as_slice()) {
+        (Method::Get, ["topics"]) => {
+            let keyword = query_param(query, "keyword").unwrap_or_default();
+            json(&get_topics_fn(data, &keyword)?)
+        }
+        (Method::Get, ["topics", topic]) => json(&find_topic_fn(data, topic)?),
+        (Method::Delete, ["topics", topic_id]) => {
+            let remains = delete_topic_fn(data, topic_id)?;
+            json(&Remains { remains })
+        }
+        (Method::Get, ["topics", topic_id, "memo"]) => {
+            let id = query_param(query, "id");
+            json(&get_memo_fn(data, topic_id, id.as_deref())?)
+        }
+        (Method::Get, ["topics", topic_id, "memos"]) => json(&get_memo_all_fn(data, topic_id)?),
+        (Method::Post, ["topics", topic_id, "memos"]) => {
+            let body: MemoBody = parse(body)?;
+            json(&create_memo_fn(data, topic_id, &body.content)?)
+        }
+        (Method::Delete, ["topics", topic_id, "memos", id]) => {
+            let remains = delete_memo_fn(data, topic_id, Some(id))?;
+            json(&Remains { remains })
+        }
+        (Method::Post, ["topics", topic, "append"]) => {
+            let body: AppendBody = parse(body)?;
+            let heading = body
+                .heading
+                .then(|| Local::now().format("%Y-%m-%d %H:%M").to_string());
+            json(&append_memo_fn(
+                data,
+                topic,
+                &body.text,
+                heading.as_deref(),
+            )?)
+        }
+        (Method::Get, ["topics", topic_id, "tags"]) => {
+            let tags: Vec<_> = get_memo_tag_fn(data, topic_id)?
+                .into_iter()
+                .map(|t| t.name)
+                .collect();
+            json(&tags)
+        }
+        (Method::Post, ["topics", topic_id, "tags"]) => {
+            let body: TagBody = parse(body)?;
+            add_memo_tag_fn(data, topic_id, &body.tag)?;
+            Ok(None)
+        }
+        (Method::Delete, ["topics", topic_id, "tags", tag]) => {
+            remove_memo_tag_fn(data, topic_id, tag)?;
+            Ok(None)
+        }
+        (Method::Get, ["tags"]) => json(&get_all_tags_fn(data)?),
+        (Method::Get, ["tags", "complete"]) => {
+            let input = query_param(query, "input").unwrap_or_default();
+            json(&complete_tag_fn(data, &input)?)
+        }
+        _ => Err(HttpError::new(404, "no route")),
+    }
+}
+
+fn json<T: Serialize + ?Sized>(value: &T) -> Result<Option<String>, HttpError> {
+    let json = serde_json::to_string(value).map_err(|e| Error::from(io::Error::from(e)))?;
+    Ok(Some(json))
+}
+
+fn parse<'a, T: Deserialize<'a>>(body: &'a str) -> Result<T, HttpError> {
+    serde_json::from_str(body).map_err(|e| HttpError::new(400, &e.to_string()))
+}
+
+fn query_param(query: &str, name: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|p| p.split_once('='))
+        .find(|(k, _)| *k == name)
+        .map(|(_, v)| decode(&v.replace('+', " ")))
+}
+
+fn decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::setup_connect;
+    use std::io::Read;
+    use std::net::TcpStream;
+
+    const TOKEN: &str = "secret-token-for-test";
+
+    #[test]
+    fn test_unauthorized() {
+        let addr = start(setup_appdate(), 0, TOKEN).unwrap();
+
+        let (status, _) = request(addr, "GET", "/topics", None, "");
+        assert_eq!(401, status);
+
+        let (status, _) = request(addr, "GET", "/topics", Some("wrong"), "");
+        assert_eq!(401, status);
+    }
+
+    #[test]
+    fn test_get_topics() {
+        let addr = start(setup_appdate(), 0, TOKEN).unwrap();
+
+        let (status, body) = request(addr, "GET", "/topics", Some(TOKEN), "");
+        assert_eq!(200, status);
+        let topics: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(2, topics.as_array().unwrap().len());
+
+        let (status, body) = request(addr, "GET", "/topics?keyword=%23tag1", Some(TOKEN), "");
+        assert_eq!(200, status);
+        let topics: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(1, topics.as_array().unwrap().len());
+        assert_eq!("t1", topics[0]["id"]);
+    }
+
+    #[test]
+    fn test_get_topic() {
+        let addr = start(setup_appdate(), 0, TOKEN).unwrap();
+
+        let (status, body) = request(addr, "GET", "/topics/title%202", Some(TOKEN), "");
+        assert_eq!(200, status);
+        let topic: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!("t2", topic["id"]);
+
+        let (status, body) = request(addr, "GET", "/topics/t3", Some(TOKEN), "");
+        assert_eq!(404, status);
+        assert!(body.contains("error"));
+    }
+
+    #[test]
+    fn test_memo() {
+        let addr = start(setup_appdate(), 0, TOKEN).unwrap();
+
+        let (status, body) = request(addr, "GET", "/topics/t1/memo", Some(TOKEN), "");
+        assert_eq!(200, status);
+        let memo: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!("content1", memo["content"]);
+
+        let (status, body) = request(addr, "GET", "/topics/t1/memo?id=m1", Some(TOKEN), "");
+        assert_eq!(200, status);
+        let memo: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!("content0", memo["content"]);
+
+        let body = r##"{"content":"# new\n"}"##;
+        let (status, body) = request(addr, "POST", "/topics/t2/memos", Some(TOKEN), body);
+        assert_eq!(200, status);
+        let memo: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!("# new\n", memo["content"]);
+
+        let (status, body) = request(addr, "GET", "/topics/t2/memos", Some(TOKEN), "");
+        assert_eq!(200, status);
+        let memos: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(2, memos.as_array().unwrap().len());
+
+        let (status, body) = request(addr, "DELETE", "/topics/t1/memos/m1", Some(TOKEN), "");
+        assert_eq!(200, status);
+        assert_eq!(r#"{"remains":1}"#, body);
+
+        let (status, body) = request(addr, "DELETE", "/topics/t1", Some(TOKEN), "");
+        assert_eq!(200, status);
+        assert_eq!(r#"{"remains":0}"#, body);
+
+        let (status, _) = request(addr, "POST", "/topics/t2/memos", Some(TOKEN), "{");
+        assert_eq!(400, status);
+    }
+
+    #[test]
+    fn test_append() {
+        let addr = start(setup_appdate(), 0, TOKEN).unwrap();
+
+        let body = r#"{"text":"line"}"#;
+        let (status, body) = request(addr, "POST", "/topics/title1/append", Some(TOKEN), body);
+        assert_eq!(200, status);
+        let memo: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!("t1", memo["topic_id"]);
+        assert!(memo["content"].as_str().unwrap().ends_with("line\n"));
+    }
+
+    #[test]
+    fn test_tags() {
+        let addr = start(setup_appdate(), 0, TOKEN).unwrap();
+
+        let body = r#"{"tag":"a b"}"#;
+        let (status, _) = request(addr, "POST", "/topics/t1/tags", Some(TOKEN), body);
+        assert_eq!(204, status);
+
+        let (status, body) = request(addr, "GET", "/topics/t1/tags", Some(TOKEN), "");
+        assert_eq!(200, status);
+        assert_eq!(r#"["tag1","a b"]"#, body);
+
+        let (status, _) = request(addr, "DELETE", "/topics/t1/tags/a%20b", Some(TOKEN), "");
+        assert_eq!(204, status);
+
+        let (_, body) = request(addr, "GET", "/topics/t1/tags", Some(TOKEN), "");
+        assert_eq!(r#"["tag1"]"#, body);
+    }
+
+    #[test]
+    fn test_all_tags() {
+        let addr = start(setup_appdate(), 0, TOKEN).unwrap();
+
+        let (status, body) = request(addr, "GET", "/tags", Some(TOKEN), "");
+        assert_eq!(200, status);
+        let tags: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!("tag1", tags[0]["name"]);
+        assert_eq!(1, tags[0]["count"]);
+
+        let (status, body) = request(addr, "GET", "/tags/complete?input=ta", Some(TOKEN), "");
+        assert_eq!(200, status);
+        assert_eq!(r#"["tag1"]"#, body);
+    }
+
+    #[test]
+    fn test_locked_topic() {
+        let data = setup_appdate();
+        crate::api::lock_topic_fn(&data, "t1", "pass").unwrap();
+        let addr = start(data, 0, TOKEN).unwrap();
+
+        let (status, body) = request(addr, "GET", "/topics/t1/memo", Some(TOKEN), "");
+        assert_eq!(423, status);
+        assert_eq!(r#"{"error":"invalid passphrase"}"#, body);
+    }
+
+    #[test]
+    fn test_no_route() {
+        let addr = start(setup_appdate(), 0, TOKEN).unwrap();
+
+        let (status, _) = request(addr, "PUT", "/topics", Some(TOKEN), "");
+        assert_eq!(404, status);
+    }
+
+    #[test]
+    fn test_short_token() {
+        assert!(start(setup_appdate(), 0, "").is_err());
+        assert!(start(setup_appdate(), 0, "short").is_err());
+    }
+
+    #[test]
+    fn test_body_too_large() {
+        let addr = start(setup_appdate(), 0, TOKEN).unwrap();
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "POST /topics/t1/memos HTTP/1.1\r\nHost: {addr}\r\nAuthorization: Bearer {TOKEN}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            MAX_BODY + 1
+        )
+        .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 413"));
+    }
+
+    #[test]
+    fn test_internal_error_hidden() {
+        let e = HttpError::from(Error::Internal("detail".to_owned()));
+        assert_eq!(500, e.status);
+        assert_eq!("internal error", e.message);
+
+        assert_eq!(409, HttpError::from(Error::Cancelled).status);
+    }
+
+    #[test]
+    fn test_query_param() {
+        assert_eq!(Some("a b".to_owned()), query_param("x=1&k=a+b", "k"));
+        assert_eq!(None, query_param("x=1", "k"));
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!("a b/あ", decode("a%20b%2F%E3%81%82"));
+        assert_eq!("100%", decode("100%"));
+        assert_eq!("%zz", decode("%zz"));
+    }
+
+    fn request(
+        addr: SocketAddr,
+        method: &str,
+        path: &str,
+        token: Option<&str>,
+        body: &str,
+    ) -> (u16, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+
+        let auth = token
+            .map(|t| format!("Authorization: Bearer {t}\r\n"))
+            .unwrap_or_default();
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: {addr}\r\n{auth}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, body.to_owned())
+    }
+
+    fn setup_appdate() -> AppData {
+        let conn = setup_connect();
+        db::Topic::create(&conn, "t1", "title1", 0).unwrap();
+        db::Topic::create(&conn, "t2", "title 2", 1).unwrap();
+        db::Memo::create(&conn, "m1", "t1", 0, "content0").unwrap();
+        db::Memo::create(&conn, "m2", "t1", 1, "content1").unwrap();
+        db::Memo::create(&conn, "m3", "t2", 1, "content2").unwrap();
+        db::TopicTag::create(&conn, "tag1", "t1").unwrap();
+
+        AppData::new(conn)
+    }
+}
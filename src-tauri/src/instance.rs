@@ -0,0 +1,373 @@
+use crate::api::{append_memo_fn, find_topic_fn};
+use crate::error::Error;
+use crate::http::constant_time_eq;
+use crate::{db, AppData};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use uuid::Uuid;
+
+const TIMEOUT: Duration = Duration::from_secs(3);
+/// Lock without address is left by instance killed before listening.
+const STARTUP: Duration = Duration::from_secs(60);
+
+/// Arguments forwarded from second launch to running instance.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Args {
+    pub topic: Option<String>,
+    pub capture: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Message {
+    token: String,
+    args: Args,
+}
+
+/// File in data directory holding address and token of instance using the database.
+pub fn lock_path(db_path: &Path) -> PathBuf {
+    // Database file may not exist yet at first launch.
+    let dir = db_path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let path = match (fs::canonicalize(dir), db_path.file_name()) {
+        (Ok(dir), Some(name)) => dir.join(name),
+        _ => db_path.to_path_buf(),
+    };
+
+    db::data_dir().join("instances").join(format!(
+        "{:016x}",
+        fnv1a(path.as_os_str().as_encoded_bytes())
+    ))
+}
+
+/// Hash stable across builds and platforms unlike `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Exclusive lock of database by primary instance, lock file is removed on drop.
+#[derive(Debug)]
+pub struct InstanceLock {
+    path: PathBuf,
+}
+
+impl InstanceLock {
+    /// Take lock, returns None when other instance holds it.
+    pub fn acquire(path: &Path) -> Result<Option<Self>, Error> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        // Retry once after removing lock left by killed instance.
+        for _ in 0..2 {
+            match create_new(path) {
+                Ok(()) => {
+                    return Ok(Some(Self {
+                        path: path.to_path_buf(),
+                    }))
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    if !is_stale(path) {
+                        return Ok(None);
+                    }
+                    log::info!("remove stale instance lock path={}", path.display());
+                    match fs::remove_file(path) {
+                        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+                        _ => {}
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            log::warn!("remove instance lock error={e}");
+        }
+    }
+}
+
+fn create_new(path: &Path) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path).map(|_| ())
+}
+
+/// Whether holder of lock is gone, lock is written with address when instance starts listening.
+fn is_stale(path: &Path) -> bool {
+    let Ok(content) = fs::read_to_string(path) else {
+        return false;
+    };
+    match content
+        .split_once(' ')
+        .map(|(a, _)| a.parse::<SocketAddr>())
+    {
+        Some(Ok(addr)) => TcpStream::connect_timeout(&addr, TIMEOUT).is_err(),
+        _ => fs::metadata(path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| SystemTime::now().duration_since(t).ok())
+            .is_some_and(|age| age > STARTUP),
+    }
+}
+
+/// Send arguments to instance holding lock, waits while the instance is starting.
+pub fn forward_wait(lock: &Path, args: &Args) -> Result<bool, Error> {
+    let start = Instant::now();
+    loop {
+        if forward(lock, args)? {
+            return Ok(true);
+        }
+        if start.elapsed() > TIMEOUT {
+            return Ok(false);
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Send arguments to running instance, returns false when no instance is running.
+pub fn forward(lock: &Path, args: &Args) -> Result<bool, Error> {
+    let Ok(content) = fs::read_to_string(lock) else {
+        return Ok(false);
+    };
+    let Some((addr, token)) = content.trim().split_once(' ') else {
+        return Ok(false);
+    };
+    let Ok(addr) = addr.parse::<SocketAddr>() else {
+        return Ok(false);
+    };
+
+    // Lock file is left when instance is killed.
+    let Ok(mut stream) = TcpStream::connect_timeout(&addr, TIMEOUT) else {
+        log::debug!("stale instance lock path={}", lock.display());
+        return Ok(false);
+    };
+    stream.set_read_timeout(Some(TIMEOUT))?;
+
+    let message = Message {
+        token: token.to_owned(),
+        args: args.clone(),
+    };
+    let mut line = serde_json::to_string(&message).map_err(io::Error::from)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+
+    let mut reply = String::new();
+    if BufReader::new(stream).read_line(&mut reply).is_err() {
+        return Ok(false);
+    }
+    Ok(reply.trim() == "ok")
+}
+
+/// Accept arguments from later launches in background, and record address in lock file.
+pub fn listen<F>(lock: &Path, handler: F) -> Result<SocketAddr, Error>
+where
+    F: Fn(Args) + Send + 'static,
+{
+    let listener = TcpListener::bind(("127.0.0.1", 0))?;
+    let addr = listener.local_addr()?;
+    let token = Uuid::new_v4().simple().to_string();
+
+    write_lock(lock, &format!("{addr} {token}"))?;
+    log::info!("listen instance addr={addr} lock={}", lock.display());
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let result = stream.map_err(Error::from).and_then(|s| receive(s, &token));
+            match result {
+                Ok(args) => handler(args),
+                Err(e) => log::warn!("receive instance args error={e}"),
+            }
+        }
+    });
+
+    Ok(addr)
+}
+
+fn receive(stream: TcpStream, token: &str) -> Result<Args, Error> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let message: Message = serde_json::from_str(&line).map_err(io::Error::from)?;
+    if !constant_time_eq(message.token.as_bytes(), token.as_bytes()) {
+        return Err(Error::Internal("invalid instance token".to_owned()));
+    }
+
+    (&stream).write_all(b"ok\n")?;
+    Ok(message.args)
+}
+
+fn write_lock(lock: &Path, content: &str) -> Result<(), Error> {
+    if let Some(dir) = lock.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    // Replace atomically not to be read partially by other launch.
+    let tmp = lock.with_extension("tmp");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(&tmp)?.write_all(content.as_bytes())?;
+    fs::rename(tmp, lock)?;
+
+    Ok(())
+}
+
+/// Apply arguments, returns topic to be opened.
+pub fn apply(data: &AppData, args: &Args) -> Result<Option<String>, Error> {
+    if let Some(text) = &args.capture {
+        let topic = args.topic.as_deref().unwrap_or("");
+        let memo = append_memo_fn(data, topic, text, None)?;
+        return Ok(Some(memo.topic_id));
+    }
+
+    match &args.topic {
+        Some(topic) => Ok(Some(find_topic_fn(data, topic)?.id)),
+        None => Ok(None),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::create_table_if_not_exists;
+    use rusqlite::Connection;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_lock_path() {
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(lock_path(Path::new("a.db")), lock_path(&cwd.join("a.db")));
+        assert_ne!(lock_path(Path::new("a.db")), lock_path(Path::new("b.db")));
+        assert_eq!(0xcbf29ce484222325, fnv1a(b""));
+        assert_eq!(0xaf63dc4c8601ec8c, fnv1a(b"a"));
+    }
+
+    #[test]
+    fn test_instance_lock() {
+        let lock = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let held = InstanceLock::acquire(&lock).unwrap().unwrap();
+        assert!(InstanceLock::acquire(&lock).unwrap().is_none());
+
+        drop(held);
+        assert!(!lock.exists());
+        let held = InstanceLock::acquire(&lock).unwrap();
+        assert!(held.is_some());
+    }
+
+    #[test]
+    fn test_instance_lock_stale() {
+        let lock = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let addr = TcpListener::bind(("127.0.0.1", 0))
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        fs::write(&lock, format!("{addr} token")).unwrap();
+
+        let held = InstanceLock::acquire(&lock).unwrap().unwrap();
+        assert_eq!("", fs::read_to_string(&lock).unwrap());
+
+        // Lock of listening instance is kept.
+        listen(&lock, |_| {}).unwrap();
+        assert!(InstanceLock::acquire(&lock).unwrap().is_none());
+        drop(held);
+    }
+
+    #[test]
+    fn test_forward() {
+        let lock = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let args = Args {
+            topic: Some("t1".to_owned()),
+            capture: None,
+        };
+        assert!(!forward(&lock, &args).unwrap());
+
+        let (tx, rx) = mpsc::channel();
+        listen(&lock, move |a| {
+            let _ = tx.send(a);
+        })
+        .unwrap();
+
+        assert!(forward(&lock, &args).unwrap());
+        assert_eq!(args, rx.recv_timeout(TIMEOUT).unwrap());
+
+        fs::remove_file(lock).unwrap();
+    }
+
+    #[test]
+    fn test_forward_invalid_token() {
+        let lock = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let (tx, rx) = mpsc::channel();
+        let addr = listen(&lock, move |a| {
+            let _ = tx.send(a);
+        })
+        .unwrap();
+
+        fs::write(&lock, format!("{addr} wrong")).unwrap();
+        assert!(!forward(&lock, &Args::default()).unwrap());
+        assert!(rx.try_recv().is_err());
+
+        fs::remove_file(lock).unwrap();
+    }
+
+    #[test]
+    fn test_forward_stale() {
+        let lock = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let addr = TcpListener::bind(("127.0.0.1", 0))
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        fs::write(&lock, format!("{addr} token")).unwrap();
+
+        assert!(!forward(&lock, &Args::default()).unwrap());
+
+        fs::remove_file(lock).unwrap();
+    }
+
+    #[test]
+    fn test_apply() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_table_if_not_exists(&conn).unwrap();
+        let data = AppData::new(conn);
+
+        assert_eq!(None, apply(&data, &Args::default()).unwrap());
+
+        let args = Args {
+            topic: Some("inbox".to_owned()),
+            capture: Some("idea".to_owned()),
+        };
+        let topic_id = apply(&data, &args).unwrap().unwrap();
+
+        let args = Args {
+            topic: Some("inbox".to_owned()),
+            capture: None,
+        };
+        assert_eq!(Some(topic_id), apply(&data, &args).unwrap());
+    }
+}
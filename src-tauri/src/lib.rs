@@ -1,117 +1,805 @@
 pub mod api;
+pub mod backup;
+pub mod cancel;
+pub mod check;
+pub mod cli;
+pub mod config;
 pub mod db;
+pub mod epub;
 pub mod error;
+pub mod event;
+pub mod html;
+pub mod http;
+pub mod instance;
+pub mod logger;
+pub mod maintenance;
+pub mod markdown;
+pub mod mcp;
 pub mod model;
+pub mod obsidian;
+pub mod pool;
+pub mod secret;
+pub mod site;
+#[cfg(test)]
+mod testing;
+pub mod trash;
 
 use rusqlite::Connection;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use api::{
-    add_memo_tag_fn, create_memo_fn, delete_memo_fn, delete_topic_fn, get_memo_all_fn, get_memo_fn,
-    get_memo_tag_fn, get_topics_fn, remove_memo_tag_fn,
+    add_memo_tag_fn, complete_tag_fn, create_memo_fn, delete_memo_fn, delete_tag_fn,
+    delete_topic_fn, get_all_tags_fn, get_memo_all_fn, get_memo_fn, get_memo_tag_fn, get_topics_fn,
+    lock_topic_fn, merge_tag_fn, relock_topic_fn, remove_memo_tag_fn, remove_topic_lock_fn,
+    rename_tag_fn, search_topics_fn, set_tag_fn, unlock_topic_fn,
 };
-use std::sync::Mutex;
-use tauri::{Builder, Manager, State};
-use tauri_plugin_cli::CliExt;
+use backup::{backup_fn, list_backups_fn, restore_backup_fn};
+use cancel::Searches;
+use check::check_database_fn;
+use clap::Parser;
+use cli::Cli;
+use config::Config;
+use db::AutoVacuum;
+use epub::export_epub_fn;
+use error::Error;
+use log::LevelFilter;
+use maintenance::maintain_database_fn;
+use obsidian::{export_obsidian_fn, import_obsidian_fn};
+use pool::Pool;
+use secret::Keys;
+use site::export_site_fn;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tauri::{async_runtime, AppHandle, Builder, Emitter, Manager, RunEvent, State};
+use trash::{get_trash_fn, purge_trash_fn, restore_trash_fn};
 
+const READERS: usize = 4;
+const EVENT_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Clone)]
 pub struct AppData {
-    db: Mutex<Connection>,
+    db: Arc<Pool>,
+    searches: Arc<Searches>,
+    keys: Arc<Keys>,
+    /// Key of encrypted database, to open backups.
+    passphrase: Option<Arc<str>>,
+    /// Sync `#tag` in saved memo into tags of topic.
+    inline_tags: bool,
 }
 
+impl AppData {
+    pub fn new(db: Connection) -> Self {
+        AppData::with_readers(db, vec![])
+    }
+
+    pub fn with_readers(db: Connection, readers: Vec<Connection>) -> Self {
+        AppData {
+            db: Arc::new(Pool::new(db, readers)),
+            searches: Arc::new(Searches::default()),
+            keys: Arc::new(Keys::default()),
+            passphrase: None,
+            inline_tags: false,
+        }
+    }
+
+    pub fn open(
+        file_path: Option<&Path>,
+        in_memory: bool,
+        passphrase: Option<&str>,
+        auto_vacuum: Option<AutoVacuum>,
+    ) -> Result<Self, Error> {
+        let db =
+            db::setup(file_path, in_memory, passphrase, auto_vacuum).map_err(Error::from_open)?;
+        let readers = db::open_readers(&db, READERS, passphrase)?;
+        Ok(AppData {
+            passphrase: passphrase.map(Arc::from),
+            ..AppData::with_readers(db, readers)
+        })
+    }
+}
+
+/// Options to serve database once it is opened.
+struct Startup {
+    path: Option<PathBuf>,
+    memory: bool,
+    auto_vacuum: Option<AutoVacuum>,
+    trash_days: Option<u64>,
+    inline_tags: bool,
+    http_port: Option<u16>,
+    http_token: Option<String>,
+    args: instance::Args,
+    /// Arguments forwarded from later launches until database is opened.
+    forwarded: Vec<instance::Args>,
+}
+
+/// Startup waiting for passphrase of encrypted database.
+struct Locked(Mutex<Option<Startup>>);
+
+/// Topic to be opened when window is loaded.
+struct LaunchTopic(Mutex<Option<String>>);
+
 #[tauri::command]
-fn add_memo_tag(state: State<'_, AppData>, topic_id: &str, tag: &str) -> Result<(), ()> {
-    add_memo_tag_fn(state.inner(), topic_id, tag).or(Err(()))?;
-    Ok(())
+async fn add_memo_tag(
+    state: State<'_, AppData>,
+    topic_id: String,
+    tag: String,
+) -> Result<(), Error> {
+    let id = topic_id.clone();
+    invoke(state, "add_memo_tag", Some(&topic_id), move |data| {
+        add_memo_tag_fn(data, &id, &tag)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn backup_now(
+    state: State<'_, AppData>,
+    settings: State<'_, backup::Settings>,
+) -> Result<model::Backup, Error> {
+    let settings = settings.inner().clone();
+    invoke(state, "backup_now", None, move |data| {
+        backup_fn(data, &settings)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn check_database(
+    state: State<'_, AppData>,
+    repair: bool,
+) -> Result<model::CheckReport, Error> {
+    invoke(state, "check_database", None, move |data| {
+        check_database_fn(data, repair)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn complete_tag(state: State<'_, AppData>, input: String) -> Result<Vec<String>, Error> {
+    invoke(state, "complete_tag", None, move |data| {
+        complete_tag_fn(data, &input)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn create_memo(
+    state: State<'_, AppData>,
+    topic_id: String,
+    content: String,
+) -> Result<model::Memo, Error> {
+    let id = topic_id.clone();
+    invoke(state, "create_memo", Some(&topic_id), move |data| {
+        create_memo_fn(data, &id, &content)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn delete_memo(
+    state: State<'_, AppData>,
+    topic_id: String,
+    id: String,
+) -> Result<usize, Error> {
+    let topic = topic_id.clone();
+    invoke(state, "delete_memo", Some(&topic_id), move |data| {
+        delete_memo_fn(data, &topic, Some(&id))
+    })
+    .await
+}
+
+#[tauri::command]
+async fn delete_tag(state: State<'_, AppData>, name: String) -> Result<usize, Error> {
+    invoke(state, "delete_tag", None, move |data| {
+        delete_tag_fn(data, &name)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn delete_topic(state: State<'_, AppData>, topic_id: String) -> Result<usize, Error> {
+    let id = topic_id.clone();
+    invoke(state, "delete_topic", Some(&topic_id), move |data| {
+        delete_topic_fn(data, &id)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn export_epub(
+    state: State<'_, AppData>,
+    path: String,
+    keyword: String,
+    language: String,
+) -> Result<usize, Error> {
+    invoke(state, "export_epub", None, move |data| {
+        export_epub_fn(data, Path::new(&path), &keyword, &language)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn export_obsidian(
+    state: State<'_, AppData>,
+    path: String,
+    keyword: String,
+) -> Result<usize, Error> {
+    invoke(state, "export_obsidian", None, move |data| {
+        export_obsidian_fn(data, Path::new(&path), &keyword)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn export_site(
+    state: State<'_, AppData>,
+    path: String,
+    keyword: String,
+) -> Result<usize, Error> {
+    invoke(state, "export_site", None, move |data| {
+        export_site_fn(data, Path::new(&path), &keyword)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn get_all_tags(state: State<'_, AppData>) -> Result<Vec<model::Tag>, Error> {
+    invoke(state, "get_all_tags", None, get_all_tags_fn).await
+}
+
+#[tauri::command]
+async fn get_memo(
+    state: State<'_, AppData>,
+    topic_id: String,
+    id: Option<String>,
+) -> Result<model::Memo, Error> {
+    let topic = topic_id.clone();
+    invoke(state, "get_memo", Some(&topic_id), move |data| {
+        get_memo_fn(data, &topic, id.as_deref())
+    })
+    .await
+}
+
+#[tauri::command]
+async fn get_memo_all(
+    state: State<'_, AppData>,
+    topic_id: String,
+) -> Result<Vec<model::Memo>, Error> {
+    let id = topic_id.clone();
+    invoke(state, "get_memo_all", Some(&topic_id), move |data| {
+        get_memo_all_fn(data, &id)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn get_memo_tag(
+    state: State<'_, AppData>,
+    topic_id: String,
+) -> Result<Vec<model::TopicTag>, Error> {
+    let id = topic_id.clone();
+    invoke(state, "get_memo_tag", Some(&topic_id), move |data| {
+        get_memo_tag_fn(data, &id)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn get_topics(
+    state: State<'_, AppData>,
+    keyword: String,
+) -> Result<Vec<model::Topic>, Error> {
+    // Newer search supersedes this while typing keyword, but listing all is not superseded.
+    if keyword.is_empty() {
+        return invoke(state, "get_topics", None, |data| get_topics_fn(data, "")).await;
+    }
+    let ticket = state.searches.begin();
+    invoke(state, "get_topics", None, move |data| {
+        search_topics_fn(data, &keyword, ticket)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn get_trash(state: State<'_, AppData>) -> Result<Vec<model::TrashMemo>, Error> {
+    invoke(state, "get_trash", None, get_trash_fn).await
+}
+
+#[tauri::command]
+async fn import_obsidian(state: State<'_, AppData>, path: String) -> Result<usize, Error> {
+    invoke(state, "import_obsidian", None, move |data| {
+        import_obsidian_fn(data, Path::new(&path))
+    })
+    .await
+}
+
+#[tauri::command]
+fn is_locked(locked: State<'_, Locked>) -> bool {
+    locked
+        .0
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .is_some()
+}
+
+#[tauri::command]
+async fn list_backups(
+    state: State<'_, AppData>,
+    settings: State<'_, backup::Settings>,
+) -> Result<Vec<model::Backup>, Error> {
+    let settings = settings.inner().clone();
+    invoke(state, "list_backups", None, move |_| {
+        list_backups_fn(&settings)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn lock_topic(
+    state: State<'_, AppData>,
+    topic_id: String,
+    passphrase: String,
+) -> Result<(), Error> {
+    let id = topic_id.clone();
+    invoke(state, "lock_topic", Some(&topic_id), move |data| {
+        lock_topic_fn(data, &id, &passphrase)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn maintain_database(
+    state: State<'_, AppData>,
+    dry_run: bool,
+) -> Result<model::Maintenance, Error> {
+    invoke(state, "maintain_database", None, move |data| {
+        maintain_database_fn(data, dry_run)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn merge_tag(state: State<'_, AppData>, from: String, into: String) -> Result<usize, Error> {
+    invoke(state, "merge_tag", None, move |data| {
+        merge_tag_fn(data, &from, &into)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn purge_trash(state: State<'_, AppData>, topic_id: Option<String>) -> Result<usize, Error> {
+    let id = topic_id.clone();
+    invoke(state, "purge_trash", topic_id.as_deref(), move |data| {
+        purge_trash_fn(data, id.as_deref())
+    })
+    .await
+}
+
+#[tauri::command]
+async fn relock_topic(state: State<'_, AppData>, topic_id: String) -> Result<(), Error> {
+    let id = topic_id.clone();
+    invoke(state, "relock_topic", Some(&topic_id), move |data| {
+        relock_topic_fn(data, &id)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn remove_memo_tag(
+    state: State<'_, AppData>,
+    topic_id: String,
+    tag: String,
+) -> Result<(), Error> {
+    let id = topic_id.clone();
+    invoke(state, "remove_memo_tag", Some(&topic_id), move |data| {
+        remove_memo_tag_fn(data, &id, &tag)
+    })
+    .await
 }
 
 #[tauri::command]
-fn create_memo(
+async fn remove_topic_lock(
     state: State<'_, AppData>,
-    topic_id: &str,
-    content: &str,
-) -> Result<model::Memo, ()> {
-    let memo = create_memo_fn(state.inner(), topic_id, content).or(Err(()))?;
-    Ok(memo)
+    topic_id: String,
+    passphrase: String,
+) -> Result<(), Error> {
+    let id = topic_id.clone();
+    invoke(state, "remove_topic_lock", Some(&topic_id), move |data| {
+        remove_topic_lock_fn(data, &id, &passphrase)
+    })
+    .await
 }
 
 #[tauri::command]
-fn delete_memo(state: State<'_, AppData>, topic_id: &str, id: &str) -> Result<usize, ()> {
-    let remains = delete_memo_fn(state.inner(), topic_id, Some(id)).or(Err(()))?;
-    Ok(remains)
+async fn rename_tag(state: State<'_, AppData>, from: String, to: String) -> Result<usize, Error> {
+    invoke(state, "rename_tag", None, move |data| {
+        rename_tag_fn(data, &from, &to)
+    })
+    .await
 }
 
 #[tauri::command]
-fn delete_topic(state: State<'_, AppData>, topic_id: &str) -> Result<usize, ()> {
-    let remains = delete_topic_fn(state.inner(), topic_id).or(Err(()))?;
-    Ok(remains)
+async fn restore_backup(
+    state: State<'_, AppData>,
+    settings: State<'_, backup::Settings>,
+    name: String,
+) -> Result<model::Backup, Error> {
+    let settings = settings.inner().clone();
+    invoke(state, "restore_backup", None, move |data| {
+        restore_backup_fn(data, &settings, &name)
+    })
+    .await
 }
 
 #[tauri::command]
-fn get_memo(
+async fn restore_trash(
     state: State<'_, AppData>,
-    topic_id: &str,
-    id: Option<&str>,
-) -> Result<model::Memo, ()> {
-    get_memo_fn(state.inner(), topic_id, id).or(Err(()))
+    topic_id: String,
+    id: Option<String>,
+) -> Result<usize, Error> {
+    let topic = topic_id.clone();
+    invoke(state, "restore_trash", Some(&topic_id), move |data| {
+        restore_trash_fn(data, &topic, id.as_deref())
+    })
+    .await
 }
 
 #[tauri::command]
-fn get_memo_all(state: State<'_, AppData>, topic_id: &str) -> Result<Vec<model::Memo>, ()> {
-    get_memo_all_fn(state.inner(), topic_id).or(Err(()))
+async fn set_tag(
+    state: State<'_, AppData>,
+    name: String,
+    color: Option<String>,
+    description: Option<String>,
+    aliases: Option<Vec<String>>,
+) -> Result<(), Error> {
+    invoke(state, "set_tag", None, move |data| {
+        set_tag_fn(
+            data,
+            &name,
+            color.as_deref(),
+            description.as_deref(),
+            aliases.as_deref(),
+        )
+    })
+    .await
 }
 
 #[tauri::command]
-fn get_memo_tag(state: State<'_, AppData>, topic_id: &str) -> Result<Vec<String>, ()> {
-    get_memo_tag_fn(state.inner(), topic_id).or(Err(()))
+fn take_launch_topic(launch: State<'_, LaunchTopic>) -> Option<String> {
+    launch
+        .0
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take()
 }
 
 #[tauri::command]
-fn get_topics(state: State<'_, AppData>, keyword: &str) -> Result<Vec<model::Topic>, ()> {
-    get_topics_fn(state.inner(), keyword).or(Err(()))
+async fn unlock(app: AppHandle, passphrase: String) -> Result<(), Error> {
+    async_runtime::spawn_blocking(move || {
+        let locked = app.state::<Locked>();
+        let mut startup = locked.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(s) = startup.as_ref() else {
+            return Ok(());
+        };
+
+        match start(&app, s, Some(&passphrase)) {
+            Ok(()) => {
+                log::info!("command=unlock");
+                *startup = None;
+                Ok(())
+            }
+            Err(e) => {
+                log::warn!("command=unlock error={}", logger::error_chain(&e));
+                Err(e.context("unlock"))
+            }
+        }
+    })
+    .await
+    .unwrap_or_else(|e| Err(Error::Internal(e.to_string())))
 }
 
 #[tauri::command]
-fn remove_memo_tag(state: State<'_, AppData>, topic_id: &str, tag: &str) -> Result<(), ()> {
-    remove_memo_tag_fn(state.inner(), topic_id, tag).or(Err(()))?;
+async fn unlock_topic(
+    state: State<'_, AppData>,
+    topic_id: String,
+    passphrase: String,
+) -> Result<(), Error> {
+    let id = topic_id.clone();
+    invoke(state, "unlock_topic", Some(&topic_id), move |data| {
+        unlock_topic_fn(data, &id, &passphrase)
+    })
+    .await
+}
+
+/// Run command on blocking worker logging its duration and error.
+async fn invoke<T, F>(
+    state: State<'_, AppData>,
+    command: &str,
+    topic_id: Option<&str>,
+    f: F,
+) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce(&AppData) -> Result<T, Error> + Send + 'static,
+{
+    let data = state.inner().clone();
+
+    let start = Instant::now();
+    let result = async_runtime::spawn_blocking(move || f(&data))
+        .await
+        .unwrap_or_else(|e| Err(Error::Internal(e.to_string())));
+    let elapsed = start.elapsed().as_millis();
+
+    let topic_id = topic_id.unwrap_or("-");
+    match result {
+        Ok(r) => {
+            log::info!("command={command} topic_id={topic_id} duration_ms={elapsed}");
+            Ok(r)
+        }
+        Err(Error::Cancelled) => {
+            log::debug!("command={command} topic_id={topic_id} duration_ms={elapsed} cancelled");
+            Err(Error::Cancelled.context(command))
+        }
+        Err(e) => {
+            let e = e.context(command);
+            log::error!(
+                "command={command} topic_id={topic_id} duration_ms={elapsed} error={}",
+                logger::error_chain(&e)
+            );
+            Err(e)
+        }
+    }
+}
+
+/// Open database and serve it, which fails with `Error::Passphrase` if encrypted.
+fn start(app: &AppHandle, startup: &Startup, passphrase: Option<&str>) -> Result<(), Error> {
+    let data = AppData {
+        inline_tags: startup.inline_tags,
+        ..AppData::open(
+            startup.path.as_deref(),
+            startup.memory,
+            passphrase,
+            startup.auto_vacuum,
+        )?
+    };
+
+    let mut topic_id = None;
+    for args in std::iter::once(&startup.args).chain(&startup.forwarded) {
+        match instance::apply(&data, args) {
+            Ok(Some(id)) => topic_id = Some(id),
+            Ok(None) => {}
+            Err(e) => log::warn!("apply args error={e}"),
+        }
+    }
+    app.manage(LaunchTopic(Mutex::new(topic_id)));
+
+    if let Some(days) = startup.trash_days {
+        trash::schedule(data.clone(), days);
+    }
+
+    if let Some(port) = startup.http_port {
+        let token = match &startup.http_token {
+            Some(t) => t.clone(),
+            None => http::load_or_create_token()?,
+        };
+        http::start(data.clone(), port, &token)?;
+    }
+
+    // Notify changes including ones by CLI or HTTP API.
+    let handle = app.clone();
+    event::watch(data.clone(), EVENT_INTERVAL, move |e| {
+        if let Err(err) = handle.emit(e.name(), &e) {
+            log::warn!("emit event name={} error={err}", e.name());
+        }
+    })?;
+
+    // Back up at startup and every interval, in-memory database is not worth it.
+    if !startup.memory {
+        let settings = app.state::<backup::Settings>().inner().clone();
+        backup::schedule(data.clone(), settings);
+    }
+
+    app.manage(data);
+
     Ok(())
 }
 
+/// Open topic by arguments forwarded from second launch.
+fn open(app: &AppHandle, args: &instance::Args) {
+    // Kept until started or unlocked, not to lose captured text.
+    let queued = app.try_state::<Locked>().is_some_and(|locked| {
+        let mut startup = locked.0.lock().unwrap_or_else(PoisonError::into_inner);
+        startup
+            .as_mut()
+            .map(|s| s.forwarded.push(args.clone()))
+            .is_some()
+    });
+
+    if queued {
+        log::info!("queue args until started");
+    } else if let Some(data) = app.try_state::<AppData>() {
+        match instance::apply(&data, args) {
+            Ok(Some(topic_id)) => {
+                if let Err(e) = app.emit("open-topic", &topic_id) {
+                    log::warn!("emit open-topic error={e}");
+                }
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("apply args error={e}"),
+        }
+    }
+
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+    }
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    Builder::default()
-        .plugin(tauri_plugin_cli::init())
-        .setup(|app| {
-            let cli = app.cli().matches()?;
+    let cli = Cli::parse();
+
+    let (config, config_error) = match Config::load() {
+        Ok(config) => (config, None),
+        Err(e) => {
+            eprintln!("error: config.json: {e}");
+            (Config::default(), Some(e))
+        }
+    };
+    let level = cli
+        .log_level
+        .or(config.log_level())
+        .unwrap_or(LevelFilter::Info);
+    // Subcommands may run while GUI is running, so they log to another file.
+    let log_name = match cli.command {
+        Some(_) => "memo-cli.log",
+        None => "memo.log",
+    };
+    if let Err(e) = logger::init(&db::data_dir().join("logs"), log_name, level) {
+        eprintln!("error: {e}");
+    }
+    log::info!("start version={}", env!("CARGO_PKG_VERSION"));
+    if let Some(e) = config_error {
+        log::error!("load config error={e}, use default");
+    }
+    if cli.command.is_some() {
+        // Run without window.
+        std::process::exit(cli::run(cli, &config));
+    }
+
+    let args = instance::Args {
+        topic: cli.open.clone(),
+        capture: cli.capture.clone(),
+    };
+
+    // Single instance per database, in-memory database is never shared.
+    let memory = cli.memory;
+    let lock = (!cli.memory).then(|| {
+        let path = cli.path.clone().unwrap_or_else(db::create_default_path);
+        instance::lock_path(&path)
+    });
+    // Primary instance is decided by exclusive lock, not to start two for the same database.
+    let mut held = None;
+    if let Some(lock) = &lock {
+        match instance::InstanceLock::acquire(lock) {
+            Ok(Some(l)) => held = Some(l),
+            Ok(None) => {
+                match instance::forward_wait(lock, &args) {
+                    Ok(true) => log::info!("forward args to running instance"),
+                    Ok(false) => {
+                        log::error!("database is used by other process");
+                        eprintln!("error: database is used by other process");
+                    }
+                    Err(e) => log::error!("forward args error={e}"),
+                }
+                return;
+            }
+            Err(e) => log::warn!("instance lock error={e}"),
+        }
+    }
+    // Do not listen without lock, not to take address of other instance.
+    let lock = lock.filter(|_| held.is_some());
 
-            let file_path = cli
-                .args
-                .get("path")
-                .and_then(|p| p.value.as_str())
-                .map(Path::new);
+    Builder::default()
+        .setup(move |app| {
+            app.manage(config.backup());
 
-            let in_memory = cli
-                .args
-                .get("memory")
-                .and_then(|p| p.value.as_bool())
-                .unwrap_or_default();
+            let startup = Startup {
+                path: cli.path.clone(),
+                memory: cli.memory,
+                auto_vacuum: config.auto_vacuum,
+                trash_days: config.trash_days(),
+                inline_tags: config.inline_tags.unwrap_or_default(),
+                http_port: cli.http_port,
+                http_token: cli.http_token.clone(),
+                args,
+                forwarded: vec![],
+            };
+            let passphrase = cli.passphrase.as_deref().filter(|p| !p.is_empty());
 
-            let db = db::setup(file_path, in_memory)?;
+            // Arguments forwarded until started are queued in startup.
+            app.manage(Locked(Mutex::new(Some(startup))));
+            if let Some(lock) = &lock {
+                let handle = app.handle().clone();
+                instance::listen(lock, move |args| open(&handle, &args))?;
+            }
 
-            app.manage(AppData { db: Mutex::new(db) });
+            let locked = app.state::<Locked>();
+            let mut startup = locked.0.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(s) = startup.as_ref() {
+                match start(app.handle(), s, passphrase) {
+                    Ok(()) => *startup = None,
+                    Err(Error::Passphrase) => {
+                        // Frontend asks passphrase and calls `unlock`.
+                        log::info!("wait for passphrase");
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
 
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             add_memo_tag,
+            backup_now,
+            check_database,
+            complete_tag,
             create_memo,
             delete_memo,
+            delete_tag,
             delete_topic,
+            export_epub,
+            export_obsidian,
+            export_site,
+            get_all_tags,
             get_memo,
             get_memo_all,
             get_memo_tag,
             get_topics,
+            get_trash,
+            import_obsidian,
+            is_locked,
+            list_backups,
+            lock_topic,
+            maintain_database,
+            merge_tag,
+            purge_trash,
+            relock_topic,
             remove_memo_tag,
+            remove_topic_lock,
+            rename_tag,
+            restore_backup,
+            restore_trash,
+            set_tag,
+            take_launch_topic,
+            unlock,
+            unlock_topic,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(move |app, event| {
+            if let RunEvent::Exit = event {
+                backup_on_exit(app, memory);
+                // Remove lock file for next launch.
+                held.take();
+            }
+        });
+}
+
+fn backup_on_exit(app: &AppHandle, memory: bool) {
+    if memory {
+        return;
+    }
+    // Database is not opened while waiting for passphrase.
+    let (Some(data), Some(settings)) = (
+        app.try_state::<AppData>(),
+        app.try_state::<backup::Settings>(),
+    ) else {
+        return;
+    };
+
+    match backup_fn(&data, &settings) {
+        Ok(b) => log::info!("backup at exit name={}", b.name),
+        Err(e) => log::warn!("backup at exit error={e}"),
+    }
 }
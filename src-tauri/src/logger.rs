@@ -0,0 +1,192 @@
+use crate::error::Error;
+use chrono::Local;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+const MAX_SIZE: u64 = 1024 * 1024;
+const KEEP: usize = 5;
+
+/// Logger appending to file, which is rotated as `<name>.1` .. `<name>.<keep>`.
+pub struct FileLogger {
+    path: PathBuf,
+    max_size: u64,
+    keep: usize,
+    file: Mutex<Option<File>>,
+}
+
+impl FileLogger {
+    pub fn new(path: &Path, max_size: u64, keep: usize) -> Self {
+        FileLogger {
+            path: path.to_path_buf(),
+            max_size,
+            keep,
+            file: Mutex::new(None),
+        }
+    }
+
+    fn write_line(&self, line: &str) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let size = match file.as_ref() {
+            Some(f) => f.metadata()?.len(),
+            None => fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0),
+        };
+        if size > 0 && size + line.len() as u64 > self.max_size {
+            *file = None;
+            self.rotate()?;
+        }
+
+        if file.is_none() {
+            *file = Some(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?,
+            );
+        }
+
+        file.as_mut().unwrap().write_all(line.as_bytes())
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        for n in (1..self.keep).rev() {
+            let from = self.rotated_path(n);
+            if from.exists() {
+                fs::rename(from, self.rotated_path(n + 1))?;
+            }
+        }
+
+        if self.keep > 0 {
+            fs::rename(&self.path, self.rotated_path(1))
+        } else {
+            fs::remove_file(&self.path)
+        }
+    }
+
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".{n}"));
+        self.path.with_file_name(name)
+    }
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!(
+            "{} {:<5} [{}] {}\n",
+            Local::now().format("%Y-%m-%dT%H:%M:%S%.3f%:z"),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        let _ = self.write_line(&line);
+    }
+
+    fn flush(&self) {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(f) = file.as_mut() {
+            let _ = f.flush();
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Install logger writing file of specified name in directory.
+///
+/// Processes must not share the name, since rotation by one is not seen by the others.
+pub fn init(dir: &Path, name: &str, level: LevelFilter) -> Result<(), Error> {
+    fs::create_dir_all(dir)?;
+
+    let logger = FileLogger::new(&dir.join(name), MAX_SIZE, KEEP);
+    log::set_boxed_logger(Box::new(logger)).map_err(io::Error::other)?;
+    log::set_max_level(level);
+
+    Ok(())
+}
+
+/// Format error followed by its causes not included in the message.
+pub fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut chain = error.to_string();
+
+    let mut source = error.source();
+    while let Some(e) = source {
+        let message = e.to_string();
+        if !chain.ends_with(&message) {
+            chain.push_str(" <- ");
+            chain.push_str(&message);
+        }
+        source = e.source();
+    }
+
+    chain
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_file_logger_rotate() {
+        let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("a.log");
+        let logger = FileLogger::new(&path, 12, 2);
+        logger.write_line("1234567\n").unwrap();
+        logger.write_line("abcdefg\n").unwrap();
+        logger.write_line("ABCDEFG\n").unwrap();
+        logger.write_line("xyz\n").unwrap();
+
+        assert_eq!("ABCDEFG\nxyz\n", fs::read_to_string(&path).unwrap());
+        assert_eq!(
+            "abcdefg\n",
+            fs::read_to_string(dir.join("a.log.1")).unwrap()
+        );
+        assert_eq!(
+            "1234567\n",
+            fs::read_to_string(dir.join("a.log.2")).unwrap()
+        );
+        assert!(!dir.join("a.log.3").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_logger_append() {
+        let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("a.log");
+        fs::write(&path, "old\n").unwrap();
+
+        let logger = FileLogger::new(&path, 100, 2);
+        logger.write_line("new\n").unwrap();
+        assert_eq!("old\nnew\n", fs::read_to_string(&path).unwrap());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_error_chain() {
+        let e = Error::NotFound("t1".to_owned()).context("get_memo");
+        assert_eq!("get_memo: not found: t1", error_chain(&e));
+
+        let e = Error::Io(io::Error::other(Error::LockError("a".to_owned())));
+        assert_eq!("io error: lock error: a", error_chain(&e));
+    }
+}
@@ -0,0 +1,124 @@
+use crate::api::topic_model;
+use crate::error::Error;
+use crate::{db, model, AppData};
+use std::collections::HashSet;
+use std::time::Instant;
+
+const LARGEST_TOPICS: usize = 10;
+
+pub fn database_stats_fn(data: &AppData) -> Result<model::DatabaseStats, Error> {
+    let db = data.db.reader();
+
+    let (size, free) = db::file_size(&db)?;
+    let tables = db::table_sizes(&db)?
+        .into_iter()
+        .map(|t| model::TableSize {
+            name: t.name,
+            size: t.size,
+        })
+        .collect();
+
+    let topics = db::Topic::all(&db)?;
+    let locked: HashSet<_> = db::TopicLock::all(&db)?
+        .into_iter()
+        .map(|l| l.topic_id)
+        .collect();
+    let largest_topics = db::Memo::largest_topics(&db, LARGEST_TOPICS)?
+        .into_iter()
+        .map(|s| {
+            // Memos may be left without topic, see `check_database_fn`.
+            let title = topics
+                .iter()
+                .find(|t| t.id == s.topic_id)
+                .map(|t| topic_model(t, locked.contains(&t.id), &data.keys).title)
+                .unwrap_or_default();
+            model::TopicSize {
+                id: s.topic_id,
+                title,
+                revisions: s.revisions,
+                size: s.size,
+            }
+        })
+        .collect();
+
+    Ok(model::DatabaseStats {
+        size,
+        free,
+        topics: topics.len(),
+        revisions: db::Memo::count(&db)?,
+        tables,
+        largest_topics,
+    })
+}
+
+/// Report statistics, then reclaim free pages and optimize database unless `dry_run`.
+pub fn maintain_database_fn(data: &AppData, dry_run: bool) -> Result<model::Maintenance, Error> {
+    let before = database_stats_fn(data)?;
+    if dry_run {
+        return Ok(model::Maintenance {
+            before,
+            after: None,
+        });
+    }
+
+    let start = Instant::now();
+    db::vacuum(&data.db.writer())?;
+    log::info!(
+        "vacuum duration_ms={} free={}",
+        start.elapsed().as_millis(),
+        before.free
+    );
+
+    Ok(model::Maintenance {
+        before,
+        after: Some(database_stats_fn(data)?),
+    })
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{create_memo_fn, delete_topic_fn};
+    use crate::testing::setup_appdate;
+    use crate::trash::purge_trash_fn;
+
+    #[test]
+    fn test_database_stats_fn() {
+        let data = setup_appdate();
+        create_memo_fn(&data, "t1", "# title1").unwrap();
+        create_memo_fn(&data, "t1", "# title1\ncontent").unwrap();
+        create_memo_fn(&data, "t2", "# title2").unwrap();
+
+        let stats = database_stats_fn(&data).unwrap();
+        assert!(stats.size > 0);
+        assert_eq!(2, stats.topics);
+        assert_eq!(3, stats.revisions);
+        assert!(stats.tables.iter().any(|t| t.name == "memo"));
+        assert_eq!("t1", stats.largest_topics[0].id);
+        assert_eq!("title1", stats.largest_topics[0].title);
+        assert_eq!(2, stats.largest_topics[0].revisions);
+    }
+
+    #[test]
+    fn test_maintain_database_fn() {
+        let data = setup_appdate();
+        for i in 0..100 {
+            create_memo_fn(&data, &format!("t{i}"), &"content".repeat(100)).unwrap();
+        }
+        for i in 0..100 {
+            delete_topic_fn(&data, &format!("t{i}")).unwrap();
+        }
+        purge_trash_fn(&data, None).unwrap();
+
+        let report = maintain_database_fn(&data, true).unwrap();
+        assert!(report.before.free > 0);
+        assert!(report.after.is_none());
+
+        let report = maintain_database_fn(&data, false).unwrap();
+        let after = report.after.unwrap();
+        assert_eq!(0, after.free);
+        assert!(after.size < report.before.size);
+    }
+}
@@ -0,0 +1,371 @@
+use regex::{Captures, Regex};
+
+// -----------------------------------------------------------------------------------------------
+
+pub struct WikiLink {
+    pub embed: bool,
+    pub target: String,
+    pub anchor: Option<String>,
+    pub label: Option<String>,
+}
+
+impl WikiLink {
+    pub fn format(&self) -> String {
+        let embed = if self.embed { "!" } else { "" };
+        let anchor = self
+            .anchor
+            .as_ref()
+            .map(|a| format!("#{a}"))
+            .unwrap_or_default();
+        let label = self
+            .label
+            .as_ref()
+            .map(|l| format!("|{l}"))
+            .unwrap_or_default();
+        format!("{embed}[[{}{anchor}{label}]]", self.target)
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Split YAML front matter enclosed by `---` lines from the body.
+pub fn split_front_matter(content: &str) -> (Option<&str>, &str) {
+    let Some(rest) = content
+        .strip_prefix("---\n")
+        .or_else(|| content.strip_prefix("---\r\n"))
+    else {
+        return (None, content);
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            let front = &rest[..offset];
+            let body = &rest[offset + line.len()..];
+            return (Some(front), body);
+        }
+        offset += line.len();
+    }
+
+    (None, content)
+}
+
+/// Collect `tags` (or `tag`) values in front matter.
+///
+/// Both the inline form (`tags: [a, b]`, `tags: a, b`) and the block list form are supported.
+pub fn front_matter_tags(front: &str) -> Vec<String> {
+    let mut tags = vec![];
+
+    let mut in_list = false;
+    for line in front.lines() {
+        if in_list {
+            if let Some(item) = line.trim_start().strip_prefix("- ") {
+                push_tag(&mut tags, item);
+                continue;
+            }
+            in_list = false;
+        }
+
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+
+        if key.trim() != "tags" && key.trim() != "tag" {
+            continue;
+        }
+
+        let value = value.trim();
+        if value.is_empty() {
+            in_list = true;
+        } else {
+            let value = value.trim_start_matches('[').trim_end_matches(']');
+            for item in value.split([',', ' ']) {
+                push_tag(&mut tags, item);
+            }
+        }
+    }
+
+    tags
+}
+
+/// Collect `#tag` in content except code and headings.
+pub fn inline_tags(content: &str) -> Vec<String> {
+    let re = Regex::new(r"(?:^|[\s(])#([\p{L}\p{N}_/-]+)").unwrap();
+
+    let mut tags = vec![];
+    for (code, text) in split_code(content) {
+        if code {
+            continue;
+        }
+
+        for line in text.lines() {
+            if is_heading(line) {
+                continue;
+            }
+
+            for cap in re.captures_iter(line) {
+                push_tag(&mut tags, &cap[1]);
+            }
+        }
+    }
+
+    tags
+}
+
+/// Tag name whose hierarchy has no empty level, e.g. `a/b` but not `/a`, `a/` or `a//b`.
+pub fn is_valid_tag(name: &str) -> bool {
+    !name.is_empty() && name.split('/').all(|level| !level.is_empty())
+}
+
+/// Replace `[[target#anchor|label]]` and `![[...]]` outside code.
+///
+/// The link is left as it is when `f` returns `None`.
+pub fn replace_wiki_links<F>(content: &str, mut f: F) -> String
+where
+    F: FnMut(&WikiLink) -> Option<String>,
+{
+    let re = Regex::new(r"(!?)\[\[([^\]|#]*)(?:#([^\]|]*))?(?:\|([^\]]*))?\]\]").unwrap();
+
+    let mut replaced = String::with_capacity(content.len());
+    for (code, text) in split_code(content) {
+        if code {
+            replaced.push_str(text);
+            continue;
+        }
+
+        let t = re.replace_all(text, |cap: &Captures| {
+            let link = WikiLink {
+                embed: !cap[1].is_empty(),
+                target: cap[2].trim().to_owned(),
+                anchor: cap.get(3).map(|a| a.as_str().to_owned()),
+                label: cap.get(4).map(|l| l.as_str().to_owned()),
+            };
+            f(&link).unwrap_or_else(|| cap[0].to_owned())
+        });
+        replaced.push_str(&t);
+    }
+
+    replaced
+}
+
+/// Replace url of `![alt](url)` outside code.
+///
+/// The image is left as it is when `f` returns `None`.
+pub fn replace_image_links<F>(content: &str, mut f: F) -> String
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let re = Regex::new(r"!\[([^\]]*)\]\(([^)\s]+)\)").unwrap();
+
+    let mut replaced = String::with_capacity(content.len());
+    for (code, text) in split_code(content) {
+        if code {
+            replaced.push_str(text);
+            continue;
+        }
+
+        let t = re.replace_all(text, |cap: &Captures| match f(&cap[2]) {
+            Some(url) => format!("![{}]({url})", &cap[1]),
+            None => cap[0].to_owned(),
+        });
+        replaced.push_str(&t);
+    }
+
+    replaced
+}
+
+fn is_heading(line: &str) -> bool {
+    let l = line.trim_start();
+    let level = l.chars().take_while(|c| c == &'#').count();
+    (1..=6).contains(&level) && (l.len() == level || l[level..].starts_with([' ', '\t']))
+}
+
+fn push_tag(tags: &mut Vec<String>, tag: &str) {
+    // Number like `#123` is not tag, also with separators like `#2024-01`.
+    let tag = tag.trim().trim_matches(['"', '\'']).trim_start_matches('#');
+    // Slash at end of sentence like `#todo/` is not part of tag.
+    let tag = tag.trim_end_matches('/');
+    if !is_valid_tag(tag) || !tag.chars().any(char::is_alphabetic) {
+        return;
+    }
+
+    if !tags.iter().any(|t| t == tag) {
+        tags.push(tag.to_owned());
+    }
+}
+
+/// Split content into fenced code blocks, inline code spans and the others.
+fn split_code(content: &str) -> Vec<(bool, &str)> {
+    let mut segments = vec![];
+
+    let mut start = 0;
+    let mut offset = 0;
+    let mut fence: Option<String> = None;
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if let Some(f) = &fence {
+            if trimmed.trim_end() == f {
+                segments.push((true, &content[start..offset + line.len()]));
+                start = offset + line.len();
+                fence = None;
+            }
+        } else if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            let mark = trimmed.chars().next().unwrap();
+            let f = trimmed
+                .chars()
+                .take_while(|c| c == &mark)
+                .collect::<String>();
+            split_code_span(&content[start..offset], &mut segments);
+            start = offset;
+            fence = Some(f);
+        }
+        offset += line.len();
+    }
+
+    if fence.is_some() {
+        segments.push((true, &content[start..]));
+    } else {
+        split_code_span(&content[start..], &mut segments);
+    }
+
+    segments
+}
+
+fn split_code_span<'a>(text: &'a str, segments: &mut Vec<(bool, &'a str)>) {
+    let mut rest = text;
+    while let Some(begin) = rest.find('`') {
+        let ticks = rest[begin..].chars().take_while(|c| c == &'`').count();
+        let after = &rest[begin + ticks..];
+        let Some(end) = after.find(&"`".repeat(ticks)) else {
+            break;
+        };
+
+        let span_end = begin + ticks + end + ticks;
+        if begin > 0 {
+            segments.push((false, &rest[..begin]));
+        }
+        segments.push((true, &rest[begin..span_end]));
+        rest = &rest[span_end..];
+    }
+
+    if !rest.is_empty() {
+        segments.push((false, rest));
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_front_matter() {
+        let (f, b) = split_front_matter("---\ntags: a\n---\nbody\n");
+        assert_eq!(Some("tags: a\n"), f);
+        assert_eq!("body\n", b);
+    }
+
+    #[test]
+    fn test_split_front_matter_none() {
+        let (f, b) = split_front_matter("body\n---\n");
+        assert!(f.is_none());
+        assert_eq!("body\n---\n", b);
+
+        let (f, b) = split_front_matter("---\nnot closed\n");
+        assert!(f.is_none());
+        assert_eq!("---\nnot closed\n", b);
+    }
+
+    #[test]
+    fn test_front_matter_tags_inline() {
+        let tags = front_matter_tags("title: x\ntags: [a, \"b\", #c]\n");
+        assert_eq!(vec!["a", "b", "c"], tags);
+
+        let tags = front_matter_tags("tag: a\n");
+        assert_eq!(vec!["a"], tags);
+    }
+
+    #[test]
+    fn test_front_matter_tags_list() {
+        let tags = front_matter_tags("tags:\n  - a\n  - b/c\nalias: x\n");
+        assert_eq!(vec!["a", "b/c"], tags);
+    }
+
+    #[test]
+    fn test_inline_tags() {
+        let tags = inline_tags("#head\n# head\ntext #a and #b/c, #1 #a\n(#d) x#e");
+        assert_eq!(vec!["head", "a", "b/c", "d"], tags);
+
+        let tags = inline_tags("#123 #１２ #2024-01 #1/2 #_ #y1984 #1st");
+        assert_eq!(vec!["y1984", "1st"], tags);
+
+        let tags = inline_tags("#todo/ #/a #a//b #b/c//");
+        assert_eq!(vec!["todo", "b/c"], tags);
+    }
+
+    #[test]
+    fn test_is_valid_tag() {
+        assert!(is_valid_tag("a"));
+        assert!(is_valid_tag("a/b c"));
+        assert!(!is_valid_tag(""));
+        assert!(!is_valid_tag("/a"));
+        assert!(!is_valid_tag("a/"));
+        assert!(!is_valid_tag("a//b"));
+    }
+
+    #[test]
+    fn test_inline_tags_skip_code() {
+        let tags = inline_tags("```\n#a\n```\n`#b` #c\n~~~~\n#d\n");
+        assert_eq!(vec!["c"], tags);
+    }
+
+    #[test]
+    fn test_replace_wiki_links() {
+        let c = replace_wiki_links("[[a]] ![[b.png]] [[c#h|l]] `[[d]]`", |l| {
+            Some(format!(
+                "{}:{}:{}:{}",
+                l.embed,
+                l.target,
+                l.anchor.clone().unwrap_or_default(),
+                l.label.clone().unwrap_or_default()
+            ))
+        });
+        assert_eq!("false:a:: true:b.png:: false:c:h:l `[[d]]`", c);
+    }
+
+    #[test]
+    fn test_replace_wiki_links_none() {
+        let c = replace_wiki_links("[[a]] [[b]]", |l| {
+            if l.target == "a" {
+                Some("A".to_owned())
+            } else {
+                None
+            }
+        });
+        assert_eq!("A [[b]]", c);
+    }
+
+    #[test]
+    fn test_replace_image_links() {
+        let c = replace_image_links("![x](a.png) ![y](http://b)", |u| {
+            if u.starts_with("http") {
+                None
+            } else {
+                Some(format!("img/{u}"))
+            }
+        });
+        assert_eq!("![x](img/a.png) ![y](http://b)", c);
+    }
+
+    #[test]
+    fn test_wiki_link_format() {
+        let l = WikiLink {
+            embed: true,
+            target: "a".to_owned(),
+            anchor: Some("h".to_owned()),
+            label: Some("l".to_owned()),
+        };
+        assert_eq!("![[a#h|l]]", l.format());
+    }
+}
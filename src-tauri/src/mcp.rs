@@ -0,0 +1,438 @@
+use crate::api::{
+    append_memo_fn, create_memo_fn, find_topic_fn, get_all_tags_fn, get_memo_fn, get_memo_tag_fn,
+    get_topics_fn,
+};
+use crate::error::Error;
+use crate::{logger, AppData};
+use chrono::Local;
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+use uuid::Uuid;
+
+const PROTOCOL_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: &str) -> Self {
+        RpcError {
+            code,
+            message: message.to_owned(),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+/// Serve Model Context Protocol reading one JSON-RPC message per line until EOF.
+pub fn serve(data: &AppData, input: &mut dyn BufRead, output: &mut dyn Write) -> Result<(), Error> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            break;
+        }
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(response) = handle(data, &line) {
+            writeln!(output, "{response}")?;
+            output.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+fn handle(data: &AppData, line: &str) -> Option<Value> {
+    let message: Value = match serde_json::from_str(line) {
+        Ok(m) => m,
+        Err(e) => {
+            return Some(error_response(
+                Value::Null,
+                RpcError::new(PARSE_ERROR, &e.to_string()),
+            ))
+        }
+    };
+
+    let id = message.get("id").cloned();
+    let Some(method) = message.get("method").and_then(Value::as_str) else {
+        // Responses from client are not expected.
+        return id.map(|i| error_response(i, RpcError::new(INVALID_REQUEST, "no method")));
+    };
+
+    let params = message.get("params").cloned().unwrap_or(json!({}));
+    let result = dispatch(data, method, &params);
+    if let Err(e) = &result {
+        log::warn!("mcp method={method} code={} error={}", e.code, e.message);
+    }
+
+    // Notification requires no response.
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(e) => error_response(id, e),
+    })
+}
+
+fn dispatch(data: &AppData, method: &str, params: &Value) -> Result<Value, RpcError> {
+    match method {
+        "initialize" => {
+            let requested = params.get("protocolVersion").and_then(Value::as_str);
+            let version = requested
+                .filter(|v| PROTOCOL_VERSIONS.contains(v))
+                .unwrap_or(PROTOCOL_VERSIONS[0]);
+            Ok(json!({
+                "protocolVersion": version,
+                "capabilities": {"tools": {}},
+                "serverInfo": {"name": "markdown-memo", "version": env!("CARGO_PKG_VERSION")},
+            }))
+        }
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({"tools": tools()})),
+        "tools/call" => {
+            let name = params
+                .get("name")
+                .and_then(Value::as_str)
+                .ok_or_else(|| RpcError::new(INVALID_PARAMS, "no tool name"))?;
+            let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+            let result = match call_tool(data, name, &arguments) {
+                Ok(text) => json!({"content": [{"type": "text", "text": text}], "isError": false}),
+                Err(ToolError::Rpc(e)) => return Err(e),
+                Err(ToolError::App(e)) => {
+                    log::error!("mcp tool={name} error={}", logger::error_chain(&e));
+                    let text = e.to_string();
+                    json!({"content": [{"type": "text", "text": text}], "isError": true})
+                }
+            };
+            Ok(result)
+        }
+        m if m.starts_with("notifications/") => Ok(Value::Null),
+        m => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            &format!("unknown method: {m}"),
+        )),
+    }
+}
+
+fn tools() -> Value {
+    json!([
+        {
+            "name": "search",
+            "description": "Search topics using keywords or #tags. Empty query lists all topics.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {"query": {"type": "string"}},
+                "required": ["query"],
+            },
+        },
+        {
+            "name": "read_topic",
+            "description": "Read markdown content of topic.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "topic": {"type": "string", "description": "Topic id or title"},
+                    "id": {"type": "string", "description": "Memo id in history (default: latest)"},
+                },
+                "required": ["topic"],
+            },
+        },
+        {
+            "name": "list_tags",
+            "description": "List tags of topic, or all tags if topic is omitted.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {"topic": {"type": "string", "description": "Topic id or title"}},
+            },
+        },
+        {
+            "name": "append_memo",
+            "description": "Append markdown text to topic. New topic is created if not found.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "topic": {"type": "string", "description": "Topic id or title"},
+                    "text": {"type": "string"},
+                    "heading": {"type": "boolean", "description": "Put timestamp heading"},
+                },
+                "required": ["text"],
+            },
+        },
+        {
+            "name": "create_memo",
+            "description": "Save markdown content as new revision of topic, or as new topic.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "topic": {"type": "string", "description": "Topic id or title"},
+                    "content": {"type": "string"},
+                },
+                "required": ["content"],
+            },
+        },
+    ])
+}
+
+enum ToolError {
+    Rpc(RpcError),
+    App(Error),
+}
+
+impl From<Error> for ToolError {
+    fn from(error: Error) -> Self {
+        ToolError::App(error)
+    }
+}
+
+fn call_tool(data: &AppData, name: &str, arguments: &Value) -> Result<String, ToolError> {
+    let text = match name {
+        "search" => {
+            let query = required(arguments, "query")?;
+            to_json(&get_topics_fn(data, query)?)
+        }
+        "read_topic" => {
+            let topic = find_topic_fn(data, required(arguments, "topic")?)?;
+            let id = optional(arguments, "id");
+            get_memo_fn(data, &topic.id, id)?.content
+        }
+        "list_tags" => {
+            let tags: Vec<String> = match optional(arguments, "topic") {
+                Some(t) => get_memo_tag_fn(data, &find_topic_fn(data, t)?.id)?
+                    .into_iter()
+                    .map(|t| t.name)
+                    .collect(),
+                None => get_all_tags_fn(data)?.into_iter().map(|t| t.name).collect(),
+            };
+            to_json(&tags)
+        }
+        "append_memo" => {
+            let text = required(arguments, "text")?;
+            let topic = optional(arguments, "topic").unwrap_or_default();
+            let heading = arguments
+                .get("heading")
+                .and_then(Value::as_bool)
+                .unwrap_or_default()
+                .then(|| Local::now().format("%Y-%m-%d %H:%M").to_string());
+            let memo = append_memo_fn(data, topic, text, heading.as_deref())?;
+            to_json(&json!({"topic_id": memo.topic_id, "id": memo.id}))
+        }
+        "create_memo" => {
+            let content = required(arguments, "content")?;
+            let topic_id = match optional(arguments, "topic") {
+                Some(t) => find_topic_fn(data, t)?.id,
+                None => Uuid::new_v4().to_string(),
+            };
+            let memo = create_memo_fn(data, &topic_id, content)?;
+            to_json(&json!({"topic_id": memo.topic_id, "id": memo.id}))
+        }
+        n => {
+            let message = format!("unknown tool: {n}");
+            return Err(ToolError::Rpc(RpcError::new(INVALID_PARAMS, &message)));
+        }
+    };
+
+    Ok(text)
+}
+
+fn required<'a>(arguments: &'a Value, name: &str) -> Result<&'a str, ToolError> {
+    optional(arguments, name).ok_or_else(|| {
+        let message = format!("missing argument: {name}");
+        ToolError::Rpc(RpcError::new(INVALID_PARAMS, &message))
+    })
+}
+
+fn optional<'a>(arguments: &'a Value, name: &str) -> Option<&'a str> {
+    arguments.get(name).and_then(Value::as_str)
+}
+
+fn to_json<T: serde::Serialize + ?Sized>(value: &T) -> String {
+    serde_json::to_string(value).unwrap()
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": error.code, "message": error.message},
+    })
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::testing::setup_connect;
+
+    #[test]
+    fn test_initialize() {
+        let data = setup_appdate();
+
+        let responses = serve_ok(
+            &data,
+            &[
+                r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2024-11-05"}}"#,
+                r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+                r#"{"jsonrpc":"2.0","id":2,"method":"ping"}"#,
+            ],
+        );
+        assert_eq!(2, responses.len());
+        assert_eq!(1, responses[0]["id"]);
+        assert_eq!("2024-11-05", responses[0]["result"]["protocolVersion"]);
+        assert!(responses[0]["result"]["capabilities"]["tools"].is_object());
+        assert_eq!(2, responses[1]["id"]);
+    }
+
+    #[test]
+    fn test_tools_list() {
+        let data = setup_appdate();
+
+        let responses = serve_ok(
+            &data,
+            &[r#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#],
+        );
+        let tools = responses[0]["result"]["tools"].as_array().unwrap();
+        assert_eq!(5, tools.len());
+        assert!(tools.iter().all(|t| t["inputSchema"]["type"] == "object"));
+    }
+
+    #[test]
+    fn test_search() {
+        let data = setup_appdate();
+
+        let text = call_ok(&data, "search", json!({"query": "#tag1"}));
+        let topics: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(1, topics.as_array().unwrap().len());
+        assert_eq!("t1", topics[0]["id"]);
+    }
+
+    #[test]
+    fn test_read_topic() {
+        let data = setup_appdate();
+
+        let text = call_ok(&data, "read_topic", json!({"topic": "title1"}));
+        assert_eq!("content1", text);
+
+        let text = call_ok(&data, "read_topic", json!({"topic": "t1", "id": "m1"}));
+        assert_eq!("content0", text);
+
+        let response = call(&data, "read_topic", json!({"topic": "t3"}));
+        assert_eq!(true, response["result"]["isError"]);
+
+        let response = call(&data, "read_topic", json!({}));
+        assert_eq!(INVALID_PARAMS, response["error"]["code"]);
+    }
+
+    #[test]
+    fn test_list_tags() {
+        let data = setup_appdate();
+
+        let text = call_ok(&data, "list_tags", json!({"topic": "t2"}));
+        assert_eq!(r#"["tag2"]"#, text);
+
+        let text = call_ok(&data, "list_tags", json!({}));
+        assert_eq!(r#"["tag1","tag2"]"#, text);
+    }
+
+    #[test]
+    fn test_append_memo() {
+        let data = setup_appdate();
+
+        call_ok(&data, "append_memo", json!({"topic": "t2", "text": "line"}));
+        let memo = get_memo_fn(&data, "t2", None).unwrap();
+        assert_eq!("content2\n\nline\n", memo.content);
+
+        let text = call_ok(&data, "append_memo", json!({"topic": "new", "text": "a"}));
+        let result: Value = serde_json::from_str(&text).unwrap();
+        let memo = get_memo_fn(&data, result["topic_id"].as_str().unwrap(), None).unwrap();
+        assert_eq!("# new\n\na\n", memo.content);
+    }
+
+    #[test]
+    fn test_create_memo() {
+        let data = setup_appdate();
+
+        call_ok(
+            &data,
+            "create_memo",
+            json!({"topic": "t2", "content": "# b"}),
+        );
+        let memo = get_memo_fn(&data, "t2", None).unwrap();
+        assert_eq!("# b", memo.content);
+
+        call_ok(&data, "create_memo", json!({"content": "# c"}));
+        assert_eq!(3, get_topics_fn(&data, "").unwrap().len());
+    }
+
+    #[test]
+    fn test_error() {
+        let data = setup_appdate();
+
+        let responses = serve_ok(
+            &data,
+            &[
+                "{",
+                r#"{"jsonrpc":"2.0","id":1,"method":"unknown"}"#,
+                r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"x"}}"#,
+            ],
+        );
+        assert_eq!(PARSE_ERROR, responses[0]["error"]["code"]);
+        assert_eq!(METHOD_NOT_FOUND, responses[1]["error"]["code"]);
+        assert_eq!(INVALID_PARAMS, responses[2]["error"]["code"]);
+    }
+
+    fn call_ok(data: &AppData, name: &str, arguments: Value) -> String {
+        let response = call(data, name, arguments);
+        assert_eq!(false, response["result"]["isError"]);
+        response["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .to_owned()
+    }
+
+    fn call(data: &AppData, name: &str, arguments: Value) -> Value {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {"name": name, "arguments": arguments},
+        });
+        serve_ok(data, &[&request.to_string()]).remove(0)
+    }
+
+    fn serve_ok(data: &AppData, lines: &[&str]) -> Vec<Value> {
+        let input = lines.join("\n");
+        let mut output = vec![];
+        serve(data, &mut input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    }
+
+    fn setup_appdate() -> AppData {
+        let conn = setup_connect();
+        db::Topic::create(&conn, "t1", "title1", 0).unwrap();
+        db::Topic::create(&conn, "t2", "title2", 1).unwrap();
+        db::Memo::create(&conn, "m1", "t1", 0, "content0").unwrap();
+        db::Memo::create(&conn, "m2", "t1", 1, "content1").unwrap();
+        db::Memo::create(&conn, "m3", "t2", 1, "content2").unwrap();
+        db::TopicTag::create(&conn, "tag1", "t1").unwrap();
+        db::TopicTag::create(&conn, "tag2", "t2").unwrap();
+
+        AppData::new(conn)
+    }
+}
@@ -14,4 +14,89 @@ pub struct Topic {
     pub id: String,
     pub title: String,
     pub timestamp: i64,
+    pub locked: bool,
+}
+
+#[derive(Serialize)]
+pub struct Backup {
+    pub name: String,
+    pub timestamp: i64,
+    pub size: u64,
+}
+
+#[derive(Serialize)]
+pub struct CheckReport {
+    /// Corruption found by `PRAGMA integrity_check`, which is never repaired.
+    pub integrity: Vec<String>,
+    /// Topics of memos without topic.
+    pub missing_topics: Vec<String>,
+    /// Topics of tags without topic nor memo.
+    pub orphan_tags: Vec<String>,
+    /// Topics without memo.
+    pub empty_topics: Vec<String>,
+    pub repaired: bool,
+}
+
+#[derive(Serialize)]
+pub struct DatabaseStats {
+    /// Bytes of database file.
+    pub size: i64,
+    /// Bytes of free pages reclaimed by vacuum.
+    pub free: i64,
+    pub topics: usize,
+    pub revisions: i64,
+    pub tables: Vec<TableSize>,
+    pub largest_topics: Vec<TopicSize>,
+}
+
+#[derive(Serialize)]
+pub struct TableSize {
+    pub name: String,
+    pub size: i64,
+}
+
+#[derive(Serialize)]
+pub struct Tag {
+    pub name: String,
+    /// Number of tagged topics.
+    pub count: i64,
+    /// Timestamp of the latest updated topic of tag. Time when tag was added is not recorded,
+    /// so it is not the time of that.
+    pub last_used: i64,
+}
+
+#[derive(Serialize)]
+pub struct TopicTag {
+    pub name: String,
+    /// Collected from `#tag` in content, removed when it disappears.
+    pub inline: bool,
+    pub color: Option<String>,
+    pub description: Option<String>,
+    pub aliases: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct TopicSize {
+    pub id: String,
+    pub title: String,
+    pub revisions: i64,
+    pub size: i64,
+}
+
+#[derive(Serialize)]
+pub struct Maintenance {
+    pub before: DatabaseStats,
+    /// Not maintained if `None`.
+    pub after: Option<DatabaseStats>,
+}
+
+#[derive(Serialize)]
+pub struct TrashMemo {
+    pub id: String,
+    pub topic_id: String,
+    pub title: String,
+    pub timestamp: i64,
+    pub deleted_at: i64,
+    /// Topic is also in trash, restored with the memo.
+    pub topic_deleted: bool,
 }
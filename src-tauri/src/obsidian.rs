@@ -0,0 +1,591 @@
+use crate::api::{parse_title, sync_inline_tags};
+use crate::error::Error;
+use crate::event::Event;
+use crate::markdown::{self, WikiLink};
+use crate::{db, AppData};
+use rusqlite::TransactionBehavior;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use uuid::Uuid;
+
+const ATTACHMENT_DIR: &str = "attachments";
+
+struct Note {
+    id: String,
+    timestamp: i64,
+    content: String,
+    tags: Vec<String>,
+    attachments: Vec<(String, PathBuf)>,
+}
+
+// -----------------------------------------------------------------------------------------------
+
+pub fn import_obsidian_fn(data: &AppData, vault: &Path) -> Result<usize, Error> {
+    let mut note_paths = vec![];
+    let mut file_paths = vec![];
+    collect_files(vault, &mut note_paths, &mut file_paths)?;
+
+    // Obsidian resolves link by file name or by path from vault root.
+    let mut ids = HashMap::new();
+    for path in &note_paths {
+        let id = Uuid::new_v4().to_string();
+        ids.insert(link_key(&relative_path(vault, path)), id.clone());
+        ids.entry(link_key(&file_stem(path))).or_insert(id);
+    }
+
+    let mut files = HashMap::new();
+    for path in &file_paths {
+        files.insert(link_key(&relative_path(vault, path)), path.clone());
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        files.entry(link_key(&name)).or_insert_with(|| path.clone());
+    }
+
+    let mut notes = vec![];
+    for path in &note_paths {
+        let id = ids[&link_key(&relative_path(vault, path))].clone();
+        let note = read_note(vault, path, id, &ids, &files)?;
+        notes.push(note);
+    }
+
+    let mut db = data.db.writer();
+    let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    for note in &notes {
+        let title = parse_title(&note.content);
+        db::Topic::create(&tx, &note.id, &title, note.timestamp)?;
+        Event::TopicCreated {
+            topic_id: note.id.clone(),
+            title,
+        }
+        .record(&tx)?;
+
+        let memo_id = Uuid::new_v4().to_string();
+        db::Memo::create(&tx, &memo_id, &note.id, note.timestamp, &note.content)?;
+        Event::MemoSaved {
+            topic_id: note.id.clone(),
+            memo_id,
+        }
+        .record(&tx)?;
+
+        // Inline tags follow content when enabled, or are added as tags from front matter.
+        let mut tags = note.tags.clone();
+        if !data.inline_tags {
+            for tag in markdown::inline_tags(&note.content) {
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+        }
+        for tag in &tags {
+            db::TopicTag::create(&tx, tag, &note.id)?;
+            Event::TagAdded {
+                topic_id: note.id.clone(),
+                tag: tag.clone(),
+            }
+            .record(&tx)?;
+        }
+        if data.inline_tags {
+            sync_inline_tags(&tx, &note.id, &note.content)?;
+        }
+
+        for (name, path) in &note.attachments {
+            let content = fs::read(path)?;
+            let attachment_id = Uuid::new_v4().to_string();
+            db::Attachment::create(&tx, &attachment_id, &note.id, name, &content)?;
+        }
+    }
+
+    tx.commit()?;
+    Ok(notes.len())
+}
+
+fn read_note(
+    vault: &Path,
+    path: &Path,
+    id: String,
+    ids: &HashMap<String, String>,
+    files: &HashMap<String, PathBuf>,
+) -> Result<Note, Error> {
+    let text = fs::read_to_string(path)?;
+    let timestamp = fs::metadata(path)?
+        .modified()
+        .unwrap_or_else(|_| SystemTime::now())
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+
+    let (front, body) = markdown::split_front_matter(&text);
+    let tags = front.map(markdown::front_matter_tags).unwrap_or_default();
+
+    let mut attachments: Vec<(String, PathBuf)> = vec![];
+    let mut attach = |path: &PathBuf| {
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        if !attachments.iter().any(|(n, _)| n == &name) {
+            attachments.push((name.clone(), path.clone()));
+        }
+        name
+    };
+
+    let content = markdown::replace_wiki_links(body, |link| {
+        let key = link_key(&link.target);
+        if let Some(topic_id) = ids.get(&key) {
+            let label = link.label.clone().unwrap_or_else(|| link.target.clone());
+            let l = WikiLink {
+                embed: link.embed,
+                target: topic_id.clone(),
+                anchor: link.anchor.clone(),
+                label: Some(label),
+            };
+            Some(l.format())
+        } else if let Some(file) = files.get(&key) {
+            let l = WikiLink {
+                embed: link.embed,
+                target: attach(file),
+                anchor: link.anchor.clone(),
+                label: link.label.clone(),
+            };
+            Some(l.format())
+        } else {
+            None
+        }
+    });
+
+    let note_dir = path.parent().unwrap_or(vault);
+    let content = markdown::replace_image_links(&content, |url| {
+        if url.contains("://") {
+            return None;
+        }
+
+        let url = url.replace("%20", " ");
+        let file = [note_dir.join(&url), vault.join(&url)]
+            .into_iter()
+            .find(|p| p.is_file())
+            .or_else(|| files.get(&link_key(&url)).cloned())?;
+        Some(attach(&file).replace(' ', "%20"))
+    });
+
+    Ok(Note {
+        id,
+        timestamp,
+        content,
+        tags,
+        attachments,
+    })
+}
+
+fn collect_files(
+    dir: &Path,
+    notes: &mut Vec<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> Result<(), Error> {
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            // Skip `.obsidian` and `.trash`.
+            continue;
+        }
+
+        if path.is_dir() {
+            collect_files(&path, notes, files)?;
+        } else if path.extension().is_some_and(|e| e == "md") {
+            notes.push(path);
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem().unwrap().to_string_lossy().to_string()
+}
+
+fn link_key(target: &str) -> String {
+    let key = target.replace('\\', "/").to_lowercase();
+    key.strip_suffix(".md").unwrap_or(&key).to_owned()
+}
+
+fn relative_path(vault: &Path, path: &Path) -> String {
+    path.strip_prefix(vault)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string()
+}
+
+// -----------------------------------------------------------------------------------------------
+
+pub fn export_obsidian_fn(data: &AppData, vault: &Path, keyword: &str) -> Result<usize, Error> {
+    let db = data.db.reader();
+    let mut topics = if keyword.is_empty() {
+        db::Topic::all(&db)
+    } else {
+        db::Topic::search(&db, keyword)
+    }?;
+    // Links are resolved also into topics not exported.
+    let all = db::Topic::all(&db)?;
+
+    // Locked topics are never exported.
+    let locked = db::TopicLock::all(&db)?;
+    let is_locked = |id: &str| locked.iter().any(|l| l.topic_id == id);
+    topics.retain(|t| !is_locked(&t.id));
+
+    // Existing files in vault are not overwritten.
+    let mut used = file_names(vault)?
+        .into_iter()
+        .filter_map(|n| n.strip_suffix(".md").map(str::to_owned))
+        .collect();
+    let mut stems = HashMap::new();
+    for topic in &topics {
+        let stem = unique_name(&sanitize_file_name(&topic.title), "", &mut used);
+        stems.insert(topic.id.clone(), stem);
+    }
+
+    fs::create_dir_all(vault.join(ATTACHMENT_DIR))?;
+
+    let mut used_files = file_names(&vault.join(ATTACHMENT_DIR))?;
+    let mut count = 0;
+    for topic in &topics {
+        let Ok(memo) = db::Memo::latest_by_topic(&db, &topic.id) else {
+            continue;
+        };
+
+        let mut renamed = HashMap::new();
+        for attachment in db::Attachment::all_by_topic(&db, &topic.id)? {
+            let name = sanitize_file_name(&attachment.name);
+            let (stem, ext) = match name.rsplit_once('.') {
+                Some((s, e)) => (s.to_owned(), format!(".{e}")),
+                None => (name.clone(), "".to_owned()),
+            };
+            let file_name = unique_name(&stem, &ext, &mut used_files);
+            fs::write(
+                vault.join(ATTACHMENT_DIR).join(&file_name),
+                &attachment.content,
+            )?;
+            renamed.insert(attachment.name, file_name);
+        }
+
+        let content = markdown::replace_wiki_links(&memo.content, |link| {
+            if let Some(file_name) = renamed.get(&link.target) {
+                let l = WikiLink {
+                    embed: link.embed,
+                    target: file_name.clone(),
+                    anchor: link.anchor.clone(),
+                    label: link.label.clone(),
+                };
+                return Some(l.format());
+            }
+
+            let t = db::Topic::resolve(&all, &link.target)?;
+            let Some(stem) = stems.get(&t.id) else {
+                // Topic not exported is left as plain text, not to leak its id.
+                let label = match &link.label {
+                    Some(label) => label.clone(),
+                    None if is_locked(&t.id) => "(locked)".to_owned(),
+                    None => t.title.clone(),
+                };
+                return Some(label);
+            };
+            let l = WikiLink {
+                embed: link.embed,
+                target: stem.clone(),
+                anchor: link.anchor.clone(),
+                label: link.label.clone().filter(|l| l != stem),
+            };
+            Some(l.format())
+        });
+
+        let content = markdown::replace_image_links(&content, |url| {
+            let file_name = renamed.get(&url.replace("%20", " "))?;
+            Some(format!(
+                "{ATTACHMENT_DIR}/{}",
+                file_name.replace(' ', "%20")
+            ))
+        });
+
+        let inline_tags = markdown::inline_tags(&content);
+        let tags = db::TopicTag::all_by_topic(&db, &topic.id)?
+            .into_iter()
+            .filter(|t| !inline_tags.contains(t))
+            .collect::<Vec<String>>();
+
+        let mut text = String::new();
+        if !tags.is_empty() {
+            text.push_str("---\ntags:\n");
+            for tag in tags {
+                text.push_str(&format!("  - {tag}\n"));
+            }
+            text.push_str("---\n");
+        }
+        text.push_str(&content);
+
+        fs::write(vault.join(format!("{}.md", stems[&topic.id])), text)?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+pub(crate) fn sanitize_file_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || "\\/:*?\"<>|#^[]".contains(c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect::<String>();
+
+    let name = name.trim().trim_end_matches('.');
+    if name.is_empty() {
+        "Untitled".to_owned()
+    } else {
+        name.to_owned()
+    }
+}
+
+/// Lowercase names of files in directory, which may not exist.
+fn file_names(dir: &Path) -> Result<HashSet<String>, Error> {
+    let mut names = HashSet::new();
+    if dir.is_dir() {
+        for entry in fs::read_dir(dir)? {
+            names.insert(entry?.file_name().to_string_lossy().to_lowercase());
+        }
+    }
+
+    Ok(names)
+}
+
+fn unique_name(stem: &str, ext: &str, used: &mut HashSet<String>) -> String {
+    let mut name = format!("{stem}{ext}");
+    let mut n = 1;
+    while used.contains(&name.to_lowercase()) {
+        n += 1;
+        name = format!("{stem} {n}{ext}");
+    }
+
+    used.insert(name.to_lowercase());
+    name
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{create_memo_fn, find_topic_fn, get_memo_tag_fn};
+    use crate::testing::{setup_appdate, temp_dir};
+
+    #[test]
+    fn test_import_obsidian_fn() {
+        let data = setup_appdate();
+        let vault = setup_vault();
+
+        let count = import_obsidian_fn(&data, &vault).unwrap();
+        assert_eq!(2, count);
+
+        {
+            let conn = data.db.writer();
+
+            let topics = db::Topic::all(&conn).unwrap();
+            assert_eq!(2, topics.len());
+            let a = topics.iter().find(|t| t.title == "Note A").unwrap();
+            let b = topics.iter().find(|t| t.title == "Note B").unwrap();
+
+            let memo = db::Memo::latest_by_topic(&conn, &a.id).unwrap();
+            assert_eq!(
+                format!("# Note A\nsee [[{}|Note B]] ![[img.png]] #inline\n", b.id),
+                memo.content
+            );
+
+            let tags = db::TopicTag::all_by_topic(&conn, &a.id).unwrap();
+            assert_eq!(vec!["fm".to_owned(), "inline".to_owned()], tags);
+
+            let attachments = db::Attachment::all_by_topic(&conn, &a.id).unwrap();
+            assert_eq!(1, attachments.len());
+            assert_eq!("img.png", attachments[0].name);
+            assert_eq!(b"img".to_vec(), attachments[0].content);
+
+            let memo = db::Memo::latest_by_topic(&conn, &b.id).unwrap();
+            assert_eq!(
+                format!("# Note B\n![x](pic%201.png) [[{}|alias]]\n", a.id),
+                memo.content
+            );
+
+            let attachments = db::Attachment::all_by_topic(&conn, &b.id).unwrap();
+            assert_eq!(1, attachments.len());
+            assert_eq!("pic 1.png", attachments[0].name);
+        }
+
+        fs::remove_dir_all(vault).unwrap();
+    }
+
+    #[test]
+    fn test_import_obsidian_fn_inline_tags() {
+        let data = AppData {
+            inline_tags: true,
+            ..setup_appdate()
+        };
+        let vault = setup_vault();
+        import_obsidian_fn(&data, &vault).unwrap();
+
+        let topic = find_topic_fn(&data, "Note A").unwrap();
+        let tags = get_memo_tag_fn(&data, &topic.id).unwrap();
+        let tags: Vec<_> = tags.iter().map(|t| (t.name.as_str(), t.inline)).collect();
+        assert_eq!(vec![("fm", false), ("inline", true)], tags);
+
+        // Inline tag is removed with text.
+        create_memo_fn(&data, &topic.id, "# Note A\n").unwrap();
+        let tags = get_memo_tag_fn(&data, &topic.id).unwrap();
+        assert_eq!(vec!["fm"], tags.iter().map(|t| &t.name).collect::<Vec<_>>());
+
+        fs::remove_dir_all(vault).unwrap();
+    }
+
+    #[test]
+    fn test_import_obsidian_fn_rollback() {
+        let data = setup_appdate();
+        let vault = setup_vault();
+        {
+            let conn = data.db.writer();
+            let sql = "CREATE TEMP TRIGGER failure BEFORE INSERT ON attachment \
+                       BEGIN SELECT RAISE(ABORT, 'injected'); END";
+            conn.execute(sql, []).unwrap();
+        }
+
+        assert!(import_obsidian_fn(&data, &vault).is_err());
+
+        {
+            let conn = data.db.writer();
+            assert_eq!(0, db::Topic::all(&conn).unwrap().len());
+            assert_eq!(0, db::TopicTag::all(&conn).unwrap().len());
+        }
+
+        fs::remove_dir_all(vault).unwrap();
+    }
+
+    #[test]
+    fn test_export_obsidian_fn() {
+        let data = setup_appdate();
+        let vault = setup_vault();
+        import_obsidian_fn(&data, &vault).unwrap();
+
+        let export = temp_dir();
+        let count = export_obsidian_fn(&data, &export, "").unwrap();
+        assert_eq!(2, count);
+
+        let a = fs::read_to_string(export.join("Note A.md")).unwrap();
+        assert_eq!(
+            "---\ntags:\n  - fm\n---\n# Note A\nsee [[Note B]] ![[img.png]] #inline\n",
+            a
+        );
+
+        let b = fs::read_to_string(export.join("Note B.md")).unwrap();
+        assert_eq!(
+            "# Note B\n![x](attachments/pic%201.png) [[Note A|alias]]\n",
+            b
+        );
+
+        let img = fs::read(export.join("attachments").join("img.png")).unwrap();
+        assert_eq!(b"img".to_vec(), img);
+
+        let pic = fs::read(export.join("attachments").join("pic 1.png")).unwrap();
+        assert_eq!(b"pic".to_vec(), pic);
+
+        fs::remove_dir_all(vault).unwrap();
+        fs::remove_dir_all(export).unwrap();
+    }
+
+    #[test]
+    fn test_export_obsidian_fn_keyword() {
+        let data = setup_appdate();
+        let vault = setup_vault();
+        import_obsidian_fn(&data, &vault).unwrap();
+
+        let export = temp_dir();
+        let count = export_obsidian_fn(&data, &export, "#fm").unwrap();
+        assert_eq!(1, count);
+
+        // Link to topic not exported is left as plain text.
+        let a = fs::read_to_string(export.join("Note A.md")).unwrap();
+        assert_eq!(
+            "---\ntags:\n  - fm\n---\n# Note A\nsee Note B ![[img.png]] #inline\n",
+            a
+        );
+        assert!(!export.join("Note B.md").exists());
+
+        fs::remove_dir_all(vault).unwrap();
+        fs::remove_dir_all(export).unwrap();
+    }
+
+    #[test]
+    fn test_export_obsidian_fn_existing() {
+        let data = setup_appdate();
+        let vault = setup_vault();
+        import_obsidian_fn(&data, &vault).unwrap();
+
+        let export = temp_dir();
+        fs::create_dir_all(export.join(ATTACHMENT_DIR)).unwrap();
+        fs::write(export.join("note a.md"), "mine").unwrap();
+        fs::write(export.join(ATTACHMENT_DIR).join("img.png"), "mine").unwrap();
+
+        let count = export_obsidian_fn(&data, &export, "").unwrap();
+        assert_eq!(2, count);
+
+        let mine = fs::read_to_string(export.join("note a.md")).unwrap();
+        assert_eq!("mine", mine);
+        let mine = fs::read_to_string(export.join(ATTACHMENT_DIR).join("img.png")).unwrap();
+        assert_eq!("mine", mine);
+
+        let a = fs::read_to_string(export.join("Note A 2.md")).unwrap();
+        assert!(a.contains("see [[Note B]] ![[img 2.png]] "));
+        let b = fs::read_to_string(export.join("Note B.md")).unwrap();
+        assert!(b.contains(" [[Note A 2|alias]]"));
+
+        fs::remove_dir_all(vault).unwrap();
+        fs::remove_dir_all(export).unwrap();
+    }
+
+    #[test]
+    fn test_sanitize_file_name() {
+        assert_eq!("a_b_c", sanitize_file_name("a/b:c"));
+        assert_eq!("Untitled", sanitize_file_name(" . "));
+    }
+
+    #[test]
+    fn test_unique_name() {
+        let mut used = HashSet::new();
+        assert_eq!("a.png", unique_name("a", ".png", &mut used));
+        assert_eq!("A 2.png", unique_name("A", ".png", &mut used));
+        assert_eq!("a 3.png", unique_name("a", ".png", &mut used));
+    }
+
+    fn setup_vault() -> PathBuf {
+        let vault = temp_dir();
+        fs::create_dir_all(vault.join(".obsidian")).unwrap();
+        fs::create_dir_all(vault.join("sub")).unwrap();
+        fs::create_dir_all(vault.join(ATTACHMENT_DIR)).unwrap();
+
+        fs::write(vault.join(".obsidian").join("app.json"), "{}").unwrap();
+        fs::write(
+            vault.join("Note A.md"),
+            "---\ntags: [fm]\n---\n# Note A\nsee [[Note B]] ![[img.png]] #inline\n",
+        )
+        .unwrap();
+        fs::write(
+            vault.join("sub").join("Note B.md"),
+            "# Note B\n![x](../attachments/pic%201.png) [[Note A|alias]]\n",
+        )
+        .unwrap();
+        fs::write(vault.join(ATTACHMENT_DIR).join("img.png"), "img").unwrap();
+        fs::write(vault.join(ATTACHMENT_DIR).join("pic 1.png"), "pic").unwrap();
+
+        vault
+    }
+}
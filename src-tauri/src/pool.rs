@@ -0,0 +1,208 @@
+use rusqlite::Connection;
+use std::ops::Deref;
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+
+/// Dedicated writer connection and read-only connections for concurrent queries.
+///
+/// Without readers, e.g. in-memory database, reads are served by the writer.
+pub struct Pool {
+    writer: Mutex<Connection>,
+    readers: Mutex<Vec<Connection>>,
+    available: Condvar,
+    size: usize,
+}
+
+pub enum ReadConnection<'a> {
+    Pooled(&'a Pool, Option<Connection>),
+    Writer(MutexGuard<'a, Connection>),
+}
+
+impl Deref for ReadConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        match self {
+            ReadConnection::Pooled(_, conn) => conn.as_ref().unwrap(),
+            ReadConnection::Writer(conn) => conn,
+        }
+    }
+}
+
+impl Drop for ReadConnection<'_> {
+    fn drop(&mut self) {
+        if let ReadConnection::Pooled(pool, conn) = self {
+            if let Some(conn) = conn.take() {
+                rollback(&conn);
+                lock(&pool.readers).push(conn);
+                pool.available.notify_one();
+            }
+        }
+    }
+}
+
+impl Pool {
+    pub fn new(writer: Connection, readers: Vec<Connection>) -> Self {
+        Pool {
+            writer: Mutex::new(writer),
+            size: readers.len(),
+            readers: Mutex::new(readers),
+            available: Condvar::new(),
+        }
+    }
+
+    /// Wait until the writer is released by other thread.
+    pub fn writer(&self) -> MutexGuard<'_, Connection> {
+        let conn = lock(&self.writer);
+        rollback(&conn);
+        conn
+    }
+
+    /// Wait until one of readers is released by other thread.
+    pub fn reader(&self) -> ReadConnection<'_> {
+        if self.size == 0 {
+            return ReadConnection::Writer(self.writer());
+        }
+
+        let mut readers = lock(&self.readers);
+        loop {
+            if let Some(conn) = readers.pop() {
+                return ReadConnection::Pooled(self, Some(conn));
+            }
+
+            readers = self
+                .available
+                .wait(readers)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+}
+
+/// Roll back transaction left open, e.g. raw `BEGIN` interrupted by panic.
+///
+/// `rusqlite::Transaction` rolls back when dropped, but a raw `BEGIN` does not.
+fn rollback(conn: &Connection) {
+    if !conn.is_autocommit() {
+        log::warn!("rollback open transaction");
+        let _ = conn.execute_batch("ROLLBACK");
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // Connection is usable after panic, open transaction is rolled back by `rollback`.
+    mutex.lock().unwrap_or_else(|e| {
+        log::warn!("recover poisoned lock");
+        e.into_inner()
+    })
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{create_memo_fn, get_memo_fn, get_topics_fn};
+    use crate::testing::{remove_db, temp_db_path};
+    use crate::AppData;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_reader_in_memory() {
+        let pool = Pool::new(Connection::open_in_memory().unwrap(), vec![]);
+        pool.writer()
+            .execute("CREATE TABLE a (b TEXT)", [])
+            .unwrap();
+
+        let reader = pool.reader();
+        let count: i64 = reader
+            .query_row("SELECT COUNT(*) FROM a", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(0, count);
+    }
+
+    #[test]
+    fn test_reader_not_block_writer() {
+        let path = temp_db_path();
+        let data = AppData::open(Some(&path), false, None, None).unwrap();
+
+        let reader = data.db.reader();
+        reader.execute_batch("BEGIN").unwrap();
+        let count: i64 = reader
+            .query_row("SELECT COUNT(*) FROM memo", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(0, count);
+
+        create_memo_fn(&data, "t1", "# a").unwrap();
+
+        // Snapshot of reader is not changed in transaction.
+        let count: i64 = reader
+            .query_row("SELECT COUNT(*) FROM memo", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(0, count);
+        reader.execute_batch("COMMIT").unwrap();
+        drop(reader);
+
+        // Transaction left open is rolled back when returned to pool.
+        let reader = data.db.reader();
+        reader.execute_batch("BEGIN").unwrap();
+        drop(reader);
+        assert!(data.db.reader().is_autocommit());
+
+        assert_eq!("# a", get_memo_fn(&data, "t1", None).unwrap().content);
+
+        remove_db(&path);
+    }
+
+    #[test]
+    fn test_concurrent_read_write() {
+        let path = temp_db_path();
+        let data = AppData::open(Some(&path), false, None, None).unwrap();
+
+        let mut handles = vec![];
+        for w in 0..4 {
+            let data = data.clone();
+            handles.push(thread::spawn(move || {
+                for i in 0..20 {
+                    let topic_id = format!("t{w}-{i}");
+                    create_memo_fn(&data, &topic_id, &format!("# {topic_id}")).unwrap();
+                }
+            }));
+        }
+
+        for _ in 0..8 {
+            let data = data.clone();
+            handles.push(thread::spawn(move || {
+                for _ in 0..20 {
+                    let topics = get_topics_fn(&data, "").unwrap();
+                    assert!(topics.len() <= 80);
+                }
+            }));
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(80, get_topics_fn(&data, "").unwrap().len());
+
+        remove_db(&path);
+    }
+
+    #[test]
+    fn test_recover_poisoned() {
+        let pool = Arc::new(Pool::new(Connection::open_in_memory().unwrap(), vec![]));
+
+        let p = pool.clone();
+        let _ = thread::spawn(move || {
+            let conn = p.writer();
+            conn.execute_batch("BEGIN; CREATE TABLE a (b TEXT);")
+                .unwrap();
+            panic!("poison");
+        })
+        .join();
+
+        let conn = pool.writer();
+        assert!(conn.is_autocommit());
+        conn.execute("CREATE TABLE a (b TEXT)", []).unwrap();
+    }
+}
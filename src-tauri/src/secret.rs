@@ -0,0 +1,167 @@
+use crate::error::Error;
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{Key as CipherKey, XChaCha20Poly1305, XNonce};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+const PREFIX: &str = "locked:";
+const NONCE_LEN: usize = 24;
+pub const SALT_LEN: usize = 16;
+
+/// Key derived from passphrase of locked topic, which is not cloned not to leave copy of it.
+pub struct Key([u8; 32]);
+
+impl Key {
+    pub fn derive(passphrase: &str, salt: &[u8]) -> Result<Self, Error> {
+        // Derived into key directly not to leave copy of it.
+        let mut key = Key([0; 32]);
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key.0)
+            .map_err(|e| Error::Internal(e.to_string()))?;
+        Ok(key)
+    }
+
+    /// Encrypt text as `locked:<hex of nonce and ciphertext>`.
+    pub fn encrypt(&self, text: &str) -> Result<String, Error> {
+        let mut encrypted = PREFIX.to_owned();
+        for b in self.encrypt_bytes(text.as_bytes())? {
+            encrypted.push_str(&format!("{b:02x}"));
+        }
+        Ok(encrypted)
+    }
+
+    /// Decrypt text, which fails with `Error::Passphrase` if key does not match.
+    pub fn decrypt(&self, encrypted: &str) -> Result<String, Error> {
+        let bytes = encrypted
+            .strip_prefix(PREFIX)
+            .and_then(decode_hex)
+            .ok_or_else(|| Error::Internal("invalid locked content".to_owned()))?;
+
+        let text = self.decrypt_bytes(&bytes)?;
+        String::from_utf8(text).map_err(|e| Error::Internal(e.to_string()))
+    }
+
+    /// Encrypt binary like attachment as nonce followed by ciphertext.
+    pub fn encrypt_bytes(&self, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        let nonce = random::<NONCE_LEN>()?;
+        let cipher = XChaCha20Poly1305::new(CipherKey::from_slice(&self.0));
+        let ciphertext = cipher
+            .encrypt(XNonce::from_slice(&nonce), bytes)
+            .map_err(|_| Error::Internal("encrypt".to_owned()))?;
+
+        Ok([nonce.as_slice(), &ciphertext].concat())
+    }
+
+    pub fn decrypt_bytes(&self, encrypted: &[u8]) -> Result<Vec<u8>, Error> {
+        if encrypted.len() < NONCE_LEN {
+            return Err(Error::Internal("invalid locked content".to_owned()));
+        }
+
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+        let cipher = XChaCha20Poly1305::new(CipherKey::from_slice(&self.0));
+        cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| Error::Passphrase)
+    }
+}
+
+impl Drop for Key {
+    /// Clear key not to be left in freed memory, with volatile write not to be optimized out.
+    fn drop(&mut self) {
+        for b in self.0.iter_mut() {
+            // SAFETY: `b` is valid and aligned reference to byte of the key.
+            unsafe { std::ptr::write_volatile(b, 0) };
+        }
+        std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+/// Keys of locked topics unlocked in this session.
+#[derive(Default)]
+pub struct Keys {
+    keys: Mutex<HashMap<String, Arc<Key>>>,
+}
+
+impl Keys {
+    /// Shared key, which is cleared when the last reference is dropped after removed.
+    pub fn get(&self, topic_id: &str) -> Option<Arc<Key>> {
+        self.lock().get(topic_id).cloned()
+    }
+
+    pub fn insert(&self, topic_id: &str, key: Key) {
+        self.lock().insert(topic_id.to_owned(), Arc::new(key));
+    }
+
+    pub fn remove(&self, topic_id: &str) {
+        self.lock().remove(topic_id);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<Key>>> {
+        self.keys.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+pub fn random<const N: usize>() -> Result<[u8; N], Error> {
+    let mut bytes = [0; N];
+    getrandom::fill(&mut bytes).map_err(|e| Error::Internal(e.to_string()))?;
+    Ok(bytes)
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let salt = random::<SALT_LEN>().unwrap();
+        let key = Key::derive("secret", &salt).unwrap();
+
+        let encrypted = key.encrypt("# title\ncontent").unwrap();
+        assert!(encrypted.starts_with(PREFIX));
+        assert!(!encrypted.contains("title"));
+        assert_ne!(encrypted, key.encrypt("# title\ncontent").unwrap());
+        assert_eq!("# title\ncontent", key.decrypt(&encrypted).unwrap());
+
+        let wrong = Key::derive("wrong", &salt).unwrap();
+        assert!(matches!(wrong.decrypt(&encrypted), Err(Error::Passphrase)));
+        assert!(matches!(key.decrypt("plain"), Err(Error::Internal(_))));
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_bytes() {
+        let salt = random::<SALT_LEN>().unwrap();
+        let key = Key::derive("secret", &salt).unwrap();
+
+        let encrypted = key.encrypt_bytes(&[0, 1, 2]).unwrap();
+        assert_eq!(NONCE_LEN + 3 + 16, encrypted.len());
+        assert_eq!(vec![0, 1, 2], key.decrypt_bytes(&encrypted).unwrap());
+
+        let wrong = Key::derive("wrong", &salt).unwrap();
+        assert!(matches!(
+            wrong.decrypt_bytes(&encrypted),
+            Err(Error::Passphrase)
+        ));
+        assert!(matches!(key.decrypt_bytes(&[0]), Err(Error::Internal(_))));
+    }
+
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(Some(vec![0, 255, 16]), decode_hex("00ff10"));
+        assert_eq!(None, decode_hex("0"));
+        assert_eq!(None, decode_hex("zz"));
+    }
+}
@@ -0,0 +1,393 @@
+use crate::error::Error;
+use crate::html::{self, escape};
+use crate::markdown::{self, WikiLink};
+use crate::obsidian::sanitize_file_name;
+use crate::{db, AppData};
+use chrono::{DateTime, Local};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+const STYLE: &str = r#"
+body {
+  margin: 0;
+  color: #222222;
+  background-color: #ffffff;
+  font-family: sans-serif;
+  line-height: 1.6;
+}
+
+header {
+  padding: 10px 20px;
+  color: #ffffff;
+  background-color: #222222;
+}
+
+header a {
+  color: #ffffff;
+  text-decoration: none;
+}
+
+main {
+  max-width: 800px;
+  margin: 0 auto;
+  padding: 10px 20px;
+}
+
+time {
+  color: #888888;
+  font-size: small;
+}
+
+ul.tags {
+  padding: 0;
+}
+
+ul.tags li {
+  display: inline-block;
+  margin: 2px;
+  padding: 0px 8px;
+  border-radius: 30px;
+  background-color: #008800;
+}
+
+ul.tags li a {
+  color: #ffffff;
+  text-decoration: none;
+}
+
+img {
+  max-width: 100%;
+}
+
+pre {
+  padding: 10px;
+  overflow-x: auto;
+  background-color: #eeeeee;
+}
+
+table {
+  border-collapse: collapse;
+}
+
+th,
+td {
+  padding: 3px 8px;
+  border: 1px solid #cccccc;
+}
+"#;
+
+const IMAGE_EXTENSIONS: [&str; 7] = ["apng", "avif", "gif", "jpeg", "jpg", "png", "svg"];
+
+// -----------------------------------------------------------------------------------------------
+
+pub fn export_site_fn(data: &AppData, dir: &Path, keyword: &str) -> Result<usize, Error> {
+    let db = data.db.reader();
+    let mut topics = if keyword.is_empty() {
+        db::Topic::all(&db)
+    } else {
+        db::Topic::search(&db, keyword)
+    }?;
+
+    // Locked topics are never exported.
+    let locked = db::TopicLock::all(&db)?;
+    topics.retain(|t| !locked.iter().any(|l| l.topic_id == t.id));
+
+    topics.sort_unstable_by_key(|t| t.timestamp);
+    topics.reverse();
+
+    fs::create_dir_all(dir.join("topics"))?;
+    fs::create_dir_all(dir.join("tags"))?;
+    fs::write(dir.join("style.css"), STYLE.trim_start())?;
+
+    let mut exported = vec![];
+    let mut tagged: BTreeMap<String, Vec<&db::Topic>> = BTreeMap::new();
+    for topic in &topics {
+        let Ok(memo) = db::Memo::latest_by_topic(&db, &topic.id) else {
+            continue;
+        };
+
+        let attachment_dir = dir.join("attachments").join(&topic.id);
+        let mut files = HashMap::new();
+        for attachment in db::Attachment::all_by_topic(&db, &topic.id)? {
+            let file_name = sanitize_file_name(&attachment.name);
+            fs::create_dir_all(&attachment_dir)?;
+            fs::write(attachment_dir.join(&file_name), &attachment.content)?;
+
+            let url = format!(
+                "../attachments/{}/{}",
+                html::encode_url(&topic.id),
+                html::encode_url(&file_name)
+            );
+            files.insert(attachment.name, url);
+        }
+
+        let content = resolve_links(&memo.content, &topics, &files);
+        let (body, _) = html::render(&content);
+
+        let tags = db::TopicTag::all_by_topic(&db, &topic.id)?;
+        for tag in &tags {
+            tagged.entry(tag.clone()).or_default().push(topic);
+        }
+
+        let page = topic_page(topic, &tags, &body);
+        fs::write(dir.join("topics").join(topic_file_name(topic)), page)?;
+        exported.push(topic);
+    }
+
+    for (tag, tag_topics) in &tagged {
+        let title = format!("#{tag}");
+        let list = topic_list(tag_topics, "../");
+        let page = page(
+            &title,
+            "../",
+            &format!("<h1>{}</h1>\n{list}", escape(&title)),
+        );
+        fs::write(dir.join("tags").join(tag_file_name(tag)), page)?;
+    }
+
+    fs::write(dir.join("index.html"), index_page(&exported, &tagged))?;
+
+    Ok(exported.len())
+}
+
+fn resolve_links(content: &str, topics: &[db::Topic], files: &HashMap<String, String>) -> String {
+    let content = markdown::replace_wiki_links(content, |link| {
+        // Label is escaped the same as title.
+        let label = html::escape_markdown(link.label.as_ref().unwrap_or(&link.target));
+        if let Some(url) = files.get(&link.target) {
+            return Some(attachment_link(link, &label, url));
+        }
+
+        match db::Topic::resolve(topics, &link.target) {
+            Some(t) => {
+                let anchor = link
+                    .anchor
+                    .as_ref()
+                    .map(|a| format!("#{}", html::slug(a)))
+                    .unwrap_or_default();
+                let url = html::encode_url(&topic_file_name(t));
+                Some(format!("[{label}]({url}{anchor})"))
+            }
+            None => Some(label),
+        }
+    });
+
+    markdown::replace_image_links(&content, |url| {
+        files.get(&url.replace("%20", " ")).map(|u| u.to_owned())
+    })
+}
+
+fn attachment_link(link: &WikiLink, label: &str, url: &str) -> String {
+    let is_image = link
+        .target
+        .rsplit_once('.')
+        .is_some_and(|(_, e)| IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()));
+
+    if link.embed && is_image {
+        format!("![{label}]({url})")
+    } else {
+        format!("[{label}]({url})")
+    }
+}
+
+// -----------------------------------------------------------------------------------------------
+
+fn index_page(topics: &[&db::Topic], tagged: &BTreeMap<String, Vec<&db::Topic>>) -> String {
+    let mut body = String::new();
+
+    body.push_str("<h1>Topics</h1>\n");
+    body.push_str(&topic_list(topics, ""));
+
+    if !tagged.is_empty() {
+        body.push_str("<h1>Tags</h1>\n<ul class=\"tags\">\n");
+        for (tag, tag_topics) in tagged {
+            body.push_str(&format!(
+                "<li><a href=\"tags/{}\">#{}</a> ({})</li>\n",
+                html::encode_url(&tag_file_name(tag)),
+                escape(tag),
+                tag_topics.len()
+            ));
+        }
+        body.push_str("</ul>\n");
+    }
+
+    page("Markdown MEMO", "", &body)
+}
+
+fn topic_page(topic: &db::Topic, tags: &[String], content: &str) -> String {
+    let mut body = String::new();
+
+    body.push_str(&format!("<p>{}</p>\n", time(topic.timestamp)));
+
+    if !tags.is_empty() {
+        body.push_str("<ul class=\"tags\">\n");
+        for tag in tags {
+            body.push_str(&format!(
+                "<li><a href=\"../tags/{}\">#{}</a></li>\n",
+                html::encode_url(&tag_file_name(tag)),
+                escape(tag)
+            ));
+        }
+        body.push_str("</ul>\n");
+    }
+
+    body.push_str("<article>\n");
+    body.push_str(content);
+    body.push_str("</article>\n");
+
+    page(&topic.title, "../", &body)
+}
+
+fn topic_list(topics: &[&db::Topic], root: &str) -> String {
+    let mut list = "<ul>\n".to_owned();
+    for topic in topics {
+        list.push_str(&format!(
+            "<li><a href=\"{root}topics/{}\">{}</a> {}</li>\n",
+            html::encode_url(&topic_file_name(topic)),
+            escape(&topic.title),
+            time(topic.timestamp)
+        ));
+    }
+    list.push_str("</ul>\n");
+    list
+}
+
+fn page(title: &str, root: &str, body: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{}</title>
+<link rel="stylesheet" href="{root}style.css">
+</head>
+<body>
+<header><a href="{root}index.html">Markdown MEMO</a></header>
+<main>
+{body}</main>
+</body>
+</html>
+"#,
+        escape(title)
+    )
+}
+
+fn time(timestamp: i64) -> String {
+    let datetime = DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .with_timezone(&Local);
+    format!(
+        "<time datetime=\"{}\">{}</time>",
+        datetime.to_rfc3339(),
+        datetime.format("%Y-%m-%d %H:%M")
+    )
+}
+
+fn topic_file_name(topic: &db::Topic) -> String {
+    format!("{}.html", sanitize_file_name(&topic.id))
+}
+
+fn tag_file_name(tag: &str) -> String {
+    let name = tag
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c.to_string()
+            } else {
+                format!("~{:x}", c as u32)
+            }
+        })
+        .collect::<String>();
+    format!("{name}.html")
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{setup_connect, temp_dir};
+
+    #[test]
+    fn test_export_site_fn() {
+        let data = setup_appdate();
+        let dir = temp_dir();
+
+        let count = export_site_fn(&data, &dir, "").unwrap();
+        assert_eq!(2, count);
+
+        let index = fs::read_to_string(dir.join("index.html")).unwrap();
+        assert!(index.contains("<a href=\"topics/t1.html\">title1</a>"));
+        assert!(index.contains("<a href=\"topics/t2.html\">title2</a>"));
+        assert!(index.contains("<a href=\"tags/a~2fb.html\">#a/b</a> (1)"));
+        assert!(!index.contains("t3.html"));
+
+        let t1 = fs::read_to_string(dir.join("topics").join("t1.html")).unwrap();
+        assert!(t1.contains("<h1 id=\"h\">h</h1>"));
+        assert!(t1.contains("<a href=\"t2.html#x-y\">to t2</a>"));
+        assert!(t1.contains("<img src=\"../attachments/t1/a%20b.png\" alt=\"a b.png\" />"));
+        assert!(t1.contains("<a href=\"../attachments/t1/c.pdf\">c.pdf</a>"));
+        assert!(t1.contains("missing"));
+        assert!(t1.contains("<a href=\"t2.html\">&lt;b&gt;x&lt;/b&gt; *y*</a>"));
+        assert!(t1.contains("<a href=\"../tags/a~2fb.html\">#a/b</a>"));
+
+        let t2 = fs::read_to_string(dir.join("topics").join("t2.html")).unwrap();
+        assert!(t2.contains("<img src=\"a%20b.png\" alt=\"x\" />"));
+        assert!(t2.contains("<a href=\"t1.html\">title1</a>"));
+
+        let tag = fs::read_to_string(dir.join("tags").join("a~2fb.html")).unwrap();
+        assert!(tag.contains("<a href=\"../topics/t1.html\">title1</a>"));
+
+        let png = fs::read(dir.join("attachments").join("t1").join("a b.png")).unwrap();
+        assert_eq!(b"png".to_vec(), png);
+        assert!(dir.join("style.css").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_export_site_fn_keyword() {
+        let data = setup_appdate();
+        let dir = temp_dir();
+
+        let count = export_site_fn(&data, &dir, "#a/b").unwrap();
+        assert_eq!(1, count);
+
+        // Link to not exported topic is rendered as text.
+        let t1 = fs::read_to_string(dir.join("topics").join("t1.html")).unwrap();
+        assert!(!t1.contains("t2.html"));
+        assert!(t1.contains("to t2"));
+        assert!(!dir.join("topics").join("t2.html").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_tag_file_name() {
+        assert_eq!("a-b_c.html", tag_file_name("a-b_c"));
+        assert_eq!("a~2fb~20.html", tag_file_name("a/b "));
+    }
+
+    fn setup_appdate() -> AppData {
+        let conn = setup_connect();
+        db::Topic::create(&conn, "t1", "title1", 1).unwrap();
+        db::Topic::create(&conn, "t2", "title2", 0).unwrap();
+        db::Topic::create(&conn, "t3", "title3", 0).unwrap();
+        db::Memo::create(
+            &conn,
+            "m1",
+            "t1",
+            1,
+            "# h\n[[t2#X Y|to t2]] ![[a b.png]] ![[c.pdf]] [[missing]] [[t2|<b>x</b> *y*]]\n",
+        )
+        .unwrap();
+        db::Memo::create(&conn, "m2", "t2", 0, "## X Y\n[[title1]] ![x](a%20b.png)\n").unwrap();
+        db::TopicTag::create(&conn, "a/b", "t1").unwrap();
+        db::Attachment::create(&conn, "a1", "t1", "a b.png", b"png").unwrap();
+        db::Attachment::create(&conn, "a2", "t1", "c.pdf", b"pdf").unwrap();
+
+        AppData::new(conn)
+    }
+}
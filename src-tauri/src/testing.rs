@@ -0,0 +1,38 @@
+use crate::db::create_table_if_not_exists;
+use crate::AppData;
+use rusqlite::Connection;
+use std::fs;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// In-memory database with tables created.
+pub fn setup_connect() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    create_table_if_not_exists(&conn).unwrap();
+    conn
+}
+
+pub fn setup_appdate() -> AppData {
+    AppData::new(setup_connect())
+}
+
+/// Unique empty directory in temp directory.
+pub fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Unique database path in temp directory, remove it by `remove_db`.
+pub fn temp_db_path() -> PathBuf {
+    std::env::temp_dir().join(format!("{}.db", Uuid::new_v4()))
+}
+
+/// Remove database file with its WAL files.
+pub fn remove_db(path: &Path) {
+    for suffix in ["", "-wal", "-shm"] {
+        let mut p = path.as_os_str().to_owned();
+        p.push(suffix);
+        let _ = fs::remove_file(p);
+    }
+}
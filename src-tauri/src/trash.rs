@@ -0,0 +1,277 @@
+use crate::api::{sync_inline_tags, topic_model};
+use crate::error::Error;
+use crate::event::Event;
+use crate::{db, model, AppData};
+use rusqlite::{Connection, TransactionBehavior};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+const DAY_SECS: i64 = 24 * 60 * 60;
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Deleted memos with title of their topics, recently deleted first.
+pub fn get_trash_fn(data: &AppData) -> Result<Vec<model::TrashMemo>, Error> {
+    let db = data.db.reader();
+
+    let topics = db::Topic::all(&db)?;
+    let deleted: Vec<_> = db::TrashTopic::all(&db)?
+        .into_iter()
+        .map(|t| db::Topic {
+            id: t.id,
+            title: t.title,
+            timestamp: t.timestamp,
+        })
+        .collect();
+    let locks = db::TopicLock::all(&db)?;
+
+    let mut models = vec![];
+    for memo in db::TrashMemo::all(&db)? {
+        let trashed = deleted.iter().find(|t| t.id == memo.topic_id);
+        let locked = locks.iter().any(|l| l.topic_id == memo.topic_id);
+        let title = trashed
+            .or_else(|| topics.iter().find(|t| t.id == memo.topic_id))
+            .map(|t| topic_model(t, locked, &data.keys).title)
+            .unwrap_or_default();
+
+        models.push(model::TrashMemo {
+            id: memo.id,
+            topic_id: memo.topic_id,
+            title,
+            timestamp: memo.timestamp,
+            deleted_at: memo.deleted_at,
+            topic_deleted: trashed.is_some(),
+        });
+    }
+
+    Ok(models)
+}
+
+/// Restore memo of `id`, or all deleted memos of topic if `None`, with topic if deleted.
+pub fn restore_trash_fn(data: &AppData, topic_id: &str, id: Option<&str>) -> Result<usize, Error> {
+    let mut db = data.db.writer();
+    let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let memos: Vec<_> = db::TrashMemo::all_by_topic(&tx, topic_id)?
+        .into_iter()
+        .filter(|m| id.is_none() || id == Some(m.id.as_str()))
+        .collect();
+    if memos.is_empty() {
+        return Err(Error::NotFound(id.unwrap_or(topic_id).to_owned()));
+    }
+
+    for memo in &memos {
+        memo.restore(&tx)?;
+        Event::MemoSaved {
+            topic_id: topic_id.to_owned(),
+            memo_id: memo.id.clone(),
+        }
+        .record(&tx)?;
+    }
+
+    if let Some(topic) = db::TrashTopic::all(&tx)?
+        .into_iter()
+        .find(|t| t.id == topic_id)
+    {
+        topic.restore(&tx)?;
+        Event::TopicCreated {
+            topic_id: topic.id,
+            title: topic.title,
+        }
+        .record(&tx)?;
+    }
+
+    // Restored revision may be the latest, whose inline tags are synced.
+    if data.inline_tags && db::TopicLock::find(&tx, topic_id)?.is_none() {
+        let latest = db::Memo::latest_by_topic(&tx, topic_id)?;
+        sync_inline_tags(&tx, topic_id, &latest.content)?;
+    }
+
+    tx.commit()?;
+    log::debug!("restored memo topic_id={topic_id} count={}", memos.len());
+    Ok(memos.len())
+}
+
+/// Delete memos in trash permanently, only of topic if specified.
+pub fn purge_trash_fn(data: &AppData, topic_id: Option<&str>) -> Result<usize, Error> {
+    let mut db = data.db.writer();
+    let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let count = purge(&tx, |id, _| topic_id.is_none() || topic_id == Some(id))?;
+
+    tx.commit()?;
+    Ok(count)
+}
+
+/// Delete memos in trash deleted more than `days` ago.
+pub fn purge_expired_fn(data: &AppData, days: u64) -> Result<usize, Error> {
+    let duration = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    let before = duration.as_secs() as i64 - days as i64 * DAY_SECS;
+
+    let mut db = data.db.writer();
+    let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let count = purge(&tx, |_, deleted_at| deleted_at < before)?;
+
+    tx.commit()?;
+    Ok(count)
+}
+
+/// Purge expired memos now and every interval on background thread, for long running app.
+pub fn schedule(data: AppData, days: u64) {
+    thread::spawn(move || loop {
+        match purge_expired_fn(&data, days) {
+            Ok(count) => log::info!("purge expired trash days={days} count={count}"),
+            Err(e) => log::warn!("purge expired trash error={e}"),
+        }
+
+        thread::sleep(PURGE_INTERVAL);
+    });
+}
+
+/// Purge memos and topics matched by topic id and deletion time.
+fn purge<F>(db: &Connection, matches: F) -> Result<usize, Error>
+where
+    F: Fn(&str, i64) -> bool,
+{
+    let mut count = 0;
+    for memo in db::TrashMemo::all(db)? {
+        if matches(&memo.topic_id, memo.deleted_at) {
+            memo.purge(db)?;
+            count += 1;
+        }
+    }
+
+    let topics = db::Topic::all(db)?;
+    for topic in db::TrashTopic::all(db)? {
+        if !matches(&topic.id, topic.deleted_at) {
+            continue;
+        }
+
+        topic.purge(db)?;
+        // Lock is shared with topic recreated by the same id.
+        if !topics.iter().any(|t| t.id == topic.id) {
+            if let Some(lock) = db::TopicLock::find(db, &topic.id)? {
+                lock.delete(db)?;
+            }
+        }
+        log::debug!("purged topic topic_id={}", topic.id);
+    }
+
+    Ok(count)
+}
+
+// -----------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{
+        add_memo_tag_fn, create_memo_fn, delete_memo_fn, delete_topic_fn, get_memo_all_fn,
+        get_memo_tag_fn, get_topics_fn, lock_topic_fn, unlock_topic_fn,
+    };
+    use crate::testing::setup_appdate;
+
+    #[test]
+    fn test_restore_trash_fn() {
+        let data = setup_appdate();
+        let m1 = create_memo_fn(&data, "t1", "# title1").unwrap();
+        create_memo_fn(&data, "t1", "# title1\ncontent").unwrap();
+        add_memo_tag_fn(&data, "t1", "tag1").unwrap();
+
+        delete_memo_fn(&data, "t1", Some(&m1.id)).unwrap();
+        assert_eq!(1, get_memo_all_fn(&data, "t1").unwrap().len());
+
+        delete_topic_fn(&data, "t1").unwrap();
+        assert!(get_topics_fn(&data, "").unwrap().is_empty());
+        assert!(get_topics_fn(&data, "#tag1").unwrap().is_empty());
+
+        let trash = get_trash_fn(&data).unwrap();
+        assert_eq!(2, trash.len());
+        assert!(trash.iter().all(|m| m.title == "title1" && m.topic_deleted));
+
+        assert_eq!(1, restore_trash_fn(&data, "t1", Some(&m1.id)).unwrap());
+        assert_eq!(1, get_topics_fn(&data, "#tag1").unwrap().len());
+        let tags = get_memo_tag_fn(&data, "t1").unwrap();
+        assert_eq!(1, tags.len());
+        assert_eq!("tag1", tags[0].name);
+
+        let trash = get_trash_fn(&data).unwrap();
+        assert_eq!(1, trash.len());
+        assert!(!trash[0].topic_deleted);
+
+        assert_eq!(1, restore_trash_fn(&data, "t1", None).unwrap());
+        assert_eq!(2, get_memo_all_fn(&data, "t1").unwrap().len());
+
+        let e = restore_trash_fn(&data, "t1", None).err();
+        assert!(matches!(e, Some(Error::NotFound(_))));
+    }
+
+    #[test]
+    fn test_purge_trash_fn() {
+        let data = setup_appdate();
+        create_memo_fn(&data, "t1", "# title1").unwrap();
+        create_memo_fn(&data, "t2", "# title2").unwrap();
+        lock_topic_fn(&data, "t2", "pass").unwrap();
+        unlock_topic_fn(&data, "t2", "pass").unwrap();
+        delete_topic_fn(&data, "t1").unwrap();
+        delete_topic_fn(&data, "t2").unwrap();
+
+        assert_eq!(0, purge_expired_fn(&data, 1).unwrap());
+        assert_eq!(1, purge_trash_fn(&data, Some("t2")).unwrap());
+        {
+            let db = data.db.reader();
+            assert!(db::TopicLock::find(&db, "t2").unwrap().is_none());
+        }
+
+        {
+            let db = data.db.writer();
+            db.execute(
+                "UPDATE trash_memo SET deleted_at = deleted_at - 2 * 86400",
+                [],
+            )
+            .unwrap();
+            db.execute(
+                "UPDATE trash_topic SET deleted_at = deleted_at - 2 * 86400",
+                [],
+            )
+            .unwrap();
+        }
+        assert_eq!(1, purge_expired_fn(&data, 1).unwrap());
+        assert!(get_trash_fn(&data).unwrap().is_empty());
+        {
+            let db = data.db.reader();
+            assert!(db::TrashTopic::all(&db).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_restore_trash_fn_inline_tags() {
+        let data = AppData {
+            inline_tags: true,
+            ..setup_appdate()
+        };
+        {
+            let conn = data.db.writer();
+            db::Topic::create(&conn, "t1", "title1", 0).unwrap();
+            db::Memo::create(&conn, "m1", "t1", 0, "#old").unwrap();
+            db::Memo::create(&conn, "m2", "t1", 1, "#new").unwrap();
+            sync_inline_tags(&conn, "t1", "#new").unwrap();
+        }
+
+        delete_memo_fn(&data, "t1", Some("m2")).unwrap();
+        assert_eq!(vec!["old"], tag_names(&data, "t1"));
+
+        restore_trash_fn(&data, "t1", Some("m2")).unwrap();
+        assert_eq!(vec!["new"], tag_names(&data, "t1"));
+    }
+
+    fn tag_names(data: &AppData, topic_id: &str) -> Vec<String> {
+        get_memo_tag_fn(data, topic_id)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.name)
+            .collect()
+    }
+}
@@ -1,4 +1,5 @@
 import { invoke } from "@tauri-apps/api/core";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
 
 export interface Memo {
   id: string;
@@ -12,12 +13,141 @@ export interface Topic {
   id: string;
   title: string;
   timestamp: number;
+  locked: boolean;
+}
+
+export interface Backup {
+  name: string;
+  timestamp: number;
+  size: number;
+}
+
+export interface CheckReport {
+  integrity: string[];
+  missing_topics: string[];
+  orphan_tags: string[];
+  empty_topics: string[];
+  repaired: boolean;
+}
+
+export interface DatabaseStats {
+  size: number;
+  free: number;
+  topics: number;
+  revisions: number;
+  tables: { name: string; size: number }[];
+  largest_topics: {
+    id: string;
+    title: string;
+    revisions: number;
+    size: number;
+  }[];
+}
+
+export interface Maintenance {
+  before: DatabaseStats;
+  after: DatabaseStats | null;
+}
+
+export interface Tag {
+  name: string;
+  count: number;
+  // Timestamp of the latest updated topic of tag, not when tag was added.
+  last_used: number;
+}
+
+export interface TopicTag {
+  name: string;
+  inline: boolean;
+  color: string | null;
+  description: string | null;
+  aliases: string[];
+}
+
+export interface TrashMemo {
+  id: string;
+  topic_id: string;
+  title: string;
+  timestamp: number;
+  deleted_at: number;
+  topic_deleted: boolean;
+}
+
+export interface AppError {
+  code:
+    | "cancelled"
+    | "database"
+    | "internal"
+    | "invalid"
+    | "io"
+    | "lock"
+    | "not_found"
+    | "passphrase";
+  message: string;
+  context: string[];
+}
+
+export type ChangeEvent =
+  | { kind: "topic-created"; topic_id: string; title: string }
+  | { kind: "memo-saved"; topic_id: string; memo_id: string }
+  | { kind: "memo-deleted"; topic_id: string; memo_id: string }
+  | { kind: "tag-added"; topic_id: string; tag: string }
+  | { kind: "tag-removed"; topic_id: string; tag: string }
+  | { kind: "topic-deleted"; topic_id: string }
+  | { kind: "database-changed" };
+
+const changeEvents: ChangeEvent["kind"][] = [
+  "topic-created",
+  "memo-saved",
+  "memo-deleted",
+  "tag-added",
+  "tag-removed",
+  "topic-deleted",
+  "database-changed",
+];
+
+export async function listenChanges(
+  handler: (event: ChangeEvent) => void,
+): Promise<UnlistenFn> {
+  const unlistens = await Promise.all(
+    changeEvents.map((name) =>
+      listen<ChangeEvent>(name, (e) => handler(e.payload)),
+    ),
+  );
+  return () => {
+    for (const unlisten of unlistens) {
+      unlisten();
+    }
+  };
+}
+
+// Whether event may change topic, external change may change any topic.
+export function isTopicChanged(event: ChangeEvent, topicId: string): boolean {
+  return !("topic_id" in event) || event.topic_id === topicId;
+}
+
+export async function listenOpenTopic(
+  handler: (topicId: string) => void,
+): Promise<UnlistenFn> {
+  return await listen<string>("open-topic", (e) => handler(e.payload));
 }
 
 export async function addMemoTag(topicId: string, tag: string) {
   return await invoke("add_memo_tag", { topicId, tag });
 }
 
+export async function backupNow(): Promise<Backup> {
+  return await invoke("backup_now");
+}
+
+export async function checkDatabase(repair: boolean): Promise<CheckReport> {
+  return await invoke("check_database", { repair });
+}
+
+export async function completeTag(input: string): Promise<string[]> {
+  return await invoke("complete_tag", { input });
+}
+
 export async function createMemo(
   topicId: string,
   content: string,
@@ -29,10 +159,40 @@ export async function deleteMemo(topicId: string, id: string): Promise<number> {
   return await invoke("delete_memo", { topicId, id });
 }
 
+export async function deleteTag(name: string): Promise<number> {
+  return await invoke("delete_tag", { name });
+}
+
 export async function deleteTopic(topicId: string): Promise<number> {
   return await invoke("delete_topic", { topicId });
 }
 
+export async function exportEpub(
+  path: string,
+  keyword: string,
+  language: string = navigator.language,
+): Promise<number> {
+  return await invoke("export_epub", { path, keyword, language });
+}
+
+export async function exportObsidian(
+  path: string,
+  keyword: string,
+): Promise<number> {
+  return await invoke("export_obsidian", { path, keyword });
+}
+
+export async function exportSite(
+  path: string,
+  keyword: string,
+): Promise<number> {
+  return await invoke("export_site", { path, keyword });
+}
+
+export async function getAllTags(): Promise<Tag[]> {
+  return await invoke("get_all_tags");
+}
+
 export async function getMemo(topicId: string, id?: string): Promise<Memo> {
   return await invoke("get_memo", { topicId, id });
 }
@@ -41,7 +201,7 @@ export async function getMemoHistory(topicId: string): Promise<Memo[]> {
   return await invoke("get_memo_all", { topicId });
 }
 
-export async function getMemoTag(topicId: string): Promise<string[]> {
+export async function getMemoTag(topicId: string): Promise<TopicTag[]> {
   return await invoke("get_memo_tag", { topicId });
 }
 
@@ -49,10 +209,88 @@ export async function getTopics(keyword: string): Promise<Topic[]> {
   return await invoke("get_topics", { keyword });
 }
 
+export async function getTrash(): Promise<TrashMemo[]> {
+  return await invoke("get_trash");
+}
+
+export async function importObsidian(path: string): Promise<number> {
+  return await invoke("import_obsidian", { path });
+}
+
+export async function isLocked(): Promise<boolean> {
+  return await invoke("is_locked");
+}
+
+export async function listBackups(): Promise<Backup[]> {
+  return await invoke("list_backups");
+}
+
+export async function lockTopic(topicId: string, passphrase: string) {
+  return await invoke("lock_topic", { topicId, passphrase });
+}
+
+export async function maintainDatabase(
+  dryRun: boolean,
+): Promise<Maintenance> {
+  return await invoke("maintain_database", { dryRun });
+}
+
+export async function mergeTag(from: string, into: string): Promise<number> {
+  return await invoke("merge_tag", { from, into });
+}
+
+export async function purgeTrash(topicId?: string): Promise<number> {
+  return await invoke("purge_trash", { topicId });
+}
+
+export async function relockTopic(topicId: string) {
+  return await invoke("relock_topic", { topicId });
+}
+
 export async function removeMemoTag(topicId: string, tag: string) {
   return await invoke("remove_memo_tag", { topicId, tag });
 }
 
+export async function removeTopicLock(topicId: string, passphrase: string) {
+  return await invoke("remove_topic_lock", { topicId, passphrase });
+}
+
+export async function renameTag(from: string, to: string): Promise<number> {
+  return await invoke("rename_tag", { from, to });
+}
+
+export async function restoreBackup(name: string): Promise<Backup> {
+  return await invoke("restore_backup", { name });
+}
+
+export async function restoreTrash(
+  topicId: string,
+  id?: string,
+): Promise<number> {
+  return await invoke("restore_trash", { topicId, id });
+}
+
+export async function setTag(
+  name: string,
+  color: string | null,
+  description: string | null,
+  aliases: string[] | null,
+) {
+  return await invoke("set_tag", { name, color, description, aliases });
+}
+
+export async function takeLaunchTopic(): Promise<string | null> {
+  return await invoke("take_launch_topic");
+}
+
+export async function unlock(passphrase: string) {
+  return await invoke("unlock", { passphrase });
+}
+
+export async function unlockTopic(topicId: string, passphrase: string) {
+  return await invoke("unlock_topic", { topicId, passphrase });
+}
+
 /*
 // prototype.
 const memos: Memo[] = [];
@@ -2,7 +2,12 @@ import { Task } from "@lit/task";
 import { css, html } from "lit";
 import { customElement, property } from "lit/decorators.js";
 import type { Memo } from "./api";
-import { deleteMemo, getMemoHistory } from "./api";
+import {
+  deleteMemo,
+  getMemoHistory,
+  isTopicChanged,
+  listenChanges,
+} from "./api";
 import type { MemoCard } from "./memo-card";
 import { MemoElement } from "./memo-element";
 import "./memo-card";
@@ -24,6 +29,31 @@ export class ContentHistory extends MemoElement {
 
   currentHistoryId: string | undefined;
 
+  override connectedCallback() {
+    super.connectedCallback();
+
+    // Reload history changed by this window, CLI or HTTP API.
+    listenChanges((e) => {
+      if (isTopicChanged(e, this.topicId)) {
+        this.loadTask.run();
+      }
+    }).then((unlisten) => {
+      if (this.isConnected) {
+        this.unlisten = unlisten;
+      } else {
+        unlisten();
+      }
+    });
+  }
+
+  override disconnectedCallback() {
+    super.disconnectedCallback();
+    this.unlisten?.();
+    this.unlisten = undefined;
+  }
+
+  private unlisten: (() => void) | undefined;
+
   override render() {
     return this.loadTask.render({
       initial: () => html`<div>${this.renderLoading()}</div>`,
@@ -1,8 +1,17 @@
 import { icon } from "@fortawesome/fontawesome-svg-core";
 import { Task } from "@lit/task";
 import { css, html } from "lit";
-import { customElement, property } from "lit/decorators.js";
-import { addMemoTag, getMemoTag, removeMemoTag } from "./api";
+import { customElement, property, state } from "lit/decorators.js";
+import { styleMap } from "lit/directives/style-map.js";
+import type { TopicTag } from "./api";
+import {
+  addMemoTag,
+  completeTag,
+  getMemoTag,
+  isTopicChanged,
+  listenChanges,
+  removeMemoTag,
+} from "./api";
 import { MemoElement } from "./memo-element";
 
 @customElement("content-tag")
@@ -24,6 +33,10 @@ export class ContentTag extends MemoElement {
         background-color: #0000ff;
       }
 
+      span.inline::before {
+        content: "#";
+      }
+
       span {
         margin: 0px 5px;
       }
@@ -50,6 +63,16 @@ export class ContentTag extends MemoElement {
         cursor: pointer;
         color: #cccccc;
       }
+
+      ul {
+        margin: 0px 5px;
+        padding: 0px;
+        list-style: none;
+      }
+
+      li {
+        cursor: pointer;
+      }
     `,
   ];
 
@@ -59,6 +82,21 @@ export class ContentTag extends MemoElement {
   @property()
   name: string = "";
 
+  @property()
+  color: string | null = null;
+
+  @property()
+  description: string | null = null;
+
+  @property()
+  inline: boolean = false;
+
+  @state()
+  private suggestions: string[] = [];
+
+  // Incremented by each input and pick, to drop responses of older inputs.
+  private suggestSeq = 0;
+
   override render() {
     const content =
       this.name === ""
@@ -66,8 +104,13 @@ export class ContentTag extends MemoElement {
             contenteditable="true"
             class="add"
             @keydown=${this.enterTag}
+            @input=${this.suggestTag}
           ></span> `
-        : html`<span>${this.name}</span>`;
+        : html`<span
+            class=${this.inline ? "inline" : ""}
+            title=${this.description ?? ""}
+            >${this.name}</span
+          >`;
 
     const opIcon =
       this.name === ""
@@ -76,8 +119,17 @@ export class ContentTag extends MemoElement {
 
     const clickOp = this.name === "" ? this.addTag : this.removeTag;
 
+    const suggestions = this.suggestions.map(
+      (s) => html`<li @click=${() => this.pickTag(s)}>${s}</li>`,
+    );
+
     return html`
-      <p>${content} <span @click=${clickOp}>${opIcon.node[0]}</span></p>
+      <p style=${styleMap({ backgroundColor: this.color ?? undefined })}>
+        ${content} <span @click=${clickOp}>${opIcon.node[0]}</span>
+      </p>
+      <ul>
+        ${suggestions}
+      </ul>
     `;
   }
 
@@ -103,15 +155,37 @@ export class ContentTag extends MemoElement {
       return;
     }
 
+    this.suggestSeq++;
+    this.suggestions = [];
     this.dispatchTagAddRequestEvent(content);
   }
 
   private enterTag(e: KeyboardEvent) {
     if (e.type === "keydown" && e.key === "Enter") {
       this.addTag(e);
+    } else if (e.key === "Tab" && this.suggestions.length > 0) {
+      e.preventDefault();
+      this.pickTag(this.suggestions[0]);
+    }
+  }
+
+  private async suggestTag() {
+    const seq = ++this.suggestSeq;
+    const input = this.renderRoot.querySelector("span.add")!;
+    const suggestions = await completeTag(input.textContent.trim());
+    if (seq === this.suggestSeq) {
+      this.suggestions = suggestions;
     }
   }
 
+  // Existing tag is picked to avoid near-duplicate tags by typo.
+  private pickTag(tag: string) {
+    const input = this.renderRoot.querySelector("span.add")!;
+    input.textContent = tag;
+    this.suggestSeq++;
+    this.suggestions = [];
+  }
+
   private removeTag(e: MouseEvent) {
     e.preventDefault();
     e.stopPropagation();
@@ -143,6 +217,31 @@ export class ContentTags extends MemoElement {
   @property()
   topicId: string = "";
 
+  override connectedCallback() {
+    super.connectedCallback();
+
+    // Reload tags changed by this window, CLI or HTTP API.
+    listenChanges((e) => {
+      if (isTopicChanged(e, this.topicId)) {
+        this.loadTask.run();
+      }
+    }).then((unlisten) => {
+      if (this.isConnected) {
+        this.unlisten = unlisten;
+      } else {
+        unlisten();
+      }
+    });
+  }
+
+  override disconnectedCallback() {
+    super.disconnectedCallback();
+    this.unlisten?.();
+    this.unlisten = undefined;
+  }
+
+  private unlisten: (() => void) | undefined;
+
   override render() {
     return this.loadTask.render({
       initial: () => html`<div>${this.renderLoading()}</div>`,
@@ -190,11 +289,17 @@ export class ContentTags extends MemoElement {
     args: () => [this.topicId],
   });
 
-  private renderTags(tags: string[]) {
+  private renderTags(tags: TopicTag[]) {
     const cards = [];
     for (const tag of tags) {
       cards.push(html`
-        <content-tag topicId="${this.topicId}" name="${tag}"></memo-card>
+        <content-tag
+          topicId="${this.topicId}"
+          name="${tag.name}"
+          .color=${tag.color}
+          .description=${tag.description}
+          .inline=${tag.inline}
+        ></content-tag>
       `);
     }
 
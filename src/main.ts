@@ -1,12 +1,21 @@
+import { getCurrentWindow } from "@tauri-apps/api/window";
 import { css, html } from "lit";
 import { customElement } from "lit/decorators.js";
-import type { Memo } from "./api";
+import type { AppError, Memo } from "./api";
+import {
+  isLocked,
+  listenChanges,
+  listenOpenTopic,
+  takeLaunchTopic,
+  unlock,
+} from "./api";
 import type { ContentAttr } from "./content-attr";
 import type { ContentEditor } from "./content-editor";
 import type { MemoCard } from "./memo-card";
 import { MemoElement } from "./memo-element";
 import type { NavigationMenu } from "./navigation-menu";
 import type { OperationBar } from "./operation-bar";
+import { askPassphrase } from "./passphrase";
 import "./content-attr";
 import "./content-editor";
 import "./navigation-menu";
@@ -156,6 +165,30 @@ export class MemoApp extends MemoElement {
       editor.refresh();
     });
 
+    // Topic specified by arguments of launch or second launch.
+    const openTopic = (topicId: string) => {
+      if (!editor.modified || window.confirm("Discard changes ?")) {
+        opBar.setStatus(false);
+        editor.topicId = topicId;
+        editor.basecardId = undefined;
+        attr.refresh(topicId, undefined);
+        navMenu.refresh(topicId);
+      }
+    };
+    listenOpenTopic(openTopic);
+    takeLaunchTopic().then((topicId) => {
+      if (topicId) {
+        openTopic(topicId);
+      }
+    });
+
+    listenChanges((e) => {
+      // Revisions may be changed by other process.
+      if (e.kind === "database-changed") {
+        attr.refresh(editor.topicId, editor.basecardId);
+      }
+    });
+
     this.addEventListener("mm-collapsed-changed", () => {
       // Do not use `getBoundingClientRect`.
       // Because element width not changed at this timing.
@@ -166,3 +199,28 @@ export class MemoApp extends MemoElement {
     });
   }
 }
+
+// Ask passphrase of encrypted database before loading topics.
+async function unlockDatabase(): Promise<boolean> {
+  while (await isLocked()) {
+    const passphrase = await askPassphrase("Passphrase");
+    if (passphrase === null) {
+      // Nothing to show without database.
+      await getCurrentWindow().close();
+      return false;
+    }
+
+    try {
+      await unlock(passphrase);
+    } catch (error) {
+      window.alert((error as AppError).message);
+    }
+  }
+  return true;
+}
+
+unlockDatabase().then((unlocked) => {
+  if (unlocked) {
+    document.body.appendChild(document.createElement("memo-app"));
+  }
+});
@@ -2,10 +2,11 @@ import { icon } from "@fortawesome/fontawesome-svg-core";
 import { Task } from "@lit/task";
 import { css, html } from "lit";
 import { customElement, property, state } from "lit/decorators.js";
-import type { Topic } from "./api";
-import { deleteTopic, getTopics } from "./api";
+import type { AppError, Topic } from "./api";
+import { deleteTopic, getTopics, listenChanges, unlockTopic } from "./api";
 import type { MemoCard } from "./memo-card";
 import { MemoElement } from "./memo-element";
+import { askPassphrase } from "./passphrase";
 import "./memo-card";
 
 @customElement("navigation-menu")
@@ -60,6 +61,8 @@ export class NavigationMenu extends MemoElement {
 
   currentTopicId: string | undefined;
 
+  private unlisten: (() => void) | undefined;
+
   override render() {
     const navClass = this.collapsed ? "collapsed" : "";
     const articleDisplay = this.collapsed ? "none" : "flex";
@@ -74,7 +77,8 @@ export class NavigationMenu extends MemoElement {
             initial: () => this.renderLoading(),
             pending: () => this.renderLoading(),
             complete: (topics) => this.renderTopics(topics),
-            error: (error) => html`<p>Error: ${error}</p>`,
+            error: (error) =>
+              html`<p>Error: ${(error as AppError).message}</p>`,
           })}
         </article>
         <div @click="${this.toggle}">${expander.node[0]}</div>
@@ -82,6 +86,25 @@ export class NavigationMenu extends MemoElement {
     `;
   }
 
+  override connectedCallback() {
+    super.connectedCallback();
+
+    // Reload topics changed by this window, CLI or HTTP API.
+    listenChanges(() => this.loadTask.run()).then((unlisten) => {
+      if (this.isConnected) {
+        this.unlisten = unlisten;
+      } else {
+        unlisten();
+      }
+    });
+  }
+
+  override disconnectedCallback() {
+    super.disconnectedCallback();
+    this.unlisten?.();
+    this.unlisten = undefined;
+  }
+
   override firstUpdated() {
     this.renderRoot.addEventListener("mm-memo-delete-request", (e) => {
       if (window.confirm("Delete ?")) {
@@ -105,11 +128,18 @@ export class NavigationMenu extends MemoElement {
     this.loadTask.run();
   }
 
-  private clickTopic(e: MouseEvent) {
+  private async clickTopic(e: MouseEvent) {
     e.preventDefault();
     e.stopPropagation();
 
     const target = e.target as MemoCard;
+    if (
+      this.lockedTopicIds.has(target.cardId) &&
+      !(await this.unlock(target))
+    ) {
+      return;
+    }
+
     if (this.dispatchTopicChangeEvent(target)) {
       this.currentTopicId = undefined;
 
@@ -128,9 +158,34 @@ export class NavigationMenu extends MemoElement {
     }
   }
 
+  // Ask passphrase of locked topic before opening it.
+  private async unlock(target: MemoCard): Promise<boolean> {
+    const passphrase = await askPassphrase("Passphrase of locked topic");
+    if (passphrase === null) {
+      return false;
+    }
+
+    try {
+      await unlockTopic(target.cardId, passphrase);
+    } catch (error) {
+      window.alert((error as AppError).message);
+      return false;
+    }
+
+    this.lockedTopicIds.delete(target.cardId);
+    this.loadTask.run();
+    return true;
+  }
+
+  // Locked topics whose titles are hidden until unlocked.
+  private lockedTopicIds = new Set<string>();
+
   private loadTask = new Task(this, {
     task: async ([keyword]) => {
       const topics = await getTopics(keyword);
+      this.lockedTopicIds = new Set(
+        topics.filter((t) => t.locked && t.title === "").map((t) => t.id),
+      );
       return topics;
     },
     args: () => [this.keyword],
@@ -142,7 +197,9 @@ export class NavigationMenu extends MemoElement {
       cards.push(html`
         <memo-card
           card-id="${topic.id}"
-          card-title="${topic.title}"
+          card-title="${topic.locked && topic.title === ""
+            ? "(locked)"
+            : topic.title}"
           timestamp="${topic.timestamp}"
           ?current="${this.currentTopicId === topic.id}"
           ?deletable="${true}"
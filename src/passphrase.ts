@@ -0,0 +1,37 @@
+// Ask passphrase with masked input, returns null when cancelled.
+export function askPassphrase(label: string): Promise<string | null> {
+  const dialog = document.createElement("dialog");
+  const form = document.createElement("form");
+  form.method = "dialog";
+
+  const text = document.createElement("label");
+  text.textContent = label;
+  const input = document.createElement("input");
+  input.type = "password";
+  input.autocomplete = "off";
+  input.required = true;
+  text.appendChild(input);
+
+  const ok = document.createElement("button");
+  ok.value = "ok";
+  ok.textContent = "OK";
+  const cancel = document.createElement("button");
+  cancel.type = "button";
+  cancel.textContent = "Cancel";
+  cancel.addEventListener("click", () => dialog.close());
+
+  form.append(text, ok, cancel);
+  dialog.appendChild(form);
+  document.body.appendChild(dialog);
+
+  return new Promise((resolve) => {
+    // Escape key closes dialog without return value, same as cancel.
+    dialog.addEventListener("close", () => {
+      const passphrase = dialog.returnValue === "ok" ? input.value : null;
+      input.value = "";
+      dialog.remove();
+      resolve(passphrase);
+    });
+    dialog.showModal();
+  });
+}
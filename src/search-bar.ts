@@ -49,7 +49,8 @@ export class SearchBar extends MemoElement {
   }
 
   override firstUpdated() {
-    this.keywordInput.addEventListener("change", () => {
+    // Search while typing, backend cancels superseded searches.
+    this.keywordInput.addEventListener("input", () => {
       this.dispatchKeywordChangeEvent(this.keywordInput.value);
     });
   }